@@ -0,0 +1,53 @@
+use std::sync::Arc;
+
+use pangpang::{storage, PangPang};
+
+
+
+
+fn usage() -> ! {
+    eprintln!("usage: pangpang-script [--inventory FILE | --inventory-script FILE | --ssh-config] SCRIPT");
+    std::process::exit(2);
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let (storage_args, script) = match args.split_last() {
+        Some((script, rest)) if !script.starts_with("--") => (rest, script.clone()),
+        _ => usage(),
+    };
+    let source = match std::fs::read_to_string(&script) {
+        Ok(s) => s,
+        Err(e) => {
+            eprintln!("read {} failed: {}", script, e);
+            std::process::exit(1);
+        }
+    };
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let code = rt.block_on(async move {
+        let cfg = match storage::open(storage_args.first().map(|s| s.as_str()), storage_args.get(1)) {
+            Ok(cfg) => cfg,
+            Err(e) => {
+                eprintln!("load storage failed: {:?}", e);
+                return 1;
+            }
+        };
+        let pp = Arc::new(PangPang::new(cfg));
+        let (tx, mut rx) = tokio::sync::mpsc::channel::<String>(64);
+        let printer = tokio::spawn(async move {
+            while let Some(line) = rx.recv().await {
+                println!("{}", line);
+            }
+        });
+        let result = pangpang::script::run(pp, source, tx).await;
+        let _ = printer.await;
+        match result {
+            Ok(()) => 0,
+            Err(e) => {
+                eprintln!("script failed: {:?}", e);
+                1
+            }
+        }
+    });
+    std::process::exit(code);
+}
@@ -7,10 +7,18 @@
 pub enum Error {
     StdIoError(std::io::Error),
     SshError(thrussh::Error),
+    SshKeyError(thrussh_keys::Error),
+    AuthenticationFailed(String),
     PpStreamError(String),
     WritePtyError(String),
     ReadPtyError(String),
     ProfileNotFound(String),
+    ReadOnlyStorage(String),
+    InvalidProfile(String),
+    ConfigParseError(String),
+    Unsupported(String),
+    InvalidPattern(String),
+    ScriptError(String),
 }
 
 
@@ -26,8 +34,14 @@ impl From<thrussh::Error> for Error {
     }
 }
 
-impl Into<std::io::Error> for Error {
-    fn into(self) -> std::io::Error {
+impl From<thrussh_keys::Error> for Error {
+    fn from(e: thrussh_keys::Error) -> Self {
+        Self::SshKeyError(e)
+    }
+}
+
+impl From<Error> for std::io::Error {
+    fn from(_: Error) -> Self {
         std::io::ErrorKind::Other.into()
     }
 }
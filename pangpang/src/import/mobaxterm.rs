@@ -0,0 +1,173 @@
+use std::path::Path;
+
+use crate::{errors, profile::Protocol};
+
+use super::{ImportFormat, ImportReport, home_dir, read_text, split_folder, ssh_profile, telnet_profile};
+
+
+
+
+const SESSION_TYPES: [&str; 15] = [
+    "SSH", "Telnet", "Rlogin", "RDP", "VNC", "FTP", "SFTP", "XDMCP",
+    "Serial", "File", "Shell", "Browser", "Mosh", "S3", "WSL",
+];
+
+pub fn import(path: &Path) -> Result<ImportReport, errors::Error> {
+    let mut report = ImportReport::new(ImportFormat::MobaXterm);
+    let mut folder: Vec<String> = Vec::new();
+    let mut in_bookmarks = false;
+    for line in read_text(path)?.lines() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        if line.starts_with('[') && line.ends_with(']') {
+            in_bookmarks = line[1..].starts_with("Bookmarks");
+            folder.clear();
+            continue;
+        }
+        if !in_bookmarks {
+            continue;
+        }
+        let (key, value) = match line.split_once('=') {
+            Some(kv) => kv,
+            None => continue,
+        };
+        match key {
+            "SubRep" => folder = split_folder(value),
+            "ImgNum" => {}
+            name => import_session(&mut report, name.to_string(), folder.clone(), value),
+        }
+    }
+    Ok(report)
+}
+
+fn import_session(report: &mut ImportReport, name: String, folder: Vec<String>, value: &str) {
+    let mut unsupported = Vec::new();
+    let settings: Vec<&str> = match value.split('#').nth(2) {
+        Some(s) => s.split('%').collect(),
+        None => {
+            unsupported.push("unrecognized session entry".to_string());
+            report.add(name, folder, None, unsupported);
+            return;
+        }
+    };
+    let field = |i: usize| settings.get(i).map(|s| s.trim()).filter(|s| !s.is_empty());
+    let kind = field(0).and_then(|k| k.parse::<usize>().ok()).unwrap_or(usize::MAX);
+    if kind == 1 {
+        match field(1) {
+            Some(h) => {
+                let port = field(2).and_then(|p| p.parse().ok()).unwrap_or(23);
+                let profile = telnet_profile(name.clone(), folder.clone(), h.to_string(), port);
+                report.add(name, folder, Some(profile), unsupported);
+            }
+            None => {
+                unsupported.push("session without host name".to_string());
+                report.add(name, folder, None, unsupported);
+            }
+        }
+        return;
+    }
+    if kind != 0 {
+        let kind = SESSION_TYPES.get(kind).copied().unwrap_or("unknown");
+        unsupported.push(format!("session type {}", kind));
+        report.add(name, folder, None, unsupported);
+        return;
+    }
+    let address = match field(1) {
+        Some(h) => h.to_string(),
+        None => {
+            unsupported.push("session without host name".to_string());
+            report.add(name, folder, None, unsupported);
+            return;
+        }
+    };
+    let port = field(2).and_then(|p| p.parse().ok()).unwrap_or(22);
+    let username = field(3).unwrap_or_default().to_string();
+    let mut profile = ssh_profile(name.clone(), folder.clone(), username, address, port);
+
+    if field(5) == Some("-1") {
+        unsupported.push("X11 forwarding".to_string());
+    }
+    if field(6) == Some("-1") {
+        unsupported.push("compression".to_string());
+    }
+    if let Some(cmd) = field(7) {
+        unsupported.push(format!("remote command {}", cmd));
+    }
+    if let Some(gateway) = field(8) {
+        let gw_port = field(9).and_then(|p| p.parse().ok()).unwrap_or(22);
+        let gw_user = field(10).unwrap_or_default().to_string();
+        profile.transport = Some(report.jump(folder.clone(), gw_user, gateway.to_string(), gw_port));
+    }
+    if let Some(key_file) = field(12) {
+        let key_file = key_file.replace("_ProfileDir_", &home_dir().to_string_lossy());
+        if key_file.to_lowercase().ends_with(".ppk") {
+            unsupported.push(format!("PuTTY private key {} (convert it to OpenSSH format)", key_file));
+        } else if let Protocol::Ssh(cfg) = &mut profile.protocol {
+            cfg.identity_file = Some(key_file);
+        }
+    }
+    report.add(name, folder, Some(profile), unsupported);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXPORT: &str = "\u{feff}[Misc]
+SessionTab=1
+
+[Bookmarks]
+SubRep=
+ImgNum=42
+web=#109#0%web.example.com%2200%alice%%-1%0%%gw.example.com%2222%jump%%_ProfileDir_\\.ssh\\id_ed25519#MobaFont%10
+broken=nothing here
+
+[Bookmarks_1]
+SubRep=Lab\\Routers
+ImgNum=41
+switch=#98#1%10.0.0.2%2323%%#MobaFont%10
+desktop=#91#3%10.0.0.3%3389%#MobaFont%10
+";
+
+    fn fixture(content: &str) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("pangpang-mobaxterm-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("MobaXterm.ini");
+        std::fs::write(&path, content).unwrap();
+        path
+    }
+
+    #[test]
+    fn ini_export() {
+        let report = import(&fixture(EXPORT)).unwrap();
+        let sessions: Vec<_> = report.sessions.iter()
+            .map(|s| (s.name.as_str(), s.folder.join("/"), s.imported, s.unsupported.clone()))
+            .collect();
+        assert_eq!(sessions, vec![
+            ("web", String::new(), true, vec!["X11 forwarding".to_string()]),
+            ("broken", String::new(), false, vec!["unrecognized session entry".to_string()]),
+            ("switch", "Lab/Routers".to_string(), true, vec![]),
+            ("desktop", "Lab/Routers".to_string(), false, vec!["session type RDP".to_string()]),
+        ]);
+
+        let web = report.profiles.iter().find(|p| p.name == "web").unwrap();
+        assert_eq!((web.username.as_str(), web.address.as_str(), web.port), ("alice", "web.example.com", 2200));
+        match &web.protocol {
+            Protocol::Ssh(cfg) => {
+                let key = cfg.identity_file.as_ref().unwrap();
+                assert!(key.ends_with("\\.ssh\\id_ed25519") && !key.contains("_ProfileDir_"));
+            }
+            _ => panic!("web is not ssh"),
+        }
+        let jump = report.profiles.iter().find(|p| p.name == "gw.example.com (jump)").unwrap();
+        assert_eq!((jump.username.as_str(), jump.port), ("jump", 2222));
+        assert_eq!(web.transport.as_ref(), Some(&jump.id));
+
+        let switch = report.profiles.iter().find(|p| p.name == "switch").unwrap();
+        assert!(matches!(switch.protocol, Protocol::Telnet(_)));
+        assert_eq!((switch.address.as_str(), switch.port), ("10.0.0.2", 2323));
+        assert_eq!(switch.folder, vec!["Lab", "Routers"]);
+    }
+}
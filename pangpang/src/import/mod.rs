@@ -0,0 +1,173 @@
+use std::path::{Path, PathBuf};
+
+use crate::{errors, profile::{Profile, Protocol, Inherit, TerminalSettings}, session::{ssh::SshProfile, telnet::TelnetProfile}, storage::Storage};
+
+
+
+pub mod openssh;
+pub mod putty;
+pub mod mobaxterm;
+pub mod securecrt;
+
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ImportFormat {
+    PuTTY,
+    MobaXterm,
+    SecureCRT,
+}
+
+impl ImportFormat {
+    pub fn all() -> [ImportFormat; 3] {
+        [ImportFormat::PuTTY, ImportFormat::MobaXterm, ImportFormat::SecureCRT]
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ImportFormat::PuTTY => "PuTTY (.reg)",
+            ImportFormat::MobaXterm => "MobaXterm (.mxtsessions)",
+            ImportFormat::SecureCRT => "SecureCRT (.xml)",
+        }
+    }
+
+    pub fn import(&self, path: &Path) -> Result<ImportReport, errors::Error> {
+        match self {
+            ImportFormat::PuTTY => putty::import(path),
+            ImportFormat::MobaXterm => mobaxterm::import(path),
+            ImportFormat::SecureCRT => securecrt::import(path),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct SessionReport {
+    pub name: String,
+    pub folder: Vec<String>,
+    pub imported: bool,
+    pub unsupported: Vec<String>,
+}
+
+pub struct ImportReport {
+    pub format: ImportFormat,
+    pub profiles: Vec<Profile>,
+    pub sessions: Vec<SessionReport>,
+}
+
+impl ImportReport {
+    pub(crate) fn new(format: ImportFormat) -> Self {
+        Self { format, profiles: Vec::new(), sessions: Vec::new() }
+    }
+
+    // importing the same export again updates the sessions instead of adding copies
+    pub(crate) fn add(&mut self, name: String, folder: Vec<String>, mut profile: Option<Profile>, unsupported: Vec<String>) {
+        if let Some(p) = &mut profile {
+            p.id = Profile::stable_id(&format!("import:{:?}:{}:{}", self.format, folder.join("/"), name));
+        }
+        self.sessions.push(SessionReport {
+            name,
+            folder,
+            imported: profile.is_some(),
+            unsupported,
+        });
+        if let Some(p) = profile {
+            self.profiles.push(p);
+        }
+    }
+
+    // sessions behind the same gateway share one jump profile
+    pub(crate) fn jump(&mut self, folder: Vec<String>, username: String, address: String, port: u16) -> String {
+        let id = Profile::stable_id(&format!("import-jump:{}@{}:{}", username, address, port));
+        if !self.profiles.iter().any(|p| p.id == id) {
+            let mut jump = ssh_profile(format!("{} (jump)", address), folder, username, address, port);
+            jump.id = id.clone();
+            self.profiles.push(jump);
+        }
+        id
+    }
+
+    pub fn imported(&self) -> usize {
+        self.sessions.iter().filter(|s| s.imported).count()
+    }
+
+    pub fn store(&self, storage: &mut dyn Storage) -> Result<usize, errors::Error> {
+        for p in &self.profiles {
+            storage.insert(p.clone())?;
+        }
+        Ok(self.profiles.len())
+    }
+}
+
+
+pub(crate) fn ssh_profile(name: String, folder: Vec<String>, username: String, address: String, port: u16) -> Profile {
+    Profile {
+        id: Profile::new_id(),
+        name,
+        folder,
+        tags: Vec::new(),
+        notes: String::new(),
+        username,
+        address,
+        port,
+        transport: None,
+        protocol: Protocol::Ssh(SshProfile::default()),
+        terminal: TerminalSettings::default(),
+        parent: None,
+        inherit: Inherit::empty(),
+        template: false,
+    }
+}
+
+pub(crate) fn telnet_profile(name: String, folder: Vec<String>, address: String, port: u16) -> Profile {
+    let mut profile = ssh_profile(name, folder, String::new(), address, port);
+    profile.protocol = Protocol::Telnet(TelnetProfile::default());
+    profile
+}
+
+pub(crate) fn split_folder(path: &str) -> Vec<String> {
+    path.split(['/', '\\'])
+        .filter(|s| !s.is_empty())
+        .map(|s| s.to_string())
+        .collect()
+}
+
+// %xx escapes, used by putty session keys and osc 7 file urls
+pub(crate) fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).ok();
+            if let Some(b) = hex.and_then(|h| u8::from_str_radix(h, 16).ok()) {
+                out.push(b);
+                i += 3;
+                continue;
+            }
+        }
+        out.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+pub(crate) fn read_text(path: &Path) -> Result<String, errors::Error> {
+    let data = std::fs::read(path)?;
+    if data.starts_with(&[0xff, 0xfe]) {
+        let utf16: Vec<u16> = data[2..]
+            .chunks_exact(2)
+            .map(|c| u16::from_le_bytes([c[0], c[1]]))
+            .collect();
+        Ok(String::from_utf16_lossy(&utf16))
+    } else if data.starts_with(&[0xef, 0xbb, 0xbf]) {
+        Ok(String::from_utf8_lossy(&data[3..]).into_owned())
+    } else {
+        Ok(String::from_utf8_lossy(&data).into_owned())
+    }
+}
+
+pub fn home_dir() -> PathBuf {
+    std::env::var_os("HOME")
+        .or_else(|| std::env::var_os("USERPROFILE"))
+        .map(PathBuf::from)
+        .unwrap_or_default()
+}
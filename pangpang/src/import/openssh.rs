@@ -0,0 +1,523 @@
+use std::{collections::HashMap, path::{Path, PathBuf}, time::SystemTime};
+
+use crate::{errors, profile::{Profile, Protocol, Inherit, TerminalSettings}, session::ssh::{SshProfile, PortForward}, storage::{Reload, Storage}};
+
+use super::home_dir;
+
+
+
+
+struct HostBlock {
+    patterns: Vec<String>,
+    match_all: bool,
+    options: Vec<(String, Vec<String>)>,
+}
+
+impl HostBlock {
+    fn matches(&self, alias: &str) -> bool {
+        if self.match_all {
+            return true;
+        }
+        let mut matched = false;
+        for pattern in &self.patterns {
+            if let Some(negated) = pattern.strip_prefix('!') {
+                if wildcard_match(negated, alias) {
+                    return false;
+                }
+            } else if wildcard_match(pattern, alias) {
+                matched = true;
+            }
+        }
+        matched
+    }
+}
+
+#[derive(Default)]
+struct HostOptions {
+    options: HashMap<String, Vec<String>>,
+    identity_files: Vec<String>,
+    local_forwards: Vec<Vec<String>>,
+    remote_forwards: Vec<Vec<String>>,
+}
+
+pub struct OpenSshConfig {
+    blocks: Vec<HostBlock>,
+}
+
+impl OpenSshConfig {
+    pub fn load(path: &Path) -> Result<Self, errors::Error> {
+        let mut cfg = Self { blocks: vec![HostBlock { patterns: Vec::new(), match_all: true, options: Vec::new() }] };
+        cfg.parse_file(path, 0)?;
+        Ok(cfg)
+    }
+
+    fn parse_file(&mut self, path: &Path, depth: usize) -> Result<(), errors::Error> {
+        if depth > 8 {
+            return Err(errors::Error::ConfigParseError(format!("include nested too deeply: {}", path.display())));
+        }
+        let content = std::fs::read_to_string(path)?;
+        for (n, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (key, args) = split_line(line)
+                .ok_or_else(|| errors::Error::ConfigParseError(format!("{}:{}: {}", path.display(), n + 1, line)))?;
+            match key.as_str() {
+                "host" => self.blocks.push(HostBlock { patterns: args, match_all: false, options: Vec::new() }),
+                "match" => self.blocks.push(parse_match(args)),
+                "include" => {
+                    for inc in args {
+                        let inc = expand_tilde(&inc);
+                        let inc = if Path::new(&inc).is_absolute() {
+                            PathBuf::from(inc)
+                        } else {
+                            home_dir().join(".ssh").join(inc)
+                        };
+                        if inc.is_file() {
+                            self.parse_file(&inc, depth + 1)?;
+                        } else {
+                            log::warn!("skip ssh config include: {}", inc.display());
+                        }
+                    }
+                }
+                _ => self.blocks.last_mut().unwrap().options.push((key, args)),
+            }
+        }
+        Ok(())
+    }
+
+    pub fn aliases(&self) -> Vec<String> {
+        let mut aliases = Vec::new();
+        for block in &self.blocks {
+            for pattern in &block.patterns {
+                if !pattern.starts_with('!') && !pattern.contains(['*', '?']) && !aliases.contains(pattern) {
+                    aliases.push(pattern.clone());
+                }
+            }
+        }
+        aliases
+    }
+
+    fn resolve(&self, alias: &str) -> HostOptions {
+        let mut resolved = HostOptions::default();
+        for block in self.blocks.iter().filter(|b| b.matches(alias)) {
+            for (key, args) in &block.options {
+                match key.as_str() {
+                    "identityfile" => resolved.identity_files.extend(args.iter().cloned()),
+                    "localforward" => resolved.local_forwards.push(args.clone()),
+                    "remoteforward" => resolved.remote_forwards.push(args.clone()),
+                    _ => {
+                        resolved.options.entry(key.clone()).or_insert_with(|| args.clone());
+                    }
+                }
+            }
+        }
+        resolved
+    }
+
+    pub fn profiles(&self) -> Vec<Profile> {
+        let mut profiles: HashMap<String, Profile> = HashMap::new();
+        let mut jumps: Vec<(String, String)> = Vec::new();
+        for alias in self.aliases() {
+            let opts = self.resolve(&alias);
+            profiles.insert(alias.clone(), self.to_profile(&alias, &opts));
+            if let Some(jump) = opts.options.get("proxyjump").and_then(|v| v.first()) {
+                if jump != "none" {
+                    jumps.push((alias, jump.clone()));
+                }
+            }
+        }
+
+        let mut result: Vec<Profile> = Vec::new();
+        for (alias, jump) in jumps {
+            // a hop with its own Host entry is reached the way that entry says,
+            // so the chain starts over at the last one of those
+            let hops: Vec<&str> = jump.split(',').collect();
+            let start = hops.iter().rposition(|h| profiles.contains_key(*h));
+            let mut transport = start.map(|i| profiles[hops[i]].id());
+            for hop in &hops[start.map(|i| i + 1).unwrap_or(0)..] {
+                let mut p = self.hop_profile(hop);
+                if let Some(via) = &transport {
+                    p.id = Profile::stable_id(&format!("{}+{}", p.id, via));
+                }
+                p.transport = transport.clone();
+                transport = Some(p.id());
+                if !result.iter().any(|r| r.id == p.id) {
+                    result.push(p);
+                }
+            }
+            profiles.get_mut(&alias).unwrap().transport = transport;
+        }
+        result.extend(profiles.into_values());
+        result
+    }
+
+    fn hop_profile(&self, hop: &str) -> Profile {
+        let (user, host) = match hop.rsplit_once('@') {
+            Some((u, h)) => (Some(u.to_string()), h),
+            None => (None, hop),
+        };
+        let (host, port) = match host.rsplit_once(':') {
+            Some((h, p)) if p.parse::<u16>().is_ok() => (h.to_string(), p.parse::<u16>().ok()),
+            _ => (host.to_string(), None),
+        };
+        let mut opts = self.resolve(&host);
+        if let Some(user) = user {
+            opts.options.insert("user".to_string(), vec![user]);
+        }
+        if let Some(port) = port {
+            opts.options.insert("port".to_string(), vec![port.to_string()]);
+        }
+        let mut p = self.to_profile(&host, &opts);
+        p.id = Profile::stable_id(&format!("openssh-hop:{}", hop));
+        p
+    }
+
+    fn to_profile(&self, alias: &String, opts: &HostOptions) -> Profile {
+        let first = |key: &str| opts.options.get(key).and_then(|v| v.first()).cloned();
+        let address = first("hostname").map(|h| h.replace("%h", alias)).unwrap_or_else(|| alias.clone());
+        let username = first("user").unwrap_or_else(local_username);
+        let port = first("port").and_then(|p| p.parse().ok()).unwrap_or(22);
+        let identity_file = opts.identity_files.first()
+            .map(|f| expand_tokens(f, alias, &address, &username));
+        Profile {
+            id: Profile::stable_id(&format!("openssh:{}", alias)),
+            name: alias.clone(),
+            folder: Vec::new(),
+            tags: Vec::new(),
+            notes: String::new(),
+            username,
+            address,
+            port,
+            transport: None,
+            protocol: Protocol::Ssh(SshProfile {
+                password: String::new(),
+                identity_file,
+                local_forwards: opts.local_forwards.iter().filter_map(parse_forward).collect(),
+                remote_forwards: opts.remote_forwards.iter().filter_map(parse_forward).collect(),
+            }),
+            terminal: TerminalSettings::default(),
+            parent: None,
+            inherit: Inherit::empty(),
+            template: false,
+        }
+    }
+}
+
+pub fn default_config_path() -> PathBuf {
+    home_dir().join(".ssh").join("config")
+}
+
+pub fn import_into(storage: &mut dyn Storage, path: &Path) -> Result<usize, errors::Error> {
+    let profiles = OpenSshConfig::load(path)?.profiles();
+    let count = profiles.len();
+    for p in profiles {
+        storage.insert(p)?;
+    }
+    Ok(count)
+}
+
+pub struct OpenSshStorage {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+    // mtime of the load in flight, kept once its profiles are in
+    loading: Option<SystemTime>,
+    map: HashMap<String, Profile>,
+}
+
+impl OpenSshStorage {
+    pub fn new(path: PathBuf) -> Result<Self, errors::Error> {
+        let mut s = Self {
+            path,
+            modified: None,
+            loading: None,
+            map: HashMap::new(),
+        };
+        s.refresh()?;
+        Ok(s)
+    }
+}
+
+impl Storage for OpenSshStorage {
+    fn get(&self, id: &String) -> Result<Profile, errors::Error> {
+        if let Some(p) = self.map.get(id) {
+            Ok(p.clone())
+        } else {
+            Err(errors::Error::ProfileNotFound(id.to_owned()))
+        }
+    }
+
+    fn iter(&self) -> Box<dyn Iterator<Item = (&String, &Profile)> + '_> {
+        Box::new(self.map.iter())
+    }
+
+    fn refresh(&mut self) -> Result<bool, errors::Error> {
+        match self.reload() {
+            Some(load) => {
+                self.replace(load()?);
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    fn reload(&mut self) -> Option<Reload> {
+        let modified = std::fs::metadata(&self.path).and_then(|m| m.modified()).ok();
+        if modified.is_some() && modified == self.modified {
+            return None;
+        }
+        self.loading = modified;
+        let path = self.path.clone();
+        Some(Box::new(move || Ok(OpenSshConfig::load(&path)?.profiles())))
+    }
+
+    fn replace(&mut self, profiles: Vec<Profile>) {
+        self.map = profiles.into_iter().map(|p| (p.id(), p)).collect();
+        self.modified = self.loading.take();
+        log::info!("reloaded {} profiles from {}", self.map.len(), self.path.display());
+    }
+}
+
+
+fn split_line(line: &str) -> Option<(String, Vec<String>)> {
+    let idx = line.find(|c: char| c.is_whitespace() || c == '=')?;
+    let key = line[..idx].to_lowercase();
+    let rest = line[idx..].trim_start();
+    let rest = rest.strip_prefix('=').unwrap_or(rest).trim_start();
+    let mut args = Vec::new();
+    let mut current = String::new();
+    let mut quoted = false;
+    for c in rest.chars() {
+        match c {
+            '"' => quoted = !quoted,
+            c if c.is_whitespace() && !quoted => {
+                if !current.is_empty() {
+                    args.push(std::mem::take(&mut current));
+                }
+            }
+            c => current.push(c),
+        }
+    }
+    if !current.is_empty() {
+        args.push(current);
+    }
+    if args.is_empty() {
+        None
+    } else {
+        Some((key, args))
+    }
+}
+
+fn parse_match(args: Vec<String>) -> HostBlock {
+    let mut block = HostBlock { patterns: Vec::new(), match_all: false, options: Vec::new() };
+    let mut it = args.into_iter();
+    while let Some(criteria) = it.next() {
+        match criteria.to_lowercase().as_str() {
+            "all" => block.match_all = true,
+            "host" | "originalhost" => {
+                if let Some(list) = it.next() {
+                    block.patterns.extend(list.split(',').map(|s| s.to_string()));
+                }
+            }
+            other => {
+                log::warn!("unsupported ssh config match criteria: {}", other);
+                block.match_all = false;
+                block.patterns.clear();
+                break;
+            }
+        }
+    }
+    block
+}
+
+fn parse_forward(args: &Vec<String>) -> Option<PortForward> {
+    let (bind, target) = match args.as_slice() {
+        [bind, target] => (bind.as_str(), target.as_str()),
+        _ => return None,
+    };
+    let (bind_address, bind_port) = match bind.rsplit_once(':') {
+        Some((addr, port)) => (Some(addr.trim_matches(|c| c == '[' || c == ']').to_string()), port.parse().ok()?),
+        None => (None, bind.parse().ok()?),
+    };
+    let (host, port) = target.rsplit_once(':')?;
+    Some(PortForward {
+        bind_address,
+        bind_port,
+        host: host.trim_matches(|c| c == '[' || c == ']').to_string(),
+        port: port.parse().ok()?,
+    })
+}
+
+fn wildcard_match(pattern: &str, text: &str) -> bool {
+    let p: Vec<char> = pattern.chars().collect();
+    let t: Vec<char> = text.chars().collect();
+    let (mut pi, mut ti) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+    while ti < t.len() {
+        if pi < p.len() && (p[pi] == '?' || p[pi] == t[ti]) {
+            pi += 1;
+            ti += 1;
+        } else if pi < p.len() && p[pi] == '*' {
+            star = Some((pi, ti));
+            pi += 1;
+        } else if let Some((sp, st)) = star {
+            pi = sp + 1;
+            ti = st + 1;
+            star = Some((sp, st + 1));
+        } else {
+            return false;
+        }
+    }
+    p[pi..].iter().all(|c| *c == '*')
+}
+
+fn expand_tilde(path: &str) -> String {
+    match path.strip_prefix("~/") {
+        Some(rest) => home_dir().join(rest).to_string_lossy().into_owned(),
+        None => path.to_string(),
+    }
+}
+
+fn expand_tokens(path: &str, alias: &str, host: &str, user: &str) -> String {
+    expand_tilde(path)
+        .replace("%d", &home_dir().to_string_lossy())
+        .replace("%n", alias)
+        .replace("%h", host)
+        .replace("%r", user)
+        .replace("%u", &local_username())
+        .replace("%%", "%")
+}
+
+fn local_username() -> String {
+    std::env::var("USER")
+        .or_else(|_| std::env::var("USERNAME"))
+        .unwrap_or_else(|_| "root".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    fn fixture(name: &str, content: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("pangpang-openssh-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(name);
+        std::fs::write(&path, content).unwrap();
+        path
+    }
+
+    fn by_name(profiles: Vec<Profile>) -> HashMap<String, Profile> {
+        profiles.into_iter().map(|p| (p.name.clone(), p)).collect()
+    }
+
+    fn ssh(p: &Profile) -> &SshProfile {
+        match &p.protocol {
+            Protocol::Ssh(ssh) => ssh,
+            _ => panic!("{} is not ssh", p.name),
+        }
+    }
+
+    #[test]
+    fn host_match_and_defaults() {
+        let path = fixture("hosts", r#"
+# first value wins, so the defaults come last
+Host web web2
+    HostName %h.example.com
+    Port 2222
+    IdentityFile ~/.ssh/web_%r
+Host db
+    User dba
+    LocalForward 5433 localhost:5432
+    RemoteForward 127.0.0.1:9000 [::1]:9001
+Match host web2
+    User matched
+Host *.internal !skip.internal
+    Port 2200
+Host *
+    User=everyone
+    Port 22
+"#);
+        let cfg = OpenSshConfig::load(&path).unwrap();
+        assert_eq!(cfg.aliases(), vec!["web", "web2", "db"]);
+        let profiles = by_name(cfg.profiles());
+        assert_eq!(profiles.len(), 3);
+
+        let web = &profiles["web"];
+        assert_eq!(web.address, "web.example.com");
+        assert_eq!(web.port, 2222);
+        assert_eq!(web.username, "everyone");
+        assert!(ssh(web).identity_file.as_ref().unwrap().ends_with("/.ssh/web_everyone"));
+        assert_eq!(web.id, Profile::stable_id("openssh:web"));
+
+        let web2 = &profiles["web2"];
+        assert_eq!(web2.address, "web2.example.com");
+        assert_eq!(web2.username, "matched");
+
+        let db = &profiles["db"];
+        assert_eq!(db.address, "db");
+        assert_eq!(db.port, 22);
+        assert_eq!(db.username, "dba");
+        let local = &ssh(db).local_forwards[0];
+        assert_eq!((local.bind_address.as_deref(), local.bind_port, local.host.as_str(), local.port), (None, 5433, "localhost", 5432));
+        let remote = &ssh(db).remote_forwards[0];
+        assert_eq!((remote.bind_address.as_deref(), remote.bind_port, remote.host.as_str(), remote.port), (Some("127.0.0.1"), 9000, "::1", 9001));
+
+        assert_eq!(cfg.resolve("a.internal").options["port"], vec!["2200"]);
+        assert_eq!(cfg.resolve("skip.internal").options["port"], vec!["22"]);
+    }
+
+    #[test]
+    fn proxy_jump_chain() {
+        let path = fixture("jumps", r#"
+Host bastion
+    HostName bastion.example.com
+    User ops
+Host app app2
+    HostName %h.lan
+    ProxyJump bastion,admin@inner:2200
+Host direct
+    ProxyJump none
+"#);
+        let profiles = OpenSshConfig::load(&path).unwrap().profiles();
+        // the unknown hop is shared by both hosts behind it
+        assert_eq!(profiles.len(), 5);
+        let by_id: HashMap<String, Profile> = profiles.iter().map(|p| (p.id.clone(), p.clone())).collect();
+        let profiles = by_name(profiles);
+
+        let bastion = &profiles["bastion"];
+        assert_eq!(bastion.transport, None);
+        let hop = &by_id[profiles["app"].transport.as_ref().unwrap()];
+        assert_eq!((hop.address.as_str(), hop.port, hop.username.as_str()), ("inner", 2200, "admin"));
+        assert_eq!(hop.transport.as_ref(), Some(&bastion.id));
+        assert_eq!(profiles["app2"].transport, profiles["app"].transport);
+        assert_eq!(profiles["direct"].transport, None);
+    }
+
+    #[test]
+    fn storage_reload() {
+        let path = fixture("storage", "Host one\n    HostName 10.0.0.1\n");
+        let touch = |secs: u64| {
+            let time = SystemTime::UNIX_EPOCH + Duration::from_secs(secs);
+            std::fs::File::options().write(true).open(&path).unwrap().set_modified(time).unwrap();
+        };
+        touch(1_000);
+        let mut storage = OpenSshStorage::new(path.clone()).unwrap();
+        assert_eq!(storage.iter().count(), 1);
+        assert!(storage.reload().is_none());
+
+        // a broken file is tried again on the next tick
+        std::fs::write(&path, "Host\n").unwrap();
+        touch(2_000);
+        assert!(storage.reload().unwrap()().is_err());
+        assert!(storage.reload().unwrap()().is_err());
+
+        std::fs::write(&path, "Host one\nHost two\n").unwrap();
+        touch(3_000);
+        assert!(storage.refresh().unwrap());
+        assert_eq!(storage.iter().count(), 2);
+        assert!(storage.reload().is_none());
+    }
+}
@@ -0,0 +1,291 @@
+use std::{collections::HashMap, path::Path};
+
+use crate::{errors, profile::Protocol, session::ssh::PortForward};
+
+use super::{ImportFormat, ImportReport, percent_decode, read_text, split_folder, ssh_profile, telnet_profile};
+
+
+
+
+const SESSIONS_KEY: &str = "\\Sessions\\";
+
+enum RegValue {
+    Str(String),
+    Dword(u32),
+}
+
+pub fn import(path: &Path) -> Result<ImportReport, errors::Error> {
+    let mut report = ImportReport::new(ImportFormat::PuTTY);
+    for (key, values) in parse_reg(&read_text(path)?)? {
+        import_session(&mut report, &key, &values);
+    }
+    Ok(report)
+}
+
+fn import_session(report: &mut ImportReport, key: &str, values: &HashMap<String, RegValue>) {
+    let str_value = |name: &str| match values.get(name) {
+        Some(RegValue::Str(s)) if !s.is_empty() => Some(s.clone()),
+        _ => None,
+    };
+    let dword_value = |name: &str| match values.get(name) {
+        Some(RegValue::Dword(d)) => *d,
+        _ => 0,
+    };
+
+    let mut folder = split_folder(&percent_decode(key));
+    let name = folder.pop().unwrap_or_default();
+    if let Some(f) = str_value("Folder") {
+        folder = split_folder(&f);
+    }
+    let mut unsupported = Vec::new();
+
+    let protocol = str_value("Protocol").unwrap_or_else(|| "ssh".to_string());
+    let address = match str_value("HostName") {
+        Some(h) => h,
+        None => {
+            if name != "Default Settings" {
+                unsupported.push("session without host name".to_string());
+                report.add(name, folder, None, unsupported);
+            }
+            return;
+        }
+    };
+    if protocol == "telnet" {
+        let port = match dword_value("PortNumber") {
+            0 => 23,
+            p => p as u16,
+        };
+        let profile = telnet_profile(name.clone(), folder.clone(), address, port);
+        report.add(name, folder, Some(profile), unsupported);
+        return;
+    }
+    if protocol != "ssh" {
+        unsupported.push(format!("protocol {}", protocol));
+        report.add(name, folder, None, unsupported);
+        return;
+    }
+
+    let (username, address) = match (str_value("UserName"), address.rsplit_once('@')) {
+        (Some(u), _) => (u, address),
+        (None, Some((u, h))) => (u.to_string(), h.to_string()),
+        (None, None) => (String::new(), address),
+    };
+    let port = match dword_value("PortNumber") {
+        0 => 22,
+        p => p as u16,
+    };
+    let mut profile = ssh_profile(name.clone(), folder.clone(), username, address, port);
+
+    if let Protocol::Ssh(cfg) = &mut profile.protocol {
+        if let Some(key_file) = str_value("PublicKeyFile") {
+            if key_file.to_lowercase().ends_with(".ppk") {
+                unsupported.push(format!("PuTTY private key {} (convert it to OpenSSH format)", key_file));
+            } else {
+                cfg.identity_file = Some(key_file);
+            }
+        }
+        if let Some(forwards) = str_value("PortForwardings") {
+            for rule in forwards.split(',').filter(|r| !r.is_empty()) {
+                let (local, fwd) = match parse_forward(rule) {
+                    Some(f) => f,
+                    None => {
+                        unsupported.push(format!("port forwarding {}", rule));
+                        continue;
+                    }
+                };
+                if local {
+                    cfg.local_forwards.push(fwd);
+                } else {
+                    cfg.remote_forwards.push(fwd);
+                }
+            }
+        }
+    }
+
+    match dword_value("ProxyMethod") {
+        0 => {}
+        6 => match str_value("ProxyHost") {
+            Some(host) => {
+                let port = match dword_value("ProxyPort") {
+                    0 => 22,
+                    p => p as u16,
+                };
+                let user = str_value("ProxyUsername").unwrap_or_default();
+                profile.transport = Some(report.jump(folder.clone(), user, host, port));
+            }
+            None => unsupported.push("ssh proxy without host".to_string()),
+        },
+        m => unsupported.push(format!("proxy method {}", m)),
+    }
+    for (flag, setting) in [
+        ("X11Forward", "X11 forwarding"),
+        ("AgentFwd", "agent forwarding"),
+        ("Compression", "compression"),
+        ("TryGSSAuth", "GSSAPI authentication"),
+    ] {
+        if dword_value(flag) != 0 {
+            unsupported.push(setting.to_string());
+        }
+    }
+    if let Some(cmd) = str_value("RemoteCommand") {
+        unsupported.push(format!("remote command {}", cmd));
+    }
+    report.add(name, folder, Some(profile), unsupported);
+}
+
+// session name and its values, in file order
+type RegSession = (String, HashMap<String, RegValue>);
+
+fn parse_reg(content: &str) -> Result<Vec<RegSession>, errors::Error> {
+    let mut sessions: Vec<RegSession> = Vec::new();
+    let mut in_session = false;
+    for line in content.lines() {
+        let line = line.trim();
+        if line.starts_with('[') && line.ends_with(']') {
+            let key = &line[1..line.len() - 1];
+            in_session = match key.find(SESSIONS_KEY) {
+                Some(idx) if !key.starts_with('-') => {
+                    sessions.push((key[idx + SESSIONS_KEY.len()..].to_string(), HashMap::new()));
+                    true
+                }
+                _ => false,
+            };
+            continue;
+        }
+        if !in_session || !line.starts_with('"') {
+            continue;
+        }
+        let (name, value) = line.split_once("\"=")
+            .ok_or_else(|| errors::Error::ConfigParseError(format!("invalid registry value: {}", line)))?;
+        let name = name.trim_start_matches('"').to_string();
+        let value = if let Some(dword) = value.strip_prefix("dword:") {
+            RegValue::Dword(u32::from_str_radix(dword, 16)
+                .map_err(|e| errors::Error::ConfigParseError(format!("{}: {}", line, e)))?)
+        } else if value.starts_with('"') && value.ends_with('"') && value.len() >= 2 {
+            RegValue::Str(value[1..value.len() - 1].replace("\\\"", "\"").replace("\\\\", "\\"))
+        } else {
+            continue;
+        };
+        sessions.last_mut().unwrap().1.insert(name, value);
+    }
+    Ok(sessions)
+}
+
+fn parse_forward(rule: &str) -> Option<(bool, PortForward)> {
+    let rule = rule.trim_start_matches(['4', '6']);
+    let local = match rule.chars().next()? {
+        'L' => true,
+        'R' => false,
+        _ => return None,
+    };
+    let (bind, target) = rule[1..].split_once('=')?;
+    let (bind_address, bind_port) = match bind.rsplit_once(':') {
+        Some((addr, port)) => (Some(addr.to_string()), port.parse().ok()?),
+        None => (None, bind.parse().ok()?),
+    };
+    let (host, port) = target.rsplit_once(':')?;
+    Some((local, PortForward {
+        bind_address,
+        bind_port,
+        host: host.to_string(),
+        port: port.parse().ok()?,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::profile::Profile;
+
+    use super::*;
+
+    const EXPORT: &str = r#"Windows Registry Editor Version 5.00
+
+[HKEY_CURRENT_USER\Software\SimonTatham\PuTTY\Sessions\Default%20Settings]
+"HostName"=""
+"PortNumber"=dword:00000016
+
+[HKEY_CURRENT_USER\Software\SimonTatham\PuTTY\Sessions\prod%2Fweb%20one]
+"HostName"="admin@web.example.com"
+"PortNumber"=dword:00000822
+"Protocol"="ssh"
+"PublicKeyFile"="C:\\keys\\web.ppk"
+"PortForwardings"="L8080=localhost:80,4R127.0.0.1:9000=db:9001,X1=bad"
+"X11Forward"=dword:00000001
+"ProxyMethod"=dword:00000006
+"ProxyHost"="gw.example.com"
+"ProxyUsername"="jump"
+
+[HKEY_CURRENT_USER\Software\SimonTatham\PuTTY\Sessions\router]
+"HostName"="10.0.0.1"
+"Protocol"="telnet"
+
+[HKEY_CURRENT_USER\Software\SimonTatham\PuTTY\Sessions\console]
+"HostName"="COM1"
+"Protocol"="serial"
+"Folder"="lab\\bench \"2\""
+
+[-HKEY_CURRENT_USER\Software\SimonTatham\PuTTY\Sessions\deleted]
+"HostName"="gone"
+"#;
+
+    // regedit writes utf-16 with a byte order mark
+    fn fixture(content: &str) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("pangpang-putty-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("putty.reg");
+        let mut data = vec![0xff, 0xfe];
+        data.extend(content.encode_utf16().flat_map(|c| c.to_le_bytes()));
+        std::fs::write(&path, data).unwrap();
+        path
+    }
+
+    fn profile<'a>(report: &'a ImportReport, name: &str) -> &'a Profile {
+        report.profiles.iter().find(|p| p.name == name).unwrap()
+    }
+
+    #[test]
+    fn registry_export() {
+        let path = fixture(EXPORT);
+        let report = import(&path).unwrap();
+        let sessions: Vec<_> = report.sessions.iter()
+            .map(|s| (s.name.as_str(), s.folder.join("/"), s.imported))
+            .collect();
+        assert_eq!(sessions, vec![
+            ("web one", "prod".to_string(), true),
+            ("router", String::new(), true),
+            ("console", "lab/bench \"2\"".to_string(), false),
+        ]);
+        assert_eq!(report.sessions[0].unsupported, vec![
+            "PuTTY private key C:\\keys\\web.ppk (convert it to OpenSSH format)",
+            "port forwarding X1=bad",
+            "X11 forwarding",
+        ]);
+        assert!(report.sessions[1].unsupported.is_empty());
+        assert_eq!(report.sessions[2].unsupported, vec!["protocol serial"]);
+
+        let web = profile(&report, "web one");
+        assert_eq!((web.username.as_str(), web.address.as_str(), web.port), ("admin", "web.example.com", 2082));
+        let cfg = match &web.protocol {
+            Protocol::Ssh(cfg) => cfg,
+            _ => panic!("web one is not ssh"),
+        };
+        assert!(cfg.identity_file.is_none());
+        let local = &cfg.local_forwards[0];
+        assert_eq!((local.bind_address.as_deref(), local.bind_port, local.host.as_str(), local.port), (None, 8080, "localhost", 80));
+        let remote = &cfg.remote_forwards[0];
+        assert_eq!((remote.bind_address.as_deref(), remote.bind_port, remote.host.as_str(), remote.port), (Some("127.0.0.1"), 9000, "db", 9001));
+
+        let jump = profile(&report, "gw.example.com (jump)");
+        assert_eq!((jump.username.as_str(), jump.port), ("jump", 22));
+        assert_eq!(web.transport.as_ref(), Some(&jump.id));
+
+        let router = profile(&report, "router");
+        assert!(matches!(router.protocol, Protocol::Telnet(_)));
+        assert_eq!(router.port, 23);
+
+        // a second import of the same file yields the same ids
+        let again = import(&path).unwrap();
+        let ids = |r: &ImportReport| r.profiles.iter().map(|p| p.id.clone()).collect::<Vec<_>>();
+        assert_eq!(ids(&report), ids(&again));
+    }
+}
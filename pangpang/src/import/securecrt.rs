@@ -0,0 +1,261 @@
+use std::{collections::HashMap, path::Path};
+
+use quick_xml::{Reader, events::{Event, BytesStart}};
+
+use crate::{errors, profile::Protocol};
+
+use super::{ImportFormat, ImportReport, read_text, ssh_profile, telnet_profile};
+
+
+
+
+#[derive(Default)]
+struct KeyNode {
+    name: String,
+    values: HashMap<String, String>,
+    arrays: HashMap<String, Vec<String>>,
+}
+
+pub fn import(path: &Path) -> Result<ImportReport, errors::Error> {
+    let content = read_text(path)?;
+    let mut reader = Reader::from_str(&content);
+    reader.trim_text(true);
+    let mut buf = Vec::new();
+    let mut keys: Vec<KeyNode> = Vec::new();
+    let mut value: Option<String> = None;
+    let mut array: Option<String> = None;
+    let mut sessions: Vec<(Vec<String>, KeyNode)> = Vec::new();
+    loop {
+        match reader.read_event(&mut buf) {
+            Ok(Event::Start(ref e)) => match e.name() {
+                b"key" => keys.push(KeyNode { name: name_attr(e, &reader), ..Default::default() }),
+                b"array" => array = Some(name_attr(e, &reader)),
+                b"string" | b"dword" => value = Some(name_attr(e, &reader)),
+                _ => {}
+            },
+            Ok(Event::Text(ref t)) => {
+                let text = t.unescape_and_decode(&reader)
+                    .map_err(|e| errors::Error::ConfigParseError(e.to_string()))?;
+                if let Some(node) = keys.last_mut() {
+                    match (&array, &value) {
+                        (Some(a), _) => node.arrays.entry(a.clone()).or_default().push(text),
+                        (None, Some(v)) if !v.is_empty() => {
+                            node.values.insert(v.clone(), text);
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            Ok(Event::End(ref e)) => match e.name() {
+                b"key" => {
+                    let node = keys.pop().unwrap_or_default();
+                    let in_sessions = keys.first().map(|k| k.name == "Sessions").unwrap_or(false);
+                    if in_sessions && node.values.contains_key("Protocol Name") {
+                        sessions.push((keys[1..].iter().map(|k| k.name.clone()).collect(), node));
+                    }
+                }
+                b"array" => array = None,
+                b"string" | b"dword" => value = None,
+                _ => {}
+            },
+            Ok(Event::Eof) => break,
+            Err(e) => return Err(errors::Error::ConfigParseError(format!("{} at {}", e, reader.buffer_position()))),
+            _ => {}
+        }
+        buf.clear();
+    }
+
+    let mut report = ImportReport::new(ImportFormat::SecureCRT);
+    let mut ids: HashMap<String, String> = HashMap::new();
+    let mut jumps: Vec<(usize, String)> = Vec::new();
+    for (folder, node) in sessions {
+        let mut path = folder.clone();
+        path.push(node.name.clone());
+        let before = report.profiles.len();
+        if let Some(jump) = import_session(&mut report, folder, node) {
+            jumps.push((before, jump));
+        }
+        if let Some(p) = report.profiles.get(before) {
+            ids.insert(path.join("/"), p.id());
+        }
+    }
+    for (idx, jump) in jumps {
+        match ids.get(&jump) {
+            Some(id) => report.profiles[idx].transport = Some(id.clone()),
+            None => {
+                let name = report.profiles[idx].name.clone();
+                if let Some(s) = report.sessions.iter_mut().find(|s| s.name == name) {
+                    s.unsupported.push(format!("jump session {} not found", jump));
+                }
+            }
+        }
+    }
+    Ok(report)
+}
+
+fn import_session(report: &mut ImportReport, folder: Vec<String>, node: KeyNode) -> Option<String> {
+    let value = |name: &str| node.values.get(name).map(|s| s.trim()).filter(|s| !s.is_empty());
+    let flag = |name: &str| value(name).and_then(|v| v.parse::<u32>().ok()).unwrap_or(0) != 0;
+    let mut unsupported = Vec::new();
+
+    let protocol = value("Protocol Name").unwrap_or("SSH2");
+    if protocol != "SSH2" && protocol != "Telnet" {
+        unsupported.push(format!("protocol {}", protocol));
+        report.add(node.name.clone(), folder, None, unsupported);
+        return None;
+    }
+    let address = match value("Hostname") {
+        Some(h) => h.to_string(),
+        None => {
+            unsupported.push("session without host name".to_string());
+            report.add(node.name.clone(), folder, None, unsupported);
+            return None;
+        }
+    };
+    if protocol == "Telnet" {
+        let port = value("Port").and_then(|p| p.parse().ok()).unwrap_or(23);
+        let profile = telnet_profile(node.name.clone(), folder.clone(), address, port);
+        report.add(node.name.clone(), folder, Some(profile), unsupported);
+        return None;
+    }
+    let port = value("[SSH2] Port").and_then(|p| p.parse().ok()).unwrap_or(22);
+    let username = value("Username").unwrap_or_default().to_string();
+    let mut profile = ssh_profile(node.name.clone(), folder.clone(), username, address, port);
+
+    if let Some(key_file) = value("Identity Filename V2").or_else(|| value("Identity Filename")) {
+        let key_file = key_file.split("::").next().unwrap_or(key_file).to_string();
+        if let Protocol::Ssh(cfg) = &mut profile.protocol {
+            cfg.identity_file = Some(key_file);
+        }
+    }
+    if let Some(desc) = node.arrays.get("Description") {
+        profile.notes = desc.join("\n");
+    }
+    let mut jump = None;
+    match value("Firewall Name") {
+        None | Some("None") => {}
+        Some(fw) => match fw.strip_prefix("Session:") {
+            Some(session) => jump = Some(session.replace('\\', "/")),
+            None => unsupported.push(format!("firewall {}", fw)),
+        },
+    }
+    for (name, setting) in [
+        ("Forward X11", "X11 forwarding"),
+        ("Enable Agent Forwarding", "agent forwarding"),
+        ("Compression", "compression"),
+    ] {
+        if flag(name) {
+            unsupported.push(setting.to_string());
+        }
+    }
+    for table in ["Port Forward Table V2", "Reverse Forward Table V2"] {
+        if let Some(rules) = node.arrays.get(table) {
+            if !rules.is_empty() {
+                unsupported.push(format!("{} ({} rules)", table, rules.len()));
+            }
+        }
+    }
+    if let Some(logon) = value("Logon Script") {
+        unsupported.push(format!("logon script {}", logon));
+    }
+    report.add(node.name.clone(), folder, Some(profile), unsupported);
+    jump
+}
+
+fn name_attr(e: &BytesStart, reader: &Reader<&[u8]>) -> String {
+    e.attributes()
+        .filter_map(|a| a.ok())
+        .find(|a| a.key == b"name")
+        .and_then(|a| a.unescape_and_decode_value(reader).ok())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXPORT: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<VanDyke version="3.0">
+    <key name="Sessions">
+        <key name="Prod">
+            <key name="DB">
+                <key name="db1">
+                    <string name="Protocol Name">SSH2</string>
+                    <string name="Hostname">db1.example.com</string>
+                    <dword name="[SSH2] Port">2222</dword>
+                    <string name="Username">dba</string>
+                    <string name="Identity Filename V2">/home/me/.ssh/id_rsa::rawkey</string>
+                    <string name="Firewall Name">Session:Prod\bastion</string>
+                    <dword name="Forward X11">1</dword>
+                    <array name="Description">
+                        <string>primary</string>
+                        <string>do not reboot &amp; keep</string>
+                    </array>
+                    <array name="Port Forward Table V2">
+                        <string>pg</string>
+                    </array>
+                </key>
+            </key>
+            <key name="bastion">
+                <string name="Protocol Name">SSH2</string>
+                <string name="Hostname">bastion.example.com</string>
+            </key>
+        </key>
+        <key name="orphan">
+            <string name="Protocol Name">SSH2</string>
+            <string name="Hostname">orphan.example.com</string>
+            <string name="Firewall Name">Session:missing</string>
+        </key>
+        <key name="router">
+            <string name="Protocol Name">Telnet</string>
+            <string name="Hostname">10.0.0.1</string>
+            <dword name="Port">2323</dword>
+        </key>
+        <key name="console">
+            <string name="Protocol Name">Serial</string>
+        </key>
+    </key>
+</VanDyke>
+"#;
+
+    fn fixture(content: &str) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("pangpang-securecrt-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("securecrt.xml");
+        std::fs::write(&path, content).unwrap();
+        path
+    }
+
+    #[test]
+    fn xml_export() {
+        let report = import(&fixture(EXPORT)).unwrap();
+        let sessions: Vec<_> = report.sessions.iter()
+            .map(|s| (s.name.as_str(), s.folder.join("/"), s.imported, s.unsupported.clone()))
+            .collect();
+        assert_eq!(sessions, vec![
+            ("db1", "Prod/DB".to_string(), true, vec![
+                "X11 forwarding".to_string(),
+                "Port Forward Table V2 (1 rules)".to_string(),
+            ]),
+            ("bastion", "Prod".to_string(), true, vec![]),
+            ("orphan", String::new(), true, vec!["jump session missing not found".to_string()]),
+            ("router", String::new(), true, vec![]),
+            ("console", String::new(), false, vec!["protocol Serial".to_string()]),
+        ]);
+
+        let profile = |name: &str| report.profiles.iter().find(|p| p.name == name).unwrap();
+        let db1 = profile("db1");
+        assert_eq!((db1.username.as_str(), db1.address.as_str(), db1.port), ("dba", "db1.example.com", 2222));
+        assert_eq!(db1.notes, "primary\ndo not reboot & keep");
+        match &db1.protocol {
+            Protocol::Ssh(cfg) => assert_eq!(cfg.identity_file.as_deref(), Some("/home/me/.ssh/id_rsa")),
+            _ => panic!("db1 is not ssh"),
+        }
+        assert_eq!(db1.transport.as_ref(), Some(&profile("bastion").id));
+        assert_eq!(profile("orphan").transport, None);
+
+        let router = profile("router");
+        assert!(matches!(router.protocol, Protocol::Telnet(_)));
+        assert_eq!(router.port, 2323);
+    }
+}
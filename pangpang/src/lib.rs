@@ -7,6 +7,8 @@ pub mod session;
 pub mod storage;
 pub mod profile;
 pub mod terminal;
+pub mod import;
+pub mod script;
 
 
 
@@ -28,34 +30,97 @@ pub struct PangPang {
 }
 
 impl PangPang {
-    fn new(cfg: Arc<Mutex<dyn storage::Storage>>) -> Self {
+    pub fn new(cfg: Arc<Mutex<dyn storage::Storage>>) -> Self {
         Self {
             mgr: session::PpSessionManager::new(cfg),
         }
     }
 
+    pub fn subscribe(&self) -> session::event::EventReceiver {
+        self.mgr.subscribe()
+    }
+
+    pub async fn snapshot(&self) -> Vec<session::registry::SessionSnapshot> {
+        self.mgr.snapshot().await
+    }
+
+    pub async fn close_session(&self, serial: u64) -> bool {
+        self.mgr.close_session(serial).await
+    }
+
+    pub fn close_channel(&self, serial: u64) -> bool {
+        self.mgr.close_channel(serial)
+    }
+
+    pub fn cache_policy(&self) -> session::CachePolicy {
+        self.mgr.cache_policy()
+    }
+
+    pub fn set_cache_policy(&self, policy: session::CachePolicy) {
+        self.mgr.set_cache_policy(policy)
+    }
+
     pub async fn open_session(&self, id: &String) -> Result<session::PpSessionGuard, errors::Error> {
         self.mgr.open_session(id).await
     }
 
-    pub async fn open_tunnel(&self, id: &String) -> Result<session::PpTunnelGuard, errors::Error> {
-        self.mgr.open_tunnel(id, &"host".to_string(), 100).await
+    pub async fn open_tunnel(&self, id: &String, host: &String, port: u16) -> Result<session::PpTunnelGuard, errors::Error> {
+        self.mgr.open_tunnel(id, host, port).await
+    }
+
+    pub async fn exec(&self, id: &String, command: &String) -> Result<session::PpPtyGuard, errors::Error> {
+        self.mgr.exec(id, command, false).await
     }
 
     pub async fn open_pty(&self, id: &String) -> Result<session::PpPtyGuard, errors::Error> {
         self.mgr.open_pty(id).await
     }
 
+    pub async fn open_pty_in(&self, id: &String, cwd: &String) -> Result<session::PpPtyGuard, errors::Error> {
+        self.mgr.open_pty_in(id, Some(cwd)).await
+    }
+
+    pub async fn list_containers(
+        &self,
+        host: Option<String>,
+        runtime: session::container::ContainerRuntime
+    ) -> Result<Vec<session::container::ContainerInfo>, errors::Error> {
+        session::container::list(&self.mgr, host.as_ref(), runtime).await
+    }
+
     pub async fn open_terminal(
         &self,
         id: String,
+        cwd: Option<String>,
         input: PpTerminalMessageReceiver,
         ui_render: Arc<Mutex<dyn terminal::Render>>
     ) -> Result<terminal::Terminal, errors::Error> {
-        Ok(terminal::Terminal::new(
-            Box::new(self.open_pty(&id).await?),
-            input, ui_render
-        ))
+        let profile = self.mgr.profile(&id).await?;
+        let pty = self.mgr.open_pty_in(&id, cwd.as_ref()).await?;
+        ui_render.lock().await.session(pty.session_serial());
+        let mut term = terminal::Terminal::new(
+            Box::new(pty),
+            input, ui_render, &profile.terminal
+        );
+        if let Some(settings) = &profile.terminal.logging {
+            match terminal::SessionLogger::open(settings, &profile) {
+                Ok(logger) => term.log_to(logger),
+                Err(e) => log::error!("open session log for {} failed: {:?}", id, e),
+            }
+        }
+        if !profile.terminal.triggers.is_empty() {
+            term.trigger_on(terminal::TriggerEngine::new(&profile.terminal.triggers), self.mgr.storage());
+        }
+        Ok(term)
+    }
+
+    pub fn play(
+        &self,
+        path: &std::path::Path,
+        input: PpTerminalMessageReceiver,
+        ui_render: Arc<Mutex<dyn terminal::Render>>
+    ) -> Result<terminal::Player, errors::Error> {
+        terminal::Player::open(path, input, ui_render)
     }
 }
 
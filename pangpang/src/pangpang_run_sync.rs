@@ -1,14 +1,25 @@
 use std::{sync::Arc, fmt::Debug};
 
 
-pub use tokio::sync::Mutex;
+pub use tokio::sync::{Mutex, oneshot, broadcast, mpsc};
 
-use crate::{storage::Storage, terminal::{msg::PpTerminalMessageReceiver, Render}};
+use crate::{errors, session::{container::{ContainerInfo, ContainerRuntime}, event::EventReceiver, registry::SessionSnapshot, CachePolicy}, storage::Storage, terminal::{msg::PpTerminalMessageReceiver, Render}};
 pub type PpMsgSender = tokio::sync::mpsc::Sender<PpMessage>;
 pub type PpMsgReceiver = tokio::sync::mpsc::Receiver<PpMessage>;
 pub enum PpMessage {
     Hello,
-    NewTerminal(String, PpTerminalMessageReceiver, Arc<Mutex<dyn Render>>),
+    // profile id, working directory to start in
+    NewTerminal(String, Option<String>, PpTerminalMessageReceiver, Arc<Mutex<dyn Render>>),
+    // replay an asciicast recording into a read-only terminal
+    Play(std::path::PathBuf, PpTerminalMessageReceiver, Arc<Mutex<dyn Render>>),
+    ListContainers(Option<String>, ContainerRuntime, oneshot::Sender<Result<Vec<ContainerInfo>, errors::Error>>),
+    Subscribe(oneshot::Sender<EventReceiver>),
+    Snapshot(oneshot::Sender<Vec<SessionSnapshot>>),
+    CloseSession(u64),
+    CloseChannel(u64),
+    SetCachePolicy(CachePolicy),
+    // script source, print output and the final status line go to the sender
+    RunScript(String, mpsc::Sender<String>),
 }
 impl Debug for PpMessage {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -20,24 +31,87 @@ pub fn run(cfg: Arc<Mutex<dyn Storage>>) -> PpMsgSender {
     let (tx, mut rx) = tokio::sync::mpsc::channel::<PpMessage>(1024);
     std::thread::spawn(|| {
         tokio::runtime::Runtime::new().unwrap().block_on(async move {
-            let pp = crate::PangPang::new(cfg);
+            let pp = Arc::new(crate::PangPang::new(cfg.clone()));
+            tokio::spawn(async move {
+                let mut interval = tokio::time::interval(std::time::Duration::from_secs(5));
+                loop {
+                    interval.tick().await;
+                    // inventory scripts may take a while, only the swap holds the lock
+                    let reload = cfg.lock().await.reload();
+                    if let Some(load) = reload {
+                        match tokio::task::spawn_blocking(load).await {
+                            Ok(Ok(profiles)) => cfg.lock().await.replace(profiles),
+                            Ok(Err(e)) => log::error!("refresh storage failed: {:?}", e),
+                            Err(e) => log::error!("refresh storage panicked: {:?}", e),
+                        }
+                    }
+                }
+            });
             loop {
                 match rx.recv().await {
                     None => break,
                     Some(msg) => {
                         match msg {
                             PpMessage::Hello => log::info!("ui thread say us hello!"),
-                            PpMessage::NewTerminal(id, input, render) => {
-                                if let Ok(mut term) = pp.open_terminal(id.clone(), input, render).await {
-                                    tokio::spawn(async move {
-                                        if let Err(e) = term.run().await {
-                                            log::error!("terminal exited with error: {:?}, id: {}", e, id);
-                                        } else {
-                                            log::info!("terminal exited, id: {}", id);
-                                        }
-                                    });
+                            PpMessage::NewTerminal(id, cwd, input, render) => {
+                                match pp.open_terminal(id.clone(), cwd, input, render.clone()).await {
+                                    Ok(mut term) => {
+                                        tokio::spawn(async move {
+                                            if let Err(e) = term.run().await {
+                                                log::error!("terminal exited with error: {:?}, id: {}", e, id);
+                                            } else {
+                                                log::info!("terminal exited, id: {}", id);
+                                            }
+                                        });
+                                    }
+                                    Err(e) => {
+                                        log::error!("open terminal failed: {:?}, id: {}", e, id);
+                                        render.lock().await.closed(Some(format!("{:?}", e)));
+                                    }
                                 }
                             }
+                            PpMessage::Play(path, input, render) => {
+                                match pp.play(&path, input, render) {
+                                    Ok(mut player) => {
+                                        tokio::spawn(async move {
+                                            if let Err(e) = player.run().await {
+                                                log::error!("playback of {} failed: {:?}", path.display(), e);
+                                            }
+                                        });
+                                    }
+                                    Err(e) => log::error!("open recording {} failed: {:?}", path.display(), e),
+                                }
+                            }
+                            PpMessage::ListContainers(host, runtime, reply) => {
+                                let _ = reply.send(pp.list_containers(host, runtime).await);
+                            }
+                            PpMessage::Subscribe(reply) => {
+                                let _ = reply.send(pp.subscribe());
+                            }
+                            PpMessage::Snapshot(reply) => {
+                                let _ = reply.send(pp.snapshot().await);
+                            }
+                            PpMessage::CloseSession(serial) => {
+                                if !pp.close_session(serial).await {
+                                    log::warn!("close session {} failed: not found", serial);
+                                }
+                            }
+                            PpMessage::CloseChannel(serial) => {
+                                if !pp.close_channel(serial) {
+                                    log::warn!("close channel {} failed: not found", serial);
+                                }
+                            }
+                            PpMessage::SetCachePolicy(policy) => pp.set_cache_policy(policy),
+                            PpMessage::RunScript(source, output) => {
+                                let pp = pp.clone();
+                                tokio::spawn(async move {
+                                    let status = match crate::script::run(pp, source, output.clone()).await {
+                                        Ok(()) => "script finished".to_string(),
+                                        Err(e) => format!("script failed: {:?}", e),
+                                    };
+                                    let _ = output.send(status).await;
+                                });
+                            }
                         }
                     }
                 }
@@ -1,4 +1,4 @@
-use crate::session::ssh;
+use crate::{session::{ssh, local, telnet, serial, raw_tcp, container, mosh}, terminal::{LogSettings, TriggerRule}};
 
 
 
@@ -8,40 +8,154 @@ bitflags::bitflags! {
         const SESSION_CACHE = 0b0000_0000_0000_0000_0001;
         const OPEN_PTY = 0b0000_0000_0000_0000_0010;
         const OPEN_TUNNEL   = 0b0000_0000_0000_0000_0100;
+        const PTY_RESIZE    = 0b0000_0000_0000_0000_1000;
+        const EXEC          = 0b0000_0000_0000_0001_0000;
     }
 }
+bitflags::bitflags! {
+    pub struct Inherit: u32 {
+        const USERNAME  = 0b0000_0000_0000_0000_0001;
+        const PORT      = 0b0000_0000_0000_0000_0010;
+        const AUTH      = 0b0000_0000_0000_0000_0100;
+        const TRANSPORT = 0b0000_0000_0000_0000_1000;
+        const FORWARDS  = 0b0000_0000_0000_0001_0000;
+        const TERMINAL  = 0b0000_0000_0000_0010_0000;
+    }
+}
+
+#[derive(Clone)]
+pub struct TerminalSettings {
+    pub term: String,
+    pub scrollback: usize,
+    pub logging: Option<LogSettings>,
+    pub triggers: Vec<TriggerRule>,
+}
+impl Default for TerminalSettings {
+    fn default() -> Self {
+        Self {
+            term: "xterm-256color".to_string(),
+            scrollback: 10000,
+            logging: None,
+            triggers: Vec::new(),
+        }
+    }
+}
+
 pub enum Protocol {
     Ssh(ssh::SshProfile),
+    Local(local::LocalProfile),
+    Telnet(telnet::TelnetProfile),
+    Serial(serial::SerialProfile),
+    RawTcp(raw_tcp::RawTcpProfile),
+    Container(container::ContainerProfile),
+    Mosh(mosh::MoshProfile),
 }
 
 pub struct Profile {
+    pub id: String,
+    pub name: String,
+    pub folder: Vec<String>,
+    pub tags: Vec<String>,
+    pub notes: String,
     pub username: String,
     pub address: String,
     pub port: u16,
     pub transport: Option<String>,
     pub protocol: Protocol,
+    pub terminal: TerminalSettings,
+    pub parent: Option<String>,
+    pub inherit: Inherit,
+    pub template: bool,
 }
 impl Clone for Profile {
     fn clone(&self) -> Self {
         Self {
+            id: self.id.clone(),
+            name: self.name.clone(),
+            folder: self.folder.clone(),
+            tags: self.tags.clone(),
+            notes: self.notes.clone(),
             username: self.username.clone(),
             address: self.address.clone(), 
-            port: self.port.clone(), 
+            port: self.port, 
             transport: self.transport.clone(), 
             protocol: match self.protocol {
                 Protocol::Ssh(ref cfg) => Protocol::Ssh(cfg.clone()),
-            }
+                Protocol::Local(ref cfg) => Protocol::Local(cfg.clone()),
+                Protocol::Telnet(ref cfg) => Protocol::Telnet(cfg.clone()),
+                Protocol::Serial(ref cfg) => Protocol::Serial(cfg.clone()),
+                Protocol::RawTcp(ref cfg) => Protocol::RawTcp(cfg.clone()),
+                Protocol::Container(ref cfg) => Protocol::Container(cfg.clone()),
+                Protocol::Mosh(ref cfg) => Protocol::Mosh(cfg.clone()),
+            },
+            terminal: self.terminal.clone(),
+            parent: self.parent.clone(),
+            inherit: self.inherit,
+            template: self.template,
         }
     }
 }
 
 impl Profile {
+    pub fn new_id() -> String {
+        uuid::Uuid::new_v4().to_string()
+    }
+    pub fn stable_id(seed: &str) -> String {
+        uuid::Uuid::new_v5(&uuid::Uuid::NAMESPACE_OID, seed.as_bytes()).to_string()
+    }
     pub fn id(&self) -> String {
-        format!("{}@{}:{}", self.username, self.address, self.port)
+        self.id.clone()
+    }
+    pub fn target(&self) -> String {
+        match &self.protocol {
+            Protocol::Local(cfg) => format!("local:{}", cfg.command.as_deref().unwrap_or("shell")),
+            Protocol::Serial(cfg) => format!("serial:{}@{}", cfg.device, cfg.baud_rate),
+            Protocol::Container(cfg) => format!("{}:{}", cfg.runtime.command(), cfg.container),
+            _ => format!("{}@{}:{}", self.username, self.address, self.port),
+        }
+    }
+    pub fn inherit_from(&mut self, parent: &Profile) {
+        if self.inherit.contains(Inherit::USERNAME) {
+            self.username = parent.username.clone();
+        }
+        if self.inherit.contains(Inherit::PORT) {
+            self.port = parent.port;
+        }
+        if self.inherit.contains(Inherit::TRANSPORT) {
+            self.transport = parent.transport.clone();
+        }
+        if self.inherit.contains(Inherit::TERMINAL) {
+            self.terminal = parent.terminal.clone();
+        }
+        match (&mut self.protocol, &parent.protocol) {
+            (Protocol::Ssh(cfg), Protocol::Ssh(parent_cfg)) => {
+                if self.inherit.contains(Inherit::AUTH) {
+                    cfg.password = parent_cfg.password.clone();
+                    cfg.identity_file = parent_cfg.identity_file.clone();
+                }
+                if self.inherit.contains(Inherit::FORWARDS) {
+                    cfg.local_forwards = parent_cfg.local_forwards.clone();
+                    cfg.remote_forwards = parent_cfg.remote_forwards.clone();
+                }
+            }
+            (Protocol::Mosh(cfg), Protocol::Mosh(mosh::MoshProfile { ssh: parent_cfg, .. }) | Protocol::Ssh(parent_cfg))
+                if self.inherit.contains(Inherit::AUTH) =>
+            {
+                cfg.ssh.password = parent_cfg.password.clone();
+                cfg.ssh.identity_file = parent_cfg.identity_file.clone();
+            }
+            _ => {}
+        }
     }
     pub fn capacity(&self) -> Capacity {
         match self.protocol {
             Protocol::Ssh(_) => Capacity::all(),
+            Protocol::Local(_) => Capacity::OPEN_PTY | Capacity::PTY_RESIZE | Capacity::EXEC,
+            Protocol::Telnet(_) => Capacity::OPEN_PTY | Capacity::PTY_RESIZE,
+            Protocol::Serial(_) => Capacity::OPEN_PTY,
+            Protocol::RawTcp(_) => Capacity::OPEN_PTY,
+            Protocol::Container(_) => Capacity::OPEN_PTY | Capacity::PTY_RESIZE,
+            Protocol::Mosh(_) => Capacity::OPEN_PTY | Capacity::PTY_RESIZE,
         }
     }
 }
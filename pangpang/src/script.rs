@@ -0,0 +1,268 @@
+use std::{sync::Arc, time::Duration};
+
+use rhai::{Dynamic, Engine, EvalAltResult, Map};
+use tokio::{io::{AsyncReadExt, AsyncWriteExt}, runtime::Handle, sync::{mpsc, Mutex}};
+
+use crate::{errors, session::{PpPty, PpSessionGuard, PpStream}, PangPang};
+
+
+
+
+// everything a script does goes through the public PangPang api, so scripts run the same with or without the GUI:
+//
+//   let s = open_session("profile id");      // keeps the connection cached while the script runs
+//   let r = s.exec("uname -a");              // #{ output: "...", status: 0 }
+//   let t = open_pty("profile id");
+//   t.write("sudo -i\r");
+//   t.expect("[Pp]assword", 10);             // regex, timeout in seconds, throws on timeout or eof
+//   let db = open_tunnel("profile id", "127.0.0.1", 5432);
+//   sleep(1);
+
+type ScriptResult<T> = Result<T, Box<EvalAltResult>>;
+
+#[derive(Clone)]
+pub struct ScriptSession(Arc<PpSessionGuard>);
+
+#[derive(Clone)]
+pub struct ScriptStream(Arc<Mutex<StreamState>>);
+
+struct StreamState {
+    stream: Option<Box<dyn PpStream>>,
+    buffer: Vec<u8>,
+}
+
+impl ScriptStream {
+    fn new(stream: Box<dyn PpStream>) -> Self {
+        Self(Arc::new(Mutex::new(StreamState { stream: Some(stream), buffer: Vec::new() })))
+    }
+}
+
+impl StreamState {
+
+    async fn write(&mut self, data: &str) -> Result<(), errors::Error> {
+        let stream = self.stream.as_mut().ok_or_else(closed)?;
+        stream.write_all(data.as_bytes()).await?;
+        Ok(())
+    }
+
+    // whatever arrives within the timeout, empty on timeout or eof
+    async fn read(&mut self, timeout: Duration) -> Result<String, errors::Error> {
+        if self.buffer.is_empty() {
+            match self.fill(tokio::time::Instant::now() + timeout).await {
+                Err(errors::Error::ScriptError(_)) => {}
+                other => {
+                    other?;
+                }
+            }
+        }
+        Ok(String::from_utf8_lossy(&std::mem::take(&mut self.buffer)).into_owned())
+    }
+
+    async fn expect(&mut self, pattern: &str, timeout: Duration) -> Result<String, errors::Error> {
+        let re = regex::bytes::Regex::new(pattern)
+            .map_err(|e| errors::Error::InvalidPattern(format!("{}: {}", pattern, e)))?;
+        let deadline = tokio::time::Instant::now() + timeout;
+        loop {
+            if let Some(m) = re.find(&self.buffer) {
+                let (text, end) = (String::from_utf8_lossy(m.as_bytes()).into_owned(), m.end());
+                self.buffer.drain(..end);
+                return Ok(text);
+            }
+            if !self.fill(deadline).await? {
+                return Err(errors::Error::ScriptError(format!("stream closed while waiting for {}", pattern)));
+            }
+        }
+    }
+
+    // false on eof
+    async fn fill(&mut self, deadline: tokio::time::Instant) -> Result<bool, errors::Error> {
+        let stream = self.stream.as_mut().ok_or_else(closed)?;
+        let mut buf = [0u8; 4096];
+        match tokio::time::timeout_at(deadline, stream.read(&mut buf)).await {
+            Err(_) => Err(errors::Error::ScriptError("timeout".to_string())),
+            Ok(Ok(0)) => Ok(false),
+            Ok(Ok(n)) => {
+                self.buffer.extend_from_slice(&buf[..n]);
+                Ok(true)
+            }
+            Ok(Err(e)) if e.kind() == std::io::ErrorKind::UnexpectedEof => Ok(false),
+            Ok(Err(e)) => Err(e.into()),
+        }
+    }
+}
+
+fn closed() -> errors::Error {
+    errors::Error::ScriptError("stream closed".to_string())
+}
+
+fn script_error(e: errors::Error) -> Box<EvalAltResult> {
+    format!("{:?}", e).into()
+}
+
+async fn exec(pp: &PangPang, id: &String, command: &String) -> Result<Map, errors::Error> {
+    let mut out = pp.exec(id, command).await?;
+    let mut data = Vec::new();
+    let mut buf = [0u8; 4096];
+    loop {
+        match out.read(&mut buf).await {
+            Ok(0) => break,
+            Ok(n) => data.extend_from_slice(&buf[..n]),
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(e.into()),
+        }
+    }
+    let mut result = Map::new();
+    result.insert("output".into(), String::from_utf8_lossy(&data).into_owned().into());
+    result.insert("status".into(), Dynamic::from(out.exit_status().await.map(|s| s as i64).unwrap_or(-1)));
+    Ok(result)
+}
+
+fn engine(pp: Arc<PangPang>, rt: Handle, output: mpsc::Sender<String>) -> Engine {
+    let mut engine = Engine::new();
+    let out = output.clone();
+    engine.on_print(move |s| {
+        let _ = out.blocking_send(s.to_string());
+    });
+    engine.on_debug(move |s, _, pos| {
+        let _ = output.blocking_send(format!("{:?} {}", pos, s));
+    });
+    engine.register_type_with_name::<ScriptSession>("Session");
+    engine.register_type_with_name::<ScriptStream>("Stream");
+
+    let (p, h) = (pp.clone(), rt.clone());
+    engine.register_fn("open_session", move |id: &str| -> ScriptResult<ScriptSession> {
+        h.block_on(p.open_session(&id.to_string()))
+            .map(|s| ScriptSession(Arc::new(s)))
+            .map_err(script_error)
+    });
+    engine.register_fn("profile", |s: &mut ScriptSession| -> String {
+        s.0.profile().clone()
+    });
+    let (p, h) = (pp.clone(), rt.clone());
+    engine.register_fn("exec", move |s: &mut ScriptSession, command: &str| -> ScriptResult<Map> {
+        h.block_on(exec(&p, s.0.profile(), &command.to_string())).map_err(script_error)
+    });
+    let (p, h) = (pp.clone(), rt.clone());
+    engine.register_fn("exec", move |id: &str, command: &str| -> ScriptResult<Map> {
+        h.block_on(exec(&p, &id.to_string(), &command.to_string())).map_err(script_error)
+    });
+    let (p, h) = (pp.clone(), rt.clone());
+    engine.register_fn("open_pty", move |id: &str| -> ScriptResult<ScriptStream> {
+        h.block_on(p.open_pty(&id.to_string()))
+            .map(|pty| ScriptStream::new(Box::new(pty)))
+            .map_err(script_error)
+    });
+    let (p, h) = (pp, rt.clone());
+    engine.register_fn("open_tunnel", move |id: &str, host: &str, port: i64| -> ScriptResult<ScriptStream> {
+        let port = u16::try_from(port).map_err(|_| -> Box<EvalAltResult> { format!("invalid port {}", port).into() })?;
+        h.block_on(p.open_tunnel(&id.to_string(), &host.to_string(), port))
+            .map(|tunnel| ScriptStream::new(Box::new(tunnel)))
+            .map_err(script_error)
+    });
+
+    let h = rt.clone();
+    engine.register_fn("write", move |s: &mut ScriptStream, data: &str| -> ScriptResult<()> {
+        h.block_on(async { s.0.lock().await.write(data).await }).map_err(script_error)
+    });
+    let h = rt.clone();
+    engine.register_fn("read", move |s: &mut ScriptStream, timeout: i64| -> ScriptResult<String> {
+        h.block_on(async { s.0.lock().await.read(seconds(timeout)).await }).map_err(script_error)
+    });
+    let h = rt.clone();
+    engine.register_fn("expect", move |s: &mut ScriptStream, pattern: &str, timeout: i64| -> ScriptResult<String> {
+        h.block_on(async { s.0.lock().await.expect(pattern, seconds(timeout)).await }).map_err(script_error)
+    });
+    let h = rt;
+    engine.register_fn("close", move |s: &mut ScriptStream| {
+        h.block_on(async { s.0.lock().await.stream = None });
+    });
+    engine.register_fn("sleep", |secs: i64| std::thread::sleep(seconds(secs)));
+    engine
+}
+
+fn seconds(secs: i64) -> Duration {
+    Duration::from_secs(secs.max(0) as u64)
+}
+
+// runs on a blocking thread; print and debug output goes to `output`
+pub async fn run(pp: Arc<PangPang>, source: String, output: mpsc::Sender<String>) -> Result<(), errors::Error> {
+    let rt = Handle::current();
+    tokio::task::spawn_blocking(move || {
+        engine(pp, rt, output)
+            .run(&source)
+            .map_err(|e| errors::Error::ScriptError(e.to_string()))
+    })
+    .await
+    .map_err(|e| errors::Error::ScriptError(e.to_string()))?
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{profile::{Inherit, Profile, Protocol, TerminalSettings}, session::local::LocalProfile, storage::{MockStorage, Storage}};
+
+    use super::*;
+
+    fn local() -> (Arc<PangPang>, String) {
+        let mut storage = MockStorage::new();
+        let profile = Profile {
+            id: Profile::stable_id("script test shell"),
+            name: "script test shell".to_string(),
+            folder: Vec::new(),
+            tags: Vec::new(),
+            notes: String::new(),
+            username: String::new(),
+            address: String::new(),
+            port: 0,
+            transport: None,
+            protocol: Protocol::Local(LocalProfile { command: Some("/bin/sh".to_string()), ..Default::default() }),
+            terminal: TerminalSettings::default(),
+            parent: None,
+            inherit: Inherit::empty(),
+            template: false,
+        };
+        let id = profile.id();
+        storage.insert(profile).unwrap();
+        (Arc::new(PangPang::new(Arc::new(Mutex::new(storage)))), id)
+    }
+
+    async fn run_script(pp: Arc<PangPang>, source: String) -> (Result<(), errors::Error>, Vec<String>) {
+        let (tx, mut rx) = mpsc::channel(16);
+        let result = run(pp, source, tx).await;
+        let mut printed = Vec::new();
+        while let Ok(s) = rx.try_recv() {
+            printed.push(s);
+        }
+        (result, printed)
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn pty_and_exec() {
+        let (pp, id) = local();
+        let source = format!(r#"
+            let t = open_pty("{id}");
+            t.write("echo $((6 * 7))\r");
+            print(t.expect("[0-9]+\r\n", 5));
+            t.write("exit\r");
+            t.close();
+            let r = exec("{id}", "echo hi; exit 3");
+            print(r.output);
+            print(r.status);
+        "#, id = id);
+        let (result, printed) = run_script(pp, source).await;
+        result.unwrap();
+        assert_eq!(printed, vec!["42\r\n", "hi\r\n", "3"]);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn expect_timeout() {
+        let (pp, id) = local();
+        let source = format!(r#"
+            let t = open_pty("{}");
+            t.expect("never printed", 1);
+            print("unreachable");
+        "#, id);
+        let (result, printed) = run_script(pp, source).await;
+        assert!(matches!(result, Err(errors::Error::ScriptError(e)) if e.contains("timeout")));
+        assert!(printed.is_empty());
+    }
+}
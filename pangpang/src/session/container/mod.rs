@@ -0,0 +1,276 @@
+use tokio::io::AsyncReadExt;
+
+use crate::{errors, profile::TerminalSettings};
+
+use super::{PpStream, PpSession, PpPty, PpSessionGuard, PpSessionManager, shell_quote};
+
+
+
+
+const PS_FORMAT: &str = "{{.ID}}\t{{.Names}}\t{{.Image}}\t{{.Status}}";
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ContainerRuntime {
+    Docker,
+    Podman,
+}
+
+impl ContainerRuntime {
+    pub fn all() -> [ContainerRuntime; 2] {
+        [ContainerRuntime::Docker, ContainerRuntime::Podman]
+    }
+
+    pub fn command(&self) -> &'static str {
+        match self {
+            ContainerRuntime::Docker => "docker",
+            ContainerRuntime::Podman => "podman",
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct ContainerProfile {
+    pub runtime: ContainerRuntime,
+    pub container: String,
+    pub command: String,
+    pub user: Option<String>,
+}
+
+impl Default for ContainerProfile {
+    fn default() -> Self {
+        Self {
+            runtime: ContainerRuntime::Docker,
+            container: String::new(),
+            command: "/bin/sh".to_string(),
+            user: None,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct ContainerInfo {
+    pub id: String,
+    pub name: String,
+    pub image: String,
+    pub status: String,
+}
+
+pub struct Session {
+    host: Option<PpSessionGuard>,
+    cfg: ContainerProfile,
+    term: String,
+}
+
+impl Session {
+    pub fn new(host: Option<PpSessionGuard>, term: &TerminalSettings, cfg: ContainerProfile) -> Self {
+        Self { host, cfg, term: term.term.clone() }
+    }
+
+    fn exec_args(&self, cwd: Option<&String>) -> Vec<String> {
+        let mut args = vec!["exec".to_string(), "-it".to_string(), "-e".to_string(), format!("TERM={}", self.term)];
+        if let Some(user) = &self.cfg.user {
+            args.push("-u".to_string());
+            args.push(user.clone());
+        }
+        if let Some(cwd) = cwd {
+            args.push("-w".to_string());
+            args.push(cwd.clone());
+        }
+        args.push(self.cfg.container.clone());
+        args.extend(self.cfg.command.split_whitespace().map(|s| s.to_string()));
+        args
+    }
+
+    async fn spawn(&self, cwd: Option<&String>) -> Result<Box<dyn PpPty>, errors::Error> {
+        let runtime = self.cfg.runtime.command().to_string();
+        match &self.host {
+            // the remote CLI sees the pty window change and resizes the exec session itself
+            Some(host) => {
+                let cmd = std::iter::once(&runtime)
+                    .chain(self.exec_args(cwd).iter())
+                    .map(|s| shell_quote(s))
+                    .collect::<Vec<String>>()
+                    .join(" ");
+                host.exec(&cmd, true).await
+            }
+            None => local_exec(&runtime, &self.exec_args(cwd), &self.term),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl PpSession for Session {
+    async fn open_tunnel(
+        &self,
+        host: &String,
+        port: u16,
+    ) -> Result<Box<dyn PpStream>, errors::Error> {
+        Err(errors::Error::Unsupported(format!("container session can not open tunnel to {}:{}", host, port)))
+    }
+
+    async fn open_pty(&self) -> Result<Box<dyn PpPty>, errors::Error> {
+        self.spawn(None).await
+    }
+
+    async fn open_pty_in(&self, cwd: &String) -> Result<Box<dyn PpPty>, errors::Error> {
+        self.spawn(Some(cwd)).await
+    }
+}
+
+#[cfg(unix)]
+fn local_exec(runtime: &String, args: &Vec<String>, term: &String) -> Result<Box<dyn PpPty>, errors::Error> {
+    Ok(Box::new(super::local::LocalPty::spawn(runtime, args, None, term)?))
+}
+
+#[cfg(not(unix))]
+fn local_exec(runtime: &String, _args: &Vec<String>, _term: &String) -> Result<Box<dyn PpPty>, errors::Error> {
+    Err(errors::Error::Unsupported(format!("local pty for {}", runtime)))
+}
+
+pub async fn list(mgr: &PpSessionManager, host: Option<&String>, runtime: ContainerRuntime) -> Result<Vec<ContainerInfo>, errors::Error> {
+    let output = match host {
+        Some(id) => {
+            let cmd = format!("{} ps --format {}", runtime.command(), shell_quote(PS_FORMAT));
+            remote_ps(runtime.command(), Box::new(mgr.exec(id, &cmd, false).await?)).await?
+        }
+        None => local_ps(runtime.command()).await?,
+    };
+    Ok(parse_ps(&output))
+}
+
+async fn remote_ps(program: &str, mut out: Box<dyn PpPty>) -> Result<Vec<u8>, errors::Error> {
+    let mut data = Vec::new();
+    let mut buf = [0u8; 4096];
+    loop {
+        match out.read(&mut buf).await {
+            Ok(0) => break,
+            Ok(n) => data.extend_from_slice(&buf[..n]),
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(e.into()),
+        }
+    }
+    // a channel closed without a status may have cut the listing short
+    match out.exit_status().await {
+        Some(0) => Ok(data),
+        Some(code) => Err(errors::Error::PpStreamError(format!(
+            "{} ps exited with {}: {}", program, code, String::from_utf8_lossy(&data).trim()
+        ))),
+        None => Err(errors::Error::PpStreamError(format!("{} ps exited without a status", program))),
+    }
+}
+
+async fn local_ps(program: &str) -> Result<Vec<u8>, errors::Error> {
+    let output = tokio::process::Command::new(program)
+        .args(["ps", "--format", PS_FORMAT])
+        .output()
+        .await?;
+    if !output.status.success() {
+        return Err(errors::Error::PpStreamError(format!(
+            "{} ps failed: {}", program, String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(output.stdout)
+}
+
+fn parse_ps(output: &[u8]) -> Vec<ContainerInfo> {
+    String::from_utf8_lossy(output)
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.trim_end_matches('\r').split('\t');
+            Some(ContainerInfo {
+                id: fields.next().filter(|s| !s.is_empty())?.to_string(),
+                name: fields.next().unwrap_or_default().to_string(),
+                image: fields.next().unwrap_or_default().to_string(),
+                status: fields.next().unwrap_or_default().to_string(),
+            })
+        })
+        .collect()
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::{path::PathBuf, time::Duration};
+
+    use tokio::io::AsyncWriteExt;
+
+    use super::*;
+
+    #[async_trait::async_trait]
+    impl PpPty for tokio::io::DuplexStream {
+        async fn resize(&mut self, _width: usize, _height: usize) -> Result<(), errors::Error> {
+            Ok(())
+        }
+    }
+
+    // a stand-in for the docker cli
+    fn fake_docker(name: &str, body: &str) -> String {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = std::env::temp_dir().join(format!("pangpang-container-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path: PathBuf = dir.join(name);
+        std::fs::write(&path, format!("#!/bin/sh\n{}", body)).unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+        path.to_string_lossy().into_owned()
+    }
+
+    #[tokio::test]
+    async fn list_local() {
+        let docker = fake_docker("docker-ps", r#"
+[ "$1 $2 $3" = "ps --format {{.ID}}	{{.Names}}	{{.Image}}	{{.Status}}" ] || exit 2
+printf 'abc123\tweb\tnginx:latest\tUp 2 hours\n'
+printf 'def456\tdb\tpostgres:14\tUp 5 minutes\n'
+"#);
+        let containers = parse_ps(&local_ps(&docker).await.unwrap());
+        assert_eq!(containers.len(), 2);
+        assert_eq!(containers[0].id, "abc123");
+        assert_eq!(containers[0].name, "web");
+        assert_eq!(containers[1].image, "postgres:14");
+        assert_eq!(containers[1].status, "Up 5 minutes");
+    }
+
+    #[tokio::test]
+    async fn list_local_failure() {
+        let docker = fake_docker("docker-down", "echo 'Cannot connect to the Docker daemon' >&2\nexit 1\n");
+        match local_ps(&docker).await {
+            Err(errors::Error::PpStreamError(e)) => assert!(e.contains("Cannot connect")),
+            other => panic!("unexpected result {:?}", other.map(|o| String::from_utf8_lossy(&o).into_owned())),
+        }
+    }
+
+    #[tokio::test]
+    async fn list_remote_exit_status() {
+        let docker = fake_docker("docker-remote", "printf 'abc\\tweb\\tnginx\\tUp\\r\\n'\n");
+        let out = local_exec(&docker, &Vec::new(), &"xterm".to_string()).unwrap();
+        let containers = parse_ps(&remote_ps("docker", out).await.unwrap());
+        assert_eq!(containers.len(), 1);
+        assert_eq!(containers[0].status, "Up");
+
+        let docker = fake_docker("docker-remote-failed", "echo 'permission denied'\nexit 3\n");
+        let out = local_exec(&docker, &Vec::new(), &"xterm".to_string()).unwrap();
+        assert!(matches!(remote_ps("docker", out).await, Err(errors::Error::PpStreamError(e)) if e.contains("exited with 3")));
+
+        // the channel went away before the remote command reported how it ended
+        let (out, mut remote) = tokio::io::duplex(64);
+        remote.write_all(b"abc\tweb\tnginx\tUp\n").await.unwrap();
+        drop(remote);
+        assert!(matches!(remote_ps("docker", Box::new(out)).await, Err(errors::Error::PpStreamError(e)) if e.contains("without a status")));
+    }
+
+    #[tokio::test]
+    async fn exec_args() {
+        let docker = fake_docker("docker-exec", "echo \"$@\"\n");
+        let cfg = ContainerProfile {
+            container: "web".to_string(),
+            command: "bash -l".to_string(),
+            user: Some("root".to_string()),
+            ..Default::default()
+        };
+        let session = Session::new(None, &TerminalSettings::default(), cfg);
+        let args = session.exec_args(Some(&"/srv".to_string()));
+        let mut pty = local_exec(&docker, &args, &"xterm".to_string()).unwrap();
+        let mut output = String::new();
+        tokio::time::timeout(Duration::from_secs(5), pty.read_to_string(&mut output)).await.unwrap().unwrap();
+        assert_eq!(output.trim(), "exec -it -e TERM=xterm-256color -u root -w /srv web bash -l");
+        assert_eq!(pty.exit_status().await, Some(0));
+    }
+}
@@ -0,0 +1,48 @@
+use std::time::SystemTime;
+
+use tokio::sync::broadcast;
+
+
+
+
+pub type EventSender = broadcast::Sender<SessionEvent>;
+pub type EventReceiver = broadcast::Receiver<SessionEvent>;
+
+#[derive(Clone, Debug)]
+pub enum SessionEventKind {
+    Connecting,
+    // the accepted method, for protocols that log in
+    Authenticated(String),
+    Connected,
+    ConnectFailed(String),
+    Cached,
+    Reused(usize),
+    Released(usize),
+    Evicted,
+    Closed,
+    PtyOpened,
+    PtyClosed,
+    TunnelOpened(String, u16),
+    TunnelClosed,
+}
+
+#[derive(Clone, Debug)]
+pub struct SessionEvent {
+    pub profile: String,
+    pub time: SystemTime,
+    pub kind: SessionEventKind,
+}
+
+pub fn channel() -> EventSender {
+    broadcast::channel(256).0
+}
+
+pub(crate) fn emit(events: &EventSender, profile: &String, kind: SessionEventKind) {
+    log::debug!("session event {}: {:?}", profile, kind);
+    // nobody listening is fine
+    let _ = events.send(SessionEvent {
+        profile: profile.clone(),
+        time: SystemTime::now(),
+        kind,
+    });
+}
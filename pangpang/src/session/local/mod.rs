@@ -0,0 +1,82 @@
+use crate::{errors, profile::TerminalSettings};
+
+use super::{PpStream, PpSession, PpPty};
+
+#[cfg(unix)]
+mod pty;
+#[cfg(unix)]
+pub(crate) use pty::LocalPty;
+
+
+
+
+#[derive(Clone, Default)]
+pub struct LocalProfile {
+    pub command: Option<String>,
+    pub args: Vec<String>,
+    pub cwd: Option<String>,
+}
+
+pub struct Session {
+    cfg: LocalProfile,
+    term: String,
+}
+
+impl Session {
+    pub fn new(term: &TerminalSettings, cfg: LocalProfile) -> Self {
+        Self { cfg, term: term.term.clone() }
+    }
+
+    fn program(&self) -> String {
+        match &self.cfg.command {
+            Some(cmd) => cmd.clone(),
+            None => default_shell(),
+        }
+    }
+}
+
+#[cfg(unix)]
+fn default_shell() -> String {
+    std::env::var("SHELL").unwrap_or_else(|_| "/bin/sh".to_string())
+}
+
+#[cfg(not(unix))]
+fn default_shell() -> String {
+    std::env::var("COMSPEC").unwrap_or_else(|_| "cmd.exe".to_string())
+}
+
+#[async_trait::async_trait]
+impl PpSession for Session {
+    async fn open_tunnel(
+        &self,
+        host: &String,
+        port: u16,
+    ) -> Result<Box<dyn PpStream>, errors::Error> {
+        Err(errors::Error::Unsupported(format!("local session can not open tunnel to {}:{}", host, port)))
+    }
+
+    #[cfg(unix)]
+    async fn open_pty(&self) -> Result<Box<dyn PpPty>, errors::Error> {
+        let pty = pty::LocalPty::spawn(&self.program(), &self.cfg.args, self.cfg.cwd.as_ref(), &self.term)?;
+        Ok(Box::new(pty))
+    }
+
+    #[cfg(not(unix))]
+    async fn open_pty(&self) -> Result<Box<dyn PpPty>, errors::Error> {
+        Err(errors::Error::Unsupported(format!("local pty for {}", self.program())))
+    }
+
+    #[cfg(unix)]
+    async fn open_pty_in(&self, cwd: &String) -> Result<Box<dyn PpPty>, errors::Error> {
+        let pty = pty::LocalPty::spawn(&self.program(), &self.cfg.args, Some(cwd), &self.term)?;
+        Ok(Box::new(pty))
+    }
+
+    // the command line goes to /bin/sh whatever the profile runs, and always gets a pty
+    #[cfg(unix)]
+    async fn exec(&self, command: &String, _pty: bool) -> Result<Box<dyn PpPty>, errors::Error> {
+        let args = vec!["-c".to_string(), command.clone()];
+        let pty = pty::LocalPty::spawn(&"/bin/sh".to_string(), &args, self.cfg.cwd.as_ref(), &self.term)?;
+        Ok(Box::new(pty))
+    }
+}
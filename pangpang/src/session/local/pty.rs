@@ -0,0 +1,182 @@
+use std::{io, os::unix::{io::{AsRawFd, FromRawFd, RawFd}, process::ExitStatusExt}, pin::Pin, process::Stdio, task::{Context, Poll}};
+
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf, unix::AsyncFd};
+
+use crate::errors;
+
+use super::PpPty;
+
+
+
+
+struct PtyMaster(RawFd);
+
+impl AsRawFd for PtyMaster {
+    fn as_raw_fd(&self) -> RawFd {
+        self.0
+    }
+}
+
+impl Drop for PtyMaster {
+    fn drop(&mut self) {
+        unsafe { libc::close(self.0) };
+    }
+}
+
+pub struct LocalPty {
+    master: AsyncFd<PtyMaster>,
+    child: tokio::process::Child,
+}
+
+impl LocalPty {
+    pub fn spawn(program: &String, args: &Vec<String>, cwd: Option<&String>, term: &String) -> Result<Self, errors::Error> {
+        let (mut master, mut slave): (RawFd, RawFd) = (0, 0);
+        let size = libc::winsize { ws_row: 20, ws_col: 80, ws_xpixel: 0, ws_ypixel: 0 };
+        if unsafe { libc::openpty(&mut master, &mut slave, std::ptr::null_mut(), std::ptr::null_mut(), &size as *const libc::winsize as *mut libc::winsize) } < 0 {
+            return Err(io::Error::last_os_error().into());
+        }
+        let master = PtyMaster(master);
+        unsafe {
+            libc::fcntl(master.0, libc::F_SETFD, libc::FD_CLOEXEC);
+            let flags = libc::fcntl(master.0, libc::F_GETFL);
+            libc::fcntl(master.0, libc::F_SETFL, flags | libc::O_NONBLOCK);
+        }
+
+        let mut cmd = tokio::process::Command::new(program);
+        cmd.args(args).env("TERM", term).kill_on_drop(true);
+        if let Some(cwd) = cwd {
+            cmd.current_dir(cwd);
+        }
+        let child = unsafe {
+            cmd.stdin(Stdio::from_raw_fd(libc::dup(slave)))
+                .stdout(Stdio::from_raw_fd(libc::dup(slave)))
+                .stderr(Stdio::from_raw_fd(libc::dup(slave)))
+                .pre_exec(|| {
+                    if libc::setsid() < 0 || libc::ioctl(0, libc::TIOCSCTTY as _, 0) < 0 {
+                        return Err(io::Error::last_os_error());
+                    }
+                    Ok(())
+                })
+                .spawn()
+        };
+        unsafe { libc::close(slave) };
+        let child = child?;
+        log::info!("local pty spawned {} pid: {:?}", program, child.id());
+        // the fd is owned by PtyMaster and only closed when the AsyncFd drops it
+        let master = unsafe { AsyncFd::register(master) }.map_err(|e| e.into_parts().1)?;
+        Ok(Self {
+            master,
+            child,
+        })
+    }
+}
+
+impl AsyncRead for LocalPty {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        loop {
+            let mut guard = match this.master.poll_read_ready(cx) {
+                Poll::Ready(Ok(g)) => g,
+                Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+                Poll::Pending => return Poll::Pending,
+            };
+            let unfilled = buf.initialize_unfilled();
+            let res = guard.try_io(|fd| {
+                let n = unsafe { libc::read(fd.as_raw_fd(), unfilled.as_mut_ptr() as *mut _, unfilled.len()) };
+                if n < 0 {
+                    Err(io::Error::last_os_error())
+                } else {
+                    Ok(n as usize)
+                }
+            });
+            return match res {
+                Ok(Ok(n)) => {
+                    buf.advance(n);
+                    Poll::Ready(Ok(()))
+                }
+                // the slave side reports EIO once the child closed it
+                Ok(Err(e)) if e.raw_os_error() == Some(libc::EIO) => Poll::Ready(Ok(())),
+                Ok(Err(e)) => Poll::Ready(Err(e)),
+                Err(_) => continue,
+            };
+        }
+    }
+}
+
+impl AsyncWrite for LocalPty {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<Result<usize, io::Error>> {
+        let this = self.get_mut();
+        loop {
+            let mut guard = match this.master.poll_write_ready(cx) {
+                Poll::Ready(Ok(g)) => g,
+                Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+                Poll::Pending => return Poll::Pending,
+            };
+            let res = guard.try_io(|fd| {
+                let n = unsafe { libc::write(fd.as_raw_fd(), buf.as_ptr() as *const _, buf.len()) };
+                if n < 0 {
+                    Err(io::Error::last_os_error())
+                } else {
+                    Ok(n as usize)
+                }
+            });
+            match res {
+                Ok(r) => return Poll::Ready(r),
+                Err(_) => continue,
+            }
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), io::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), io::Error>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[async_trait::async_trait]
+impl PpPty for LocalPty {
+    async fn resize(&mut self, width: usize, height: usize) -> Result<(), errors::Error> {
+        let size = libc::winsize { ws_row: height as u16, ws_col: width as u16, ws_xpixel: 0, ws_ypixel: 0 };
+        if unsafe { libc::ioctl(self.master.as_raw_fd(), libc::TIOCSWINSZ, &size) } < 0 {
+            return Err(errors::Error::WritePtyError(format!("pty resize error: {:?}", io::Error::last_os_error())));
+        }
+        Ok(())
+    }
+
+    async fn exit_status(&mut self) -> Option<i32> {
+        let status = self.child.wait().await.ok()?;
+        status.code().or_else(|| status.signal().map(|s| 128 + s))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use tokio::io::AsyncReadExt;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn output_and_exit_status() {
+        let args = vec!["-c".to_string(), "echo hi; exit 3".to_string()];
+        let mut pty = LocalPty::spawn(&"/bin/sh".to_string(), &args, None, &"xterm-256color".to_string()).unwrap();
+        pty.resize(120, 40).await.unwrap();
+        let mut out = Vec::new();
+        tokio::time::timeout(Duration::from_secs(5), pty.read_to_end(&mut out)).await.unwrap().unwrap();
+        // the line discipline turns the newline into crlf
+        assert_eq!(out, b"hi\r\n");
+        assert_eq!(pty.exit_status().await, Some(3));
+    }
+}
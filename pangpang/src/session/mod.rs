@@ -1,6 +1,6 @@
 use std::collections::HashMap;
-use std::sync::Arc;
-use std::usize;
+use std::sync::{Arc, Weak};
+use std::time::Duration;
 
 use async_trait::async_trait;
 use tokio::io::AsyncRead;
@@ -12,10 +12,20 @@ use crate::errors;
 use crate::profile;
 
 pub mod ssh;
+pub mod local;
+pub mod telnet;
+pub mod serial;
+pub mod raw_tcp;
+pub mod container;
+pub mod mosh;
 mod session_guard;
 mod tunnel_guard;
 mod pty_guard;
 mod session_allocate;
+mod session_cache;
+pub use session_cache::CachePolicy;
+pub mod event;
+pub mod registry;
 pub use session_guard::PpSessionGuard;
 pub use tunnel_guard::PpTunnelGuard;
 pub use pty_guard::PpPtyGuard;
@@ -25,10 +35,16 @@ impl<T: AsyncRead + AsyncWrite + Send + Sync + Unpin> PpStream for T {}
 #[async_trait]
 pub trait PpPty: PpStream {
     async fn resize(&mut self, width: usize, height: usize) -> Result<(), errors::Error>;
+    async fn exit_status(&mut self) -> Option<i32> {
+        None
+    }
+    async fn send_break(&mut self) -> Result<(), errors::Error> {
+        Err(errors::Error::Unsupported("break".to_string()))
+    }
 }
 
-type SessionCacheType = HashMap<String, (usize, Arc<dyn PpSession>)>;
 #[async_trait]
+#[allow(clippy::ptr_arg)]
 pub trait PpSession: Send + Sync + Unpin {
     async fn open_tunnel(
         &self,
@@ -36,51 +52,153 @@ pub trait PpSession: Send + Sync + Unpin {
         port: u16,
     ) -> Result<Box<dyn PpStream>, errors::Error>;
     async fn open_pty(&self) -> Result<Box<dyn PpPty>, errors::Error>;
-    async fn open_port_forward(&self);
+    // sessions that can not choose the working directory just open a plain pty
+    async fn open_pty_in(&self, _cwd: &String) -> Result<Box<dyn PpPty>, errors::Error> {
+        self.open_pty().await
+    }
+    async fn exec(&self, command: &String, _pty: bool) -> Result<Box<dyn PpPty>, errors::Error> {
+        Err(errors::Error::Unsupported(format!("exec {}", command)))
+    }
+    async fn open_port_forward(&self) -> Result<(), errors::Error> {
+        Err(errors::Error::Unsupported("port forward".to_string()))
+    }
 }
 
 pub struct PpSessionManager {
     config: Arc<Mutex<dyn crate::storage::Storage>>,
-    session_cache: Arc<Mutex<SessionCacheType>>,
+    session_cache: Arc<session_cache::SessionCache>,
     connecting_map: Arc<Mutex<HashMap<String, Arc<Notify>>>>,
+    events: event::EventSender,
+    registry: Arc<registry::Registry>,
 }
 
 impl PpSessionManager {
     pub fn new(config: Arc<Mutex<dyn crate::storage::Storage>>) -> Self {
-        Self {
+        let mgr = Self {
             config,
-            session_cache: Arc::new(Mutex::new(SessionCacheType::new())),
+            session_cache: Arc::new(session_cache::SessionCache::default()),
             connecting_map: Arc::new(Mutex::new(HashMap::new())),
+            events: event::channel(),
+            registry: Arc::new(registry::Registry::default()),
+        };
+        match tokio::runtime::Handle::try_current() {
+            Ok(handle) => {
+                handle.spawn(reap(Arc::downgrade(&mgr.session_cache), Arc::downgrade(&mgr.registry), mgr.events.clone()));
+            }
+            Err(_) => log::warn!("no tokio runtime, idle sessions will only be evicted on release"),
         }
+        mgr
+    }
+
+    pub fn storage(&self) -> Arc<Mutex<dyn crate::storage::Storage>> {
+        self.config.clone()
+    }
+
+    pub fn cache_policy(&self) -> CachePolicy {
+        self.session_cache.policy()
+    }
+
+    pub fn set_cache_policy(&self, policy: CachePolicy) {
+        log::info!("session cache policy: {:?}", policy);
+        self.session_cache.set_policy(policy);
+    }
+
+    pub fn subscribe(&self) -> event::EventReceiver {
+        self.events.subscribe()
+    }
+
+    fn emit(&self, id: &String, kind: event::SessionEventKind) {
+        event::emit(&self.events, id, kind);
+    }
+
+    fn guard(&self, s: Arc<dyn PpSession>, id: &String, cached: bool, serial: u64) -> PpSessionGuard {
+        let cache_id = if cached { Some(id.to_owned()) } else { None };
+        PpSessionGuard::new(s, cache_id, self.session_cache.clone(), id.to_owned(), serial, self.events.clone(), self.registry.clone())
+    }
+
+    pub async fn snapshot(&self) -> Vec<registry::SessionSnapshot> {
+        let refs = self.session_cache.refs().await;
+        self.registry.snapshot(&refs)
+    }
+
+    pub async fn close_session(&self, serial: u64) -> bool {
+        let (id, cached) = match self.registry.close_session(serial) {
+            Some(s) => s,
+            None => return false,
+        };
+        if cached && self.session_cache.evict(&id, serial).await {
+            self.registry.remove_session(serial);
+            self.emit(&id, event::SessionEventKind::Evicted);
+        }
+        true
+    }
+
+    pub fn close_channel(&self, serial: u64) -> bool {
+        self.registry.close_channel(serial)
+    }
+
+    pub async fn profile(&self, id: &String) -> Result<profile::Profile, errors::Error> {
+        self.config.lock().await.resolve(id)
     }
 
     pub async fn open_session(&self, id: &String) -> Result<PpSessionGuard, errors::Error> {
-        let cfg = self.config.lock().await.get(id)?;
+        let cfg = self.profile(id).await?;
         if cfg.capacity().contains(profile::Capacity::SESSION_CACHE) {
             self.open_session_from_cache(id).await
         } else {
-            Ok(PpSessionGuard::new(self.alloc_session(id).await?, None, self.session_cache.clone()))
+            let s = self.connect(id).await?;
+            let serial = self.registry.add_session(id, false);
+            Ok(self.guard(s, id, false, serial))
         }
     }
 
     #[async_recursion::async_recursion]
     pub async fn open_tunnel(&self, id: &String, host: &String, port: u16) -> Result<PpTunnelGuard, errors::Error> {
+        if !self.profile(id).await?.capacity().contains(profile::Capacity::OPEN_TUNNEL) {
+            return Err(errors::Error::Unsupported(format!("profile {} can not open tunnel", id)));
+        }
         let s = self.open_session(id).await?;
-        Ok(PpTunnelGuard::new(s.open_tunnel(host, port).await?, s))
+        let tunnel = s.open_tunnel(host, port).await?;
+        s.emit(event::SessionEventKind::TunnelOpened(host.to_owned(), port));
+        Ok(PpTunnelGuard::new(tunnel, s, registry::ChannelKind::Tunnel(host.to_owned(), port)))
     }
 
     pub async fn open_pty(&self, id: &String) -> Result<PpPtyGuard, errors::Error> {
+        self.open_pty_in(id, None).await
+    }
+
+    pub async fn open_pty_in(&self, id: &String, cwd: Option<&String>) -> Result<PpPtyGuard, errors::Error> {
+        let capacity = self.profile(id).await?.capacity();
+        if !capacity.contains(profile::Capacity::OPEN_PTY) {
+            return Err(errors::Error::Unsupported(format!("profile {} can not open pty", id)));
+        }
         let s = self.open_session(id).await?;
-        Ok(PpPtyGuard::new(s.open_pty().await?, s))
+        let pty = match cwd {
+            Some(cwd) => s.open_pty_in(cwd).await?,
+            None => s.open_pty().await?,
+        };
+        s.emit(event::SessionEventKind::PtyOpened);
+        Ok(PpPtyGuard::new(pty, s, capacity, registry::ChannelKind::Pty))
+    }
+
+    pub async fn exec(&self, id: &String, command: &String, pty: bool) -> Result<PpPtyGuard, errors::Error> {
+        let capacity = self.profile(id).await?.capacity();
+        if !capacity.contains(profile::Capacity::EXEC) {
+            return Err(errors::Error::Unsupported(format!("profile {} can not exec commands", id)));
+        }
+        let s = self.open_session(id).await?;
+        let pty = s.exec(command, pty).await?;
+        s.emit(event::SessionEventKind::PtyOpened);
+        Ok(PpPtyGuard::new(pty, s, capacity, registry::ChannelKind::Exec(command.to_owned())))
     }
 
     #[async_recursion::async_recursion]
     async fn open_session_from_cache(&self, id: &String) -> Result<PpSessionGuard, errors::Error> {
         loop {
-            if let Some((counter, s)) = self.session_cache.lock().await.get_mut(id) {
-                *counter += 1;
+            if let Some((s, serial, counter)) = self.session_cache.acquire(id).await {
                 log::info!("open session from cache id: {}, ref: {}", id, counter);
-                return Ok(PpSessionGuard::new(s.clone(), Some(id.to_owned()), self.session_cache.clone()));
+                self.emit(id, event::SessionEventKind::Reused(counter));
+                return Ok(self.guard(s, id, true, serial));
             }
             let mut connecting = self.connecting_map.lock().await;
             match connecting.get(id) {
@@ -93,42 +211,97 @@ impl PpSessionManager {
                     let notify = Arc::new(Notify::new());
                     connecting.insert(id.to_owned(), notify.clone());
                     drop(connecting);
-                    let s = self.alloc_session(id).await?;
-                    self.session_cache
-                        .lock()
-                        .await
-                        .insert(id.to_owned(), (1, s.clone()));
+                    let s = match self.connect(id).await {
+                        Ok(s) => s,
+                        Err(e) => {
+                            self.connecting_map.lock().await.remove(id);
+                            notify.notify_waiters();
+                            return Err(e);
+                        }
+                    };
+                    let serial = self.registry.add_session(id, true);
+                    let evicted = self.session_cache.insert(id, s.clone(), serial).await;
                     self.connecting_map.lock().await.remove(id).unwrap();
                     notify.notify_waiters();
-                    return Ok(PpSessionGuard::new(s, Some(id.to_owned()), self.session_cache.clone()));
+                    self.emit(id, event::SessionEventKind::Cached);
+                    for (evicted_id, evicted_serial) in evicted {
+                        log::info!("pangpang session evicted by cache limit: {}", evicted_id);
+                        self.registry.remove_session(evicted_serial);
+                        self.emit(&evicted_id, event::SessionEventKind::Evicted);
+                    }
+                    return Ok(self.guard(s, id, true, serial));
                 }
             };
         }
     }
 
+    async fn connect(&self, id: &String) -> Result<Arc<dyn PpSession>, errors::Error> {
+        self.emit(id, event::SessionEventKind::Connecting);
+        match self.alloc_session(id).await {
+            Ok(s) => {
+                self.emit(id, event::SessionEventKind::Connected);
+                Ok(s)
+            }
+            Err(e) => {
+                self.emit(id, event::SessionEventKind::ConnectFailed(format!("{:?}", e)));
+                Err(e)
+            }
+        }
+    }
+
     #[async_recursion::async_recursion]
     async fn alloc_session(&self, id: &String) -> Result<Arc<dyn PpSession>, errors::Error> {
-        let prof = self.config.lock().await.get(id)?;
+        let prof = self.profile(id).await?;
+        if prof.template {
+            return Err(errors::Error::InvalidProfile(format!("{} is a template", prof.name)));
+        }
         let alloc = session_allocate::Allocator;
         match prof.protocol {
-            profile::Protocol::Ssh(cfg) => {
-                alloc.ssh_alloc(self, &prof.address, prof.port, &prof.username, prof.transport, cfg).await
+            profile::Protocol::Ssh(ref cfg) => {
+                alloc.ssh_alloc(self, id, &prof, cfg.clone()).await
+            }
+            profile::Protocol::Local(cfg) => {
+                alloc.local_alloc(&prof.terminal, cfg).await
+            }
+            profile::Protocol::Telnet(_) => {
+                alloc.telnet_alloc(self, &prof.address, prof.port, prof.transport, &prof.terminal).await
+            }
+            profile::Protocol::Serial(cfg) => {
+                alloc.serial_alloc(cfg).await
+            }
+            profile::Protocol::RawTcp(cfg) => {
+                alloc.raw_tcp_alloc(self, &prof.address, prof.port, prof.transport, cfg).await
+            }
+            profile::Protocol::Container(cfg) => {
+                alloc.container_alloc(self, prof.transport, &prof.terminal, cfg).await
+            }
+            profile::Protocol::Mosh(ref cfg) => {
+                alloc.mosh_alloc(self, id, &prof, cfg.clone()).await
             }
         }
     }
 }
 
+pub(crate) fn shell_quote(s: &str) -> String {
+    if !s.is_empty() && s.chars().all(|c| c.is_ascii_alphanumeric() || "-_./=:@%+,".contains(c)) {
+        s.to_string()
+    } else {
+        format!("'{}'", s.replace('\'', "'\\''"))
+    }
+}
 
-
-
-
-
-
-
-
-
-
-
-
-
-
+async fn reap(cache: Weak<session_cache::SessionCache>, registry: Weak<registry::Registry>, events: event::EventSender) {
+    let mut interval = tokio::time::interval(Duration::from_secs(1));
+    loop {
+        interval.tick().await;
+        let (cache, registry) = match (cache.upgrade(), registry.upgrade()) {
+            (Some(c), Some(r)) => (c, r),
+            _ => break,
+        };
+        for (id, serial) in cache.reap(&registry).await {
+            log::info!("pangpang session expired unused: {}", id);
+            registry.remove_session(serial);
+            event::emit(&events, &id, event::SessionEventKind::Evicted);
+        }
+    }
+}
@@ -0,0 +1,50 @@
+use aes::Aes128;
+use ocb3::{Ocb3, aead::{Aead, KeyInit, generic_array::GenericArray}};
+
+use crate::errors;
+
+
+
+
+pub const DIRECTION_TO_CLIENT: u64 = 1 << 63;
+
+pub struct Crypto {
+    cipher: Ocb3<Aes128>,
+}
+
+impl Crypto {
+    // mosh-server prints the 128 bit key as base64 without the trailing padding
+    pub fn new(key: &str) -> Result<Self, errors::Error> {
+        let key = base64::decode(format!("{}==", key))
+            .map_err(|e| errors::Error::AuthenticationFailed(format!("mosh key: {}", e)))?;
+        let cipher = Ocb3::<Aes128>::new_from_slice(&key)
+            .map_err(|_| errors::Error::AuthenticationFailed("mosh key length".to_string()))?;
+        Ok(Self { cipher })
+    }
+
+    pub fn encrypt(&self, nonce: u64, plaintext: &[u8]) -> Result<Vec<u8>, errors::Error> {
+        let mut packet = nonce.to_be_bytes().to_vec();
+        let ciphertext = self.cipher.encrypt(GenericArray::from_slice(&full_nonce(nonce)), plaintext)
+            .map_err(|_| errors::Error::PpStreamError("mosh: encrypt failed".to_string()))?;
+        packet.extend_from_slice(&ciphertext);
+        Ok(packet)
+    }
+
+    pub fn decrypt(&self, packet: &[u8]) -> Result<(u64, Vec<u8>), errors::Error> {
+        if packet.len() < 8 + 16 {
+            return Err(errors::Error::PpStreamError("mosh: packet too short".to_string()));
+        }
+        let mut nonce = [0u8; 8];
+        nonce.copy_from_slice(&packet[..8]);
+        let nonce = u64::from_be_bytes(nonce);
+        let plaintext = self.cipher.decrypt(GenericArray::from_slice(&full_nonce(nonce)), &packet[8..])
+            .map_err(|_| errors::Error::PpStreamError("mosh: packet failed integrity check".to_string()))?;
+        Ok((nonce, plaintext))
+    }
+}
+
+fn full_nonce(nonce: u64) -> [u8; 12] {
+    let mut n = [0u8; 12];
+    n[4..].copy_from_slice(&nonce.to_be_bytes());
+    n
+}
@@ -0,0 +1,440 @@
+use std::{net::SocketAddr, sync::Arc, time::{Duration, Instant}};
+
+use tokio::{io::{AsyncReadExt, AsyncWriteExt, DuplexStream}, sync::{Mutex, mpsc}};
+
+use crate::{errors, profile::TerminalSettings};
+
+use super::{PpStream, PpSession, PpPty, ssh::SshProfile};
+
+mod crypto;
+mod proto;
+mod transport;
+mod prediction;
+
+pub use prediction::Prediction;
+use proto::{Instruction, UserEvent, HostEvent};
+
+
+
+
+const SHUTDOWN_NUM: u64 = u64::MAX;
+const ACK_DELAY: Duration = Duration::from_millis(100);
+const HEARTBEAT: Duration = Duration::from_secs(3);
+const ROAMING_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Clone)]
+pub struct MoshProfile {
+    pub ssh: SshProfile,
+    pub server: String,
+    pub udp_port: Option<String>,
+    pub prediction: Prediction,
+}
+
+impl Default for MoshProfile {
+    fn default() -> Self {
+        Self {
+            ssh: SshProfile::default(),
+            server: "mosh-server".to_string(),
+            udp_port: None,
+            prediction: Prediction::Adaptive,
+        }
+    }
+}
+
+pub struct Session {
+    connect: Mutex<Option<(SocketAddr, String)>>,
+    prediction: Prediction,
+}
+
+impl Session {
+    // starts mosh-server through the ssh session, which is no longer needed afterwards
+    pub async fn bootstrap(ssh: Arc<dyn PpSession>, addr: &String, term: &TerminalSettings, cfg: &MoshProfile) -> Result<Self, errors::Error> {
+        let mut cmd = format!("{} new -s -c 256 -l LANG=en_US.UTF-8 -l TERM={}", cfg.server, term.term);
+        if let Some(port) = &cfg.udp_port {
+            cmd.push_str(&format!(" -p {}", port));
+        }
+        let mut out = ssh.exec(&cmd, true).await?;
+        let mut output = Vec::new();
+        let mut buf = [0u8; 1024];
+        let connect = tokio::time::timeout(Duration::from_secs(15), async {
+            loop {
+                let text = String::from_utf8_lossy(&output).to_string();
+                if let Some(line) = text.lines().find(|l| l.starts_with("MOSH CONNECT ")) {
+                    let mut fields = line.split_whitespace().skip(2);
+                    if let (Some(port), Some(key)) = (fields.next(), fields.next()) {
+                        return Ok((port.to_string(), key.to_string()));
+                    }
+                }
+                match out.read(&mut buf).await {
+                    Ok(n) if n > 0 => output.extend_from_slice(&buf[..n]),
+                    _ => {
+                        return Err(errors::Error::PpStreamError(format!(
+                            "mosh-server did not start: {}", String::from_utf8_lossy(&output).trim()
+                        )));
+                    }
+                }
+            }
+        }).await;
+        let (port, key) = match connect {
+            Ok(c) => c?,
+            Err(_) => return Err(errors::Error::PpStreamError("mosh-server start timed out".to_string())),
+        };
+        let port: u16 = port.parse()
+            .map_err(|_| errors::Error::PpStreamError(format!("mosh-server reported bad port {}", port)))?;
+        let remote = tokio::net::lookup_host((addr.as_str(), port)).await?
+            .next()
+            .ok_or_else(|| errors::Error::PpStreamError(format!("can not resolve {}", addr)))?;
+        log::info!("mosh-server listening on {}", remote);
+        Ok(Self {
+            connect: Mutex::new(Some((remote, key))),
+            prediction: cfg.prediction,
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl PpSession for Session {
+    async fn open_tunnel(
+        &self,
+        host: &String,
+        port: u16,
+    ) -> Result<Box<dyn PpStream>, errors::Error> {
+        Err(errors::Error::Unsupported(format!("mosh session can not open tunnel to {}:{}", host, port)))
+    }
+
+    async fn open_pty(&self) -> Result<Box<dyn PpPty>, errors::Error> {
+        let (remote, key) = match self.connect.lock().await.take() {
+            Some(c) => c,
+            None => return Err(errors::Error::PpStreamError("mosh session already in use".to_string())),
+        };
+        let conn = transport::Connection::new(remote, &key).await?;
+        let (local, user) = tokio::io::duplex(65536);
+        let (resize, resize_rx) = mpsc::channel(8);
+        let predictor = prediction::Predictor::new(self.prediction);
+        tokio::spawn(async move {
+            if let Err(e) = pump(conn, user, resize_rx, predictor).await {
+                log::error!("mosh connection closed with error: {:?}", e);
+            }
+        });
+        Ok(Box::new(MoshPty { io: local, resize }))
+    }
+}
+
+// State synchronization: our keystrokes and resizes form numbered user states,
+// the server sends screen diffs between its numbered host states.
+struct Sync {
+    user_states: Vec<(u64, UserEvent)>,
+    user_num: u64,
+    server_acked: u64,
+    host_num: u64,
+    echo_ack: u64,
+    last_send: Instant,
+    ack_due: Option<Instant>,
+}
+
+impl Sync {
+    fn push(&mut self, event: UserEvent) -> u64 {
+        self.user_num += 1;
+        self.user_states.push((self.user_num, event));
+        self.user_num
+    }
+
+    fn instruction(&self) -> Instruction {
+        Instruction {
+            protocol_version: proto::PROTOCOL_VERSION,
+            old_num: self.server_acked,
+            new_num: self.user_num,
+            ack_num: self.host_num,
+            throwaway_num: self.server_acked,
+            diff: proto::encode_user_message(self.user_states.iter().map(|(_, e)| e)),
+        }
+    }
+}
+
+async fn pump(mut conn: transport::Connection, mut user: DuplexStream, mut resize: mpsc::Receiver<(u16, u16)>, mut predictor: prediction::Predictor) -> Result<(), errors::Error> {
+    let mut sync = Sync {
+        user_states: Vec::new(),
+        user_num: 0,
+        server_acked: 0,
+        host_num: 0,
+        echo_ack: 0,
+        last_send: Instant::now(),
+        ack_due: None,
+    };
+    sync.push(UserEvent::Resize(80, 20));
+    conn.send(&sync.instruction()).await?;
+    let mut tick = tokio::time::interval(Duration::from_millis(50));
+    let mut user_buf = [0u8; 1500];
+    loop {
+        let mut send = false;
+        tokio::select! {
+            inst = conn.recv() => {
+                let inst = match inst {
+                    Ok(Some(i)) => i,
+                    Ok(None) => continue,
+                    Err(e) => {
+                        log::warn!("mosh: receive failed: {:?}", e);
+                        continue;
+                    }
+                };
+                if inst.protocol_version != proto::PROTOCOL_VERSION {
+                    return Err(errors::Error::PpStreamError(format!("mosh: unsupported protocol version {}", inst.protocol_version)));
+                }
+                if inst.ack_num > sync.server_acked && inst.ack_num <= sync.user_num {
+                    sync.server_acked = inst.ack_num;
+                    let acked = sync.server_acked;
+                    sync.user_states.retain(|(n, _)| *n > acked);
+                }
+                if inst.new_num == SHUTDOWN_NUM {
+                    sync.host_num = SHUTDOWN_NUM;
+                    conn.send(&sync.instruction()).await?;
+                    return Ok(());
+                }
+                // diffs only apply on top of the state we are showing, the server resends otherwise
+                if inst.old_num == sync.host_num && inst.new_num > sync.host_num {
+                    let mut screen = predictor.undo();
+                    for e in proto::decode_host_message(&inst.diff)? {
+                        match e {
+                            HostEvent::Bytes(b) => screen.extend_from_slice(&b),
+                            HostEvent::EchoAck(n) => sync.echo_ack = sync.echo_ack.max(n),
+                            HostEvent::Resize => {}
+                        }
+                    }
+                    screen.extend_from_slice(&predictor.redo(sync.echo_ack));
+                    user.write_all(&screen).await?;
+                    sync.host_num = inst.new_num;
+                }
+                if sync.ack_due.is_none() {
+                    sync.ack_due = Some(Instant::now() + ACK_DELAY);
+                }
+            }
+            n = user.read(&mut user_buf) => {
+                let n = n?;
+                if n == 0 {
+                    sync.user_num = SHUTDOWN_NUM;
+                    sync.user_states.clear();
+                    return conn.send(&sync.instruction()).await;
+                }
+                let keys = user_buf[..n].to_vec();
+                let num = sync.push(UserEvent::Keys(keys.clone()));
+                let echo = predictor.keystroke(num, &keys, conn.srtt);
+                if !echo.is_empty() {
+                    user.write_all(&echo).await?;
+                }
+                send = true;
+            }
+            Some((w, h)) = resize.recv() => {
+                sync.push(UserEvent::Resize(w as i32, h as i32));
+                send = true;
+            }
+            _ = tick.tick() => {
+                if conn.last_heard.elapsed() > ROAMING_TIMEOUT {
+                    conn.rebind().await?;
+                    send = true;
+                }
+                let now = Instant::now();
+                if sync.ack_due.map(|t| t <= now).unwrap_or(false) || sync.last_send + HEARTBEAT <= now {
+                    send = true;
+                }
+                if !sync.user_states.is_empty() && sync.last_send + conn.srtt.unwrap_or(HEARTBEAT).max(ACK_DELAY) * 2 <= now {
+                    send = true;
+                }
+            }
+        }
+        if send {
+            conn.send(&sync.instruction()).await?;
+            sync.last_send = Instant::now();
+            sync.ack_due = None;
+        }
+    }
+}
+
+pub struct MoshPty {
+    io: DuplexStream,
+    resize: mpsc::Sender<(u16, u16)>,
+}
+
+#[async_trait::async_trait]
+impl PpPty for MoshPty {
+    async fn resize(&mut self, width: usize, height: usize) -> Result<(), errors::Error> {
+        self.resize.send((width as u16, height as u16)).await
+            .map_err(|_| errors::Error::WritePtyError("mosh connection closed".to_string()))
+    }
+}
+
+impl tokio::io::AsyncRead for MoshPty {
+    fn poll_read(
+        self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+        buf: &mut tokio::io::ReadBuf<'_>,
+    ) -> std::task::Poll<std::io::Result<()>> {
+        std::pin::Pin::new(&mut self.get_mut().io).poll_read(cx, buf)
+    }
+}
+
+impl tokio::io::AsyncWrite for MoshPty {
+    fn poll_write(
+        self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+        buf: &[u8],
+    ) -> std::task::Poll<Result<usize, std::io::Error>> {
+        std::pin::Pin::new(&mut self.get_mut().io).poll_write(cx, buf)
+    }
+
+    fn poll_flush(self: std::pin::Pin<&mut Self>, cx: &mut std::task::Context<'_>) -> std::task::Poll<Result<(), std::io::Error>> {
+        std::pin::Pin::new(&mut self.get_mut().io).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: std::pin::Pin<&mut Self>, cx: &mut std::task::Context<'_>) -> std::task::Poll<Result<(), std::io::Error>> {
+        std::pin::Pin::new(&mut self.get_mut().io).poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Read, Write};
+
+    use tokio::net::UdpSocket;
+
+    use super::{crypto::{Crypto, DIRECTION_TO_CLIENT}, *};
+
+    const KEY: &str = "AAECAwQFBgcICQoLDA0ODw";
+
+    fn put_varint(buf: &mut Vec<u8>, mut v: u64) {
+        while v >= 0x80 {
+            buf.push((v as u8) | 0x80);
+            v >>= 7;
+        }
+        buf.push(v as u8);
+    }
+
+    fn put_bytes(buf: &mut Vec<u8>, field: u64, data: &[u8]) {
+        put_varint(buf, (field << 3) | 2);
+        put_varint(buf, data.len() as u64);
+        buf.extend_from_slice(data);
+    }
+
+    // a host message with one screen update and the size the server took over
+    fn host_diff(bytes: &[u8], width: u64, height: u64) -> Vec<u8> {
+        let mut hoststring = Vec::new();
+        put_bytes(&mut hoststring, 4, bytes);
+        let mut resize = Vec::new();
+        put_varint(&mut resize, 5 << 3);
+        put_varint(&mut resize, width);
+        put_varint(&mut resize, 6 << 3);
+        put_varint(&mut resize, height);
+        let mut diff = Vec::new();
+        for (field, ext) in [(3, resize), (2, hoststring)] {
+            let mut inst = Vec::new();
+            put_bytes(&mut inst, field, &ext);
+            put_bytes(&mut diff, 1, &inst);
+        }
+        diff
+    }
+
+    // just enough of mosh-server to talk to one client over loopback
+    struct FakeServer {
+        socket: UdpSocket,
+        crypto: Crypto,
+        seq: u64,
+    }
+
+    impl FakeServer {
+        async fn recv(&mut self) -> proto::Instruction {
+            let mut buf = [0u8; 65536];
+            let (n, peer) = tokio::time::timeout(Duration::from_secs(5), self.socket.recv_from(&mut buf)).await.unwrap().unwrap();
+            self.socket.connect(peer).await.unwrap();
+            let (nonce, plain) = self.crypto.decrypt(&buf[..n]).unwrap();
+            assert_eq!(nonce & DIRECTION_TO_CLIENT, 0);
+            assert_eq!(u16::from_be_bytes([plain[12], plain[13]]), 0x8000);
+            let mut data = Vec::new();
+            flate2::read::ZlibDecoder::new(&plain[14..]).read_to_end(&mut data).unwrap();
+            proto::Instruction::decode(&data).unwrap()
+        }
+
+        // skips the bare acks and heartbeats the client sends in between
+        async fn recv_state(&mut self, num: u64) -> proto::Instruction {
+            loop {
+                let inst = self.recv().await;
+                if inst.new_num == num {
+                    return inst;
+                }
+            }
+        }
+
+        async fn send(&mut self, inst: &proto::Instruction) {
+            let mut z = flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+            z.write_all(&inst.encode()).unwrap();
+            let mut plain = vec![0xff, 0xff, 0xff, 0xff];
+            plain.extend_from_slice(&self.seq.to_be_bytes());
+            plain.extend_from_slice(&0x8000u16.to_be_bytes());
+            plain.extend_from_slice(&z.finish().unwrap());
+            let packet = self.crypto.encrypt(DIRECTION_TO_CLIENT | self.seq, &plain).unwrap();
+            self.seq += 1;
+            self.socket.send(&packet).await.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn loopback() {
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let addr = socket.local_addr().unwrap();
+        let mut server = FakeServer { socket, crypto: Crypto::new(KEY).unwrap(), seq: 0 };
+        let session = Session {
+            connect: Mutex::new(Some((addr, KEY.to_string()))),
+            prediction: Prediction::Never,
+        };
+        let mut pty = session.open_pty().await.unwrap();
+
+        let inst = server.recv_state(1).await;
+        assert_eq!(inst.protocol_version, proto::PROTOCOL_VERSION);
+        assert_eq!(inst.diff, proto::encode_user_message([UserEvent::Resize(80, 20)].iter()));
+
+        server.send(&proto::Instruction {
+            protocol_version: proto::PROTOCOL_VERSION,
+            old_num: 0,
+            new_num: 1,
+            ack_num: 1,
+            throwaway_num: 0,
+            diff: host_diff(b"$ ", 80, 20),
+        }).await;
+        let mut screen = [0u8; 2];
+        tokio::time::timeout(Duration::from_secs(5), pty.read_exact(&mut screen)).await.unwrap().unwrap();
+        assert_eq!(&screen, b"$ ");
+
+        // acked states are not sent again
+        pty.write_all(b"ls").await.unwrap();
+        let inst = server.recv_state(2).await;
+        assert_eq!((inst.old_num, inst.ack_num), (1, 1));
+        assert_eq!(inst.diff, proto::encode_user_message([UserEvent::Keys(b"ls".to_vec())].iter()));
+
+        pty.resize(100, 30).await.unwrap();
+        let inst = server.recv_state(3).await;
+        assert_eq!(inst.diff, proto::encode_user_message([UserEvent::Keys(b"ls".to_vec()), UserEvent::Resize(100, 30)].iter()));
+
+        // a diff against a state we never saw is ignored until the server resends
+        server.send(&proto::Instruction {
+            protocol_version: proto::PROTOCOL_VERSION,
+            old_num: 5,
+            new_num: 6,
+            ack_num: 3,
+            throwaway_num: 0,
+            diff: host_diff(b"stale", 100, 30),
+        }).await;
+        server.send(&proto::Instruction {
+            protocol_version: proto::PROTOCOL_VERSION,
+            old_num: 1,
+            new_num: 2,
+            ack_num: 3,
+            throwaway_num: 1,
+            diff: host_diff(b"ls\r\n", 100, 30),
+        }).await;
+        let mut screen = [0u8; 4];
+        tokio::time::timeout(Duration::from_secs(5), pty.read_exact(&mut screen)).await.unwrap().unwrap();
+        assert_eq!(&screen, b"ls\r\n");
+
+        drop(pty);
+        let inst = server.recv_state(SHUTDOWN_NUM).await;
+        assert!(inst.diff.is_empty());
+    }
+}
@@ -0,0 +1,79 @@
+use std::time::Duration;
+
+
+
+
+const SAVE_CURSOR: &[u8] = b"\x1b7";
+const RESTORE_CURSOR: &[u8] = b"\x1b8";
+const UNDERLINE: &[u8] = b"\x1b[4m";
+const NO_UNDERLINE: &[u8] = b"\x1b[24m";
+const MAX_PENDING: usize = 64;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Prediction {
+    Never,
+    Adaptive,
+    Always,
+}
+
+// Predicted keystrokes are drawn underlined on top of the last server state.
+// The cursor is saved before the first prediction, so the overlay can be wiped
+// before a server diff is applied and redrawn for whatever is still unconfirmed.
+pub struct Predictor {
+    mode: Prediction,
+    pending: Vec<(u64, u8)>,
+    blocked: bool,
+}
+
+impl Predictor {
+    pub fn new(mode: Prediction) -> Self {
+        Self { mode, pending: Vec::new(), blocked: false }
+    }
+
+    pub fn keystroke(&mut self, state: u64, keys: &[u8], srtt: Option<Duration>) -> Vec<u8> {
+        let enabled = match self.mode {
+            Prediction::Never => false,
+            Prediction::Always => true,
+            Prediction::Adaptive => srtt.map(|d| d > Duration::from_millis(30)).unwrap_or(false),
+        };
+        if !keys.iter().all(|b| (0x20..0x7f).contains(b)) {
+            // anything else may move the cursor, wait for the server to catch up
+            self.blocked = true;
+            return Vec::new();
+        }
+        if !enabled || self.blocked || self.pending.len() + keys.len() > MAX_PENDING {
+            return Vec::new();
+        }
+        let mut out = Vec::new();
+        if self.pending.is_empty() {
+            out.extend_from_slice(SAVE_CURSOR);
+        }
+        out.extend_from_slice(UNDERLINE);
+        out.extend_from_slice(keys);
+        out.extend_from_slice(NO_UNDERLINE);
+        self.pending.extend(keys.iter().map(|b| (state, *b)));
+        out
+    }
+
+    pub fn undo(&self) -> Vec<u8> {
+        if self.pending.is_empty() {
+            return Vec::new();
+        }
+        let mut out = RESTORE_CURSOR.to_vec();
+        out.extend_from_slice(format!("\x1b[{}X", self.pending.len()).as_bytes());
+        out
+    }
+
+    pub fn redo(&mut self, echo_ack: u64) -> Vec<u8> {
+        self.pending.retain(|(state, _)| *state > echo_ack);
+        if self.pending.is_empty() {
+            self.blocked = false;
+            return Vec::new();
+        }
+        let mut out = SAVE_CURSOR.to_vec();
+        out.extend_from_slice(UNDERLINE);
+        out.extend(self.pending.iter().map(|(_, b)| *b));
+        out.extend_from_slice(NO_UNDERLINE);
+        out
+    }
+}
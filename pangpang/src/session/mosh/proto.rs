@@ -0,0 +1,200 @@
+use crate::errors;
+
+
+
+
+pub const PROTOCOL_VERSION: u32 = 2;
+
+#[derive(Default, Debug)]
+pub struct Instruction {
+    pub protocol_version: u32,
+    pub old_num: u64,
+    pub new_num: u64,
+    pub ack_num: u64,
+    pub throwaway_num: u64,
+    pub diff: Vec<u8>,
+}
+
+pub enum UserEvent {
+    Keys(Vec<u8>),
+    Resize(i32, i32),
+}
+
+pub enum HostEvent {
+    Bytes(Vec<u8>),
+    // the server only reports back the size we sent, the terminal already has it
+    Resize,
+    EchoAck(u64),
+}
+
+impl Instruction {
+    pub fn encode(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        put_uint(&mut buf, 1, self.protocol_version as u64);
+        put_uint(&mut buf, 2, self.old_num);
+        put_uint(&mut buf, 3, self.new_num);
+        put_uint(&mut buf, 4, self.ack_num);
+        put_uint(&mut buf, 5, self.throwaway_num);
+        if !self.diff.is_empty() {
+            put_bytes(&mut buf, 6, &self.diff);
+        }
+        buf
+    }
+
+    pub fn decode(data: &[u8]) -> Result<Self, errors::Error> {
+        let mut inst = Instruction::default();
+        let mut reader = Reader::new(data);
+        while let Some((field, value)) = reader.next()? {
+            match (field, value) {
+                (1, Value::Varint(v)) => inst.protocol_version = v as u32,
+                (2, Value::Varint(v)) => inst.old_num = v,
+                (3, Value::Varint(v)) => inst.new_num = v,
+                (4, Value::Varint(v)) => inst.ack_num = v,
+                (5, Value::Varint(v)) => inst.throwaway_num = v,
+                (6, Value::Bytes(b)) => inst.diff = b.to_vec(),
+                _ => {}
+            }
+        }
+        Ok(inst)
+    }
+}
+
+pub fn encode_user_message<'a>(events: impl Iterator<Item = &'a UserEvent>) -> Vec<u8> {
+    let mut buf = Vec::new();
+    for e in events {
+        let mut inner = Vec::new();
+        match e {
+            UserEvent::Keys(keys) => {
+                let mut keystroke = Vec::new();
+                put_bytes(&mut keystroke, 4, keys);
+                put_bytes(&mut inner, 2, &keystroke);
+            }
+            UserEvent::Resize(width, height) => {
+                let mut resize = Vec::new();
+                put_uint(&mut resize, 5, *width as u64);
+                put_uint(&mut resize, 6, *height as u64);
+                put_bytes(&mut inner, 3, &resize);
+            }
+        }
+        put_bytes(&mut buf, 1, &inner);
+    }
+    buf
+}
+
+pub fn decode_host_message(data: &[u8]) -> Result<Vec<HostEvent>, errors::Error> {
+    let mut events = Vec::new();
+    let mut reader = Reader::new(data);
+    while let Some((field, value)) = reader.next()? {
+        let inst = match (field, value) {
+            (1, Value::Bytes(b)) => b,
+            _ => continue,
+        };
+        let mut reader = Reader::new(inst);
+        while let Some((field, value)) = reader.next()? {
+            let ext = match value {
+                Value::Bytes(b) => b,
+                _ => continue,
+            };
+            let mut reader = Reader::new(ext);
+            match field {
+                2 => while let Some((f, v)) = reader.next()? {
+                    if let (4, Value::Bytes(b)) = (f, v) {
+                        events.push(HostEvent::Bytes(b.to_vec()));
+                    }
+                },
+                3 => events.push(HostEvent::Resize),
+                7 => while let Some((f, v)) = reader.next()? {
+                    if let (8, Value::Varint(n)) = (f, v) {
+                        events.push(HostEvent::EchoAck(n));
+                    }
+                },
+                _ => {}
+            }
+        }
+    }
+    Ok(events)
+}
+
+fn put_varint(buf: &mut Vec<u8>, mut v: u64) {
+    while v >= 0x80 {
+        buf.push((v as u8) | 0x80);
+        v >>= 7;
+    }
+    buf.push(v as u8);
+}
+
+fn put_uint(buf: &mut Vec<u8>, field: u32, v: u64) {
+    put_varint(buf, (field as u64) << 3);
+    put_varint(buf, v);
+}
+
+fn put_bytes(buf: &mut Vec<u8>, field: u32, data: &[u8]) {
+    put_varint(buf, ((field as u64) << 3) | 2);
+    put_varint(buf, data.len() as u64);
+    buf.extend_from_slice(data);
+}
+
+enum Value<'a> {
+    Varint(u64),
+    Bytes(&'a [u8]),
+    Fixed,
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data, pos: 0 }
+    }
+
+    fn varint(&mut self) -> Result<u64, errors::Error> {
+        let mut v = 0u64;
+        for shift in (0..64).step_by(7) {
+            let b = *self.data.get(self.pos).ok_or_else(truncated)?;
+            self.pos += 1;
+            v |= ((b & 0x7f) as u64) << shift;
+            if b & 0x80 == 0 {
+                return Ok(v);
+            }
+        }
+        Err(errors::Error::PpStreamError("mosh: varint overflow".to_string()))
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], errors::Error> {
+        let end = self.pos.checked_add(len).filter(|e| *e <= self.data.len()).ok_or_else(truncated)?;
+        let b = &self.data[self.pos..end];
+        self.pos = end;
+        Ok(b)
+    }
+
+    fn next(&mut self) -> Result<Option<(u32, Value<'a>)>, errors::Error> {
+        if self.pos >= self.data.len() {
+            return Ok(None);
+        }
+        let key = self.varint()?;
+        let value = match key & 7 {
+            0 => Value::Varint(self.varint()?),
+            1 => {
+                self.take(8)?;
+                Value::Fixed
+            }
+            2 => {
+                let len = self.varint()? as usize;
+                Value::Bytes(self.take(len)?)
+            }
+            5 => {
+                self.take(4)?;
+                Value::Fixed
+            }
+            t => return Err(errors::Error::PpStreamError(format!("mosh: unsupported wire type {}", t))),
+        };
+        Ok(Some(((key >> 3) as u32, value)))
+    }
+}
+
+fn truncated() -> errors::Error {
+    errors::Error::PpStreamError("mosh: truncated message".to_string())
+}
@@ -0,0 +1,160 @@
+use std::{collections::HashMap, io::{Read, Write}, net::SocketAddr, time::{Duration, Instant}};
+
+use tokio::net::UdpSocket;
+
+use crate::errors;
+
+use super::{crypto::{Crypto, DIRECTION_TO_CLIENT}, proto::Instruction};
+
+
+
+
+const FRAGMENT_SIZE: usize = 1200;
+const FINAL_FRAGMENT: u16 = 0x8000;
+const NO_TIMESTAMP: u16 = 0xffff;
+
+// the parts received so far and whether the final one arrived
+type Fragments = (Vec<Option<Vec<u8>>>, bool);
+
+pub struct Connection {
+    socket: UdpSocket,
+    remote: SocketAddr,
+    crypto: Crypto,
+    next_seq: u64,
+    next_fragment_id: u64,
+    // per instruction id
+    fragments: HashMap<u64, Fragments>,
+    saved_timestamp: Option<(u16, Instant)>,
+    pub srtt: Option<Duration>,
+    pub last_heard: Instant,
+}
+
+impl Connection {
+    pub async fn new(remote: SocketAddr, key: &str) -> Result<Self, errors::Error> {
+        Ok(Self {
+            socket: bind(&remote).await?,
+            remote,
+            crypto: Crypto::new(key)?,
+            next_seq: 0,
+            next_fragment_id: 0,
+            fragments: HashMap::new(),
+            saved_timestamp: None,
+            srtt: None,
+            last_heard: Instant::now(),
+        })
+    }
+
+    // a fresh source port lets the server pick up our new address after a network change
+    pub async fn rebind(&mut self) -> Result<(), errors::Error> {
+        self.socket = bind(&self.remote).await?;
+        self.last_heard = Instant::now();
+        log::info!("mosh: rebound udp socket to {:?}", self.socket.local_addr());
+        Ok(())
+    }
+
+    pub async fn send(&mut self, inst: &Instruction) -> Result<(), errors::Error> {
+        let mut z = flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+        z.write_all(&inst.encode())?;
+        let payload = z.finish()?;
+        let id = self.next_fragment_id;
+        self.next_fragment_id += 1;
+        let chunks: Vec<&[u8]> = if payload.is_empty() {
+            vec![&payload[..]]
+        } else {
+            payload.chunks(FRAGMENT_SIZE).collect()
+        };
+        for (num, chunk) in chunks.iter().enumerate() {
+            let mut fragment_num = num as u16;
+            if num + 1 == chunks.len() {
+                fragment_num |= FINAL_FRAGMENT;
+            }
+            let reply = match self.saved_timestamp.take() {
+                Some((ts, at)) if at.elapsed() < Duration::from_secs(1) => ts.wrapping_add(at.elapsed().as_millis() as u16),
+                _ => NO_TIMESTAMP,
+            };
+            let mut plain = Vec::with_capacity(chunk.len() + 14);
+            plain.extend_from_slice(&timestamp().to_be_bytes());
+            plain.extend_from_slice(&reply.to_be_bytes());
+            plain.extend_from_slice(&id.to_be_bytes());
+            plain.extend_from_slice(&fragment_num.to_be_bytes());
+            plain.extend_from_slice(chunk);
+            let packet = self.crypto.encrypt(self.next_seq, &plain)?;
+            self.next_seq += 1;
+            if let Err(e) = self.socket.send(&packet).await {
+                log::warn!("mosh: send failed: {:?}", e);
+            }
+        }
+        Ok(())
+    }
+
+    // returns a complete instruction once all its fragments arrived
+    pub async fn recv(&mut self) -> Result<Option<Instruction>, errors::Error> {
+        let mut buf = [0u8; 65536];
+        let n = self.socket.recv(&mut buf).await?;
+        let (nonce, plain) = match self.crypto.decrypt(&buf[..n]) {
+            Ok(p) => p,
+            Err(e) => {
+                log::warn!("mosh: dropped packet: {:?}", e);
+                return Ok(None);
+            }
+        };
+        if nonce & DIRECTION_TO_CLIENT == 0 || plain.len() < 14 {
+            return Ok(None);
+        }
+        self.last_heard = Instant::now();
+        let ts = u16::from_be_bytes([plain[0], plain[1]]);
+        let reply = u16::from_be_bytes([plain[2], plain[3]]);
+        if ts != NO_TIMESTAMP {
+            self.saved_timestamp = Some((ts, Instant::now()));
+        }
+        if reply != NO_TIMESTAMP {
+            let rtt = Duration::from_millis(timestamp().wrapping_sub(reply) as u64);
+            self.srtt = Some(match self.srtt {
+                Some(srtt) => srtt.mul_f64(0.875) + rtt.mul_f64(0.125),
+                None => rtt,
+            });
+        }
+        let mut id = [0u8; 8];
+        id.copy_from_slice(&plain[4..12]);
+        let id = u64::from_be_bytes(id);
+        let fragment_num = u16::from_be_bytes([plain[12], plain[13]]);
+        let num = (fragment_num & !FINAL_FRAGMENT) as usize;
+
+        let (parts, complete) = self.fragments.entry(id).or_default();
+        if parts.len() <= num {
+            parts.resize(num + 1, None);
+        }
+        parts[num] = Some(plain[14..].to_vec());
+        if fragment_num & FINAL_FRAGMENT != 0 {
+            parts.truncate(num + 1);
+            *complete = true;
+        }
+        if !*complete || parts.iter().any(|p| p.is_none()) {
+            return Ok(None);
+        }
+        let (parts, _) = self.fragments.remove(&id).unwrap_or_default();
+        self.fragments.retain(|k, _| *k > id);
+        let payload: Vec<u8> = parts.into_iter().flatten().flatten().collect();
+        let mut data = Vec::new();
+        flate2::read::ZlibDecoder::new(payload.as_slice()).read_to_end(&mut data)?;
+        Ok(Some(Instruction::decode(&data)?))
+    }
+}
+
+async fn bind(remote: &SocketAddr) -> Result<UdpSocket, errors::Error> {
+    let local: SocketAddr = if remote.is_ipv4() {
+        "0.0.0.0:0".parse().unwrap()
+    } else {
+        "[::]:0".parse().unwrap()
+    };
+    let socket = UdpSocket::bind(local).await?;
+    socket.connect(remote).await?;
+    Ok(socket)
+}
+
+fn timestamp() -> u16 {
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default();
+    (now.as_millis() & 0xffff) as u16
+}
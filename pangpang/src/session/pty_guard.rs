@@ -1,8 +1,8 @@
 use tokio::io::{AsyncRead, AsyncWrite};
 
-use crate::errors;
+use crate::{errors, profile::Capacity};
 
-use super::{PpSessionGuard, PpPty};
+use super::{PpSessionGuard, PpPty, event::SessionEventKind, registry::{ChannelKind, ChannelHandle}};
 
 
 
@@ -14,19 +14,53 @@ use super::{PpSessionGuard, PpPty};
 pub struct PpPtyGuard {
     inner: Box<dyn PpPty>,
     _session: PpSessionGuard,
+    capacity: Capacity,
+    channel: ChannelHandle,
+    closed: bool,
 }
 
 impl PpPtyGuard {
-    pub fn new(inner: Box<dyn PpPty>, s: PpSessionGuard) -> Self {
-        PpPtyGuard{ inner: inner, _session: s }
+    pub fn new(inner: Box<dyn PpPty>, s: PpSessionGuard, capacity: Capacity, kind: ChannelKind) -> Self {
+        let channel = s.register(kind);
+        PpPtyGuard{ inner, _session: s, capacity, channel, closed: false }
+    }
+
+    pub fn session_serial(&self) -> u64 {
+        self._session.serial()
+    }
+
+    // a forced close from the connections view ends the stream like an eof
+    fn closed(&mut self, cx: &mut std::task::Context<'_>) -> bool {
+        if !self.closed && std::future::Future::poll(std::pin::Pin::new(&mut self.channel.close), cx).is_ready() {
+            self.closed = true;
+        }
+        self.closed
+    }
+}
+
+impl Drop for PpPtyGuard {
+    fn drop(&mut self) {
+        self._session.unregister(self.channel.serial);
+        self._session.emit(SessionEventKind::PtyClosed);
     }
 }
 
 #[async_trait::async_trait]
 impl PpPty for PpPtyGuard {
     async fn resize(&mut self, width: usize, height: usize) -> Result<(), errors::Error> {
+        if !self.capacity.contains(Capacity::PTY_RESIZE) {
+            return Ok(());
+        }
         self.inner.resize(width, height).await
     }
+
+    async fn exit_status(&mut self) -> Option<i32> {
+        self.inner.exit_status().await
+    }
+
+    async fn send_break(&mut self) -> Result<(), errors::Error> {
+        self.inner.send_break().await
+    }
 }
 impl AsyncRead for PpPtyGuard {
     fn poll_read(
@@ -34,7 +68,18 @@ impl AsyncRead for PpPtyGuard {
         cx: &mut std::task::Context<'_>,
         buf: &mut tokio::io::ReadBuf<'_>,
     ) -> std::task::Poll<std::io::Result<()>> {
-        std::pin::Pin::new(self.get_mut().inner.as_mut()).poll_read(cx, buf)
+        let this = self.get_mut();
+        if this.closed(cx) {
+            return std::task::Poll::Ready(Ok(()));
+        }
+        let before = buf.filled().len();
+        let r = std::pin::Pin::new(this.inner.as_mut()).poll_read(cx, buf);
+        if let std::task::Poll::Ready(Ok(())) = r {
+            let n = buf.filled().len() - before;
+            this.channel.stats.add_in(n);
+            this.channel.session_stats.add_in(n);
+        }
+        r
     }
 }
 impl AsyncWrite for PpPtyGuard {
@@ -43,7 +88,16 @@ impl AsyncWrite for PpPtyGuard {
         cx: &mut std::task::Context<'_>,
         buf: &[u8],
     ) -> std::task::Poll<Result<usize, std::io::Error>> {
-        std::pin::Pin::new(self.get_mut().inner.as_mut()).poll_write(cx, buf)
+        let this = self.get_mut();
+        if this.closed(cx) {
+            return std::task::Poll::Ready(Err(std::io::ErrorKind::BrokenPipe.into()));
+        }
+        let r = std::pin::Pin::new(this.inner.as_mut()).poll_write(cx, buf);
+        if let std::task::Poll::Ready(Ok(n)) = r {
+            this.channel.stats.add_out(n);
+            this.channel.session_stats.add_out(n);
+        }
+        r
     }
 
     fn poll_flush(self: std::pin::Pin<&mut Self>, cx: &mut std::task::Context<'_>) -> std::task::Poll<Result<(), std::io::Error>> {
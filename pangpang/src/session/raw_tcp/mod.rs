@@ -0,0 +1,222 @@
+use tokio::{io::{AsyncReadExt, AsyncWriteExt, DuplexStream}, sync::Mutex};
+
+use crate::errors;
+
+use super::{PpStream, PpSession, PpPty};
+
+
+
+
+#[derive(Clone)]
+pub struct RawTcpProfile {
+    pub local_echo: bool,
+    pub crlf: bool,
+}
+
+impl Default for RawTcpProfile {
+    fn default() -> Self {
+        Self { local_echo: true, crlf: true }
+    }
+}
+
+pub struct Session {
+    stream: Mutex<Option<Box<dyn PpStream>>>,
+    cfg: RawTcpProfile,
+}
+
+impl Session {
+    pub async fn new(addr: &str, port: u16, cfg: RawTcpProfile) -> Result<Self, errors::Error> {
+        let stream = tokio::net::TcpStream::connect((addr, port)).await?;
+        stream.set_nodelay(true)?;
+        Ok(Self::new_with_stream(Box::new(stream), cfg))
+    }
+
+    pub fn new_with_stream(stream: Box<dyn PpStream>, cfg: RawTcpProfile) -> Self {
+        Self {
+            stream: Mutex::new(Some(stream)),
+            cfg,
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl PpSession for Session {
+    async fn open_tunnel(
+        &self,
+        host: &String,
+        port: u16,
+    ) -> Result<Box<dyn PpStream>, errors::Error> {
+        Err(errors::Error::Unsupported(format!("raw tcp session can not open tunnel to {}:{}", host, port)))
+    }
+
+    async fn open_pty(&self) -> Result<Box<dyn PpPty>, errors::Error> {
+        let stream = match self.stream.lock().await.take() {
+            Some(s) => s,
+            None => return Err(errors::Error::PpStreamError("tcp connection already in use".to_string())),
+        };
+        let (local, remote) = tokio::io::duplex(4096);
+        let cfg = self.cfg.clone();
+        tokio::spawn(async move {
+            if let Err(e) = pump(stream, remote, cfg).await {
+                log::error!("tcp connection closed with error: {:?}", e);
+            }
+        });
+        Ok(Box::new(RawTcpPty { io: local }))
+    }
+}
+
+async fn pump(mut stream: Box<dyn PpStream>, mut user: DuplexStream, cfg: RawTcpProfile) -> Result<(), std::io::Error> {
+    let mut net_buf = [0u8; 4096];
+    let mut user_buf = [0u8; 1500];
+    let mut last_cr = false;
+    let mut escape = Escape::None;
+    loop {
+        tokio::select! {
+            n = stream.read(&mut net_buf) => {
+                let n = n?;
+                if n == 0 {
+                    return Ok(());
+                }
+                let mut data = Vec::with_capacity(n);
+                for &b in &net_buf[..n] {
+                    // the terminal needs CR to return to the first column
+                    if cfg.crlf && b == b'\n' && !last_cr {
+                        data.push(b'\r');
+                    }
+                    data.push(b);
+                    last_cr = b == b'\r';
+                }
+                user.write_all(&data).await?;
+            }
+            n = user.read(&mut user_buf) => {
+                let n = n?;
+                if n == 0 {
+                    return stream.shutdown().await;
+                }
+                let mut out = Vec::with_capacity(n);
+                let mut echo = Vec::new();
+                for &b in &user_buf[..n] {
+                    match b {
+                        b'\r' if cfg.crlf => out.extend_from_slice(b"\r\n"),
+                        _ => out.push(b),
+                    }
+                    escape.echo(b, &mut echo);
+                }
+                stream.write_all(&out).await?;
+                if cfg.local_echo {
+                    user.write_all(&echo).await?;
+                }
+            }
+        }
+    }
+}
+
+// key sequences from the terminal are sent as typed but never echoed back
+#[derive(Clone, Copy, PartialEq)]
+enum Escape {
+    None,
+    Esc,
+    Csi,
+    Ss3,
+}
+
+impl Escape {
+    fn echo(&mut self, b: u8, echo: &mut Vec<u8>) {
+        *self = match (*self, b) {
+            (_, 0x1b) => Escape::Esc,
+            (Escape::Esc, b'[') => Escape::Csi,
+            (Escape::Esc, b'O') => Escape::Ss3,
+            (Escape::Csi, 0x20..=0x3f) => Escape::Csi,
+            (Escape::Esc, _) | (Escape::Csi, _) | (Escape::Ss3, _) => Escape::None,
+            (Escape::None, _) => {
+                match b {
+                    b'\r' | b'\n' => echo.extend_from_slice(b"\r\n"),
+                    0x7f | 0x08 => echo.extend_from_slice(b"\x08 \x08"),
+                    0x20..=0x7e | 0x80..=0xff => echo.push(b),
+                    _ => {}
+                }
+                Escape::None
+            }
+        };
+    }
+}
+
+pub struct RawTcpPty {
+    io: DuplexStream,
+}
+
+#[async_trait::async_trait]
+impl PpPty for RawTcpPty {
+    async fn resize(&mut self, _width: usize, _height: usize) -> Result<(), errors::Error> {
+        Err(errors::Error::Unsupported("raw tcp connection can not be resized".to_string()))
+    }
+}
+
+impl tokio::io::AsyncRead for RawTcpPty {
+    fn poll_read(
+        self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+        buf: &mut tokio::io::ReadBuf<'_>,
+    ) -> std::task::Poll<std::io::Result<()>> {
+        std::pin::Pin::new(&mut self.get_mut().io).poll_read(cx, buf)
+    }
+}
+
+impl tokio::io::AsyncWrite for RawTcpPty {
+    fn poll_write(
+        self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+        buf: &[u8],
+    ) -> std::task::Poll<Result<usize, std::io::Error>> {
+        std::pin::Pin::new(&mut self.get_mut().io).poll_write(cx, buf)
+    }
+
+    fn poll_flush(self: std::pin::Pin<&mut Self>, cx: &mut std::task::Context<'_>) -> std::task::Poll<Result<(), std::io::Error>> {
+        std::pin::Pin::new(&mut self.get_mut().io).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: std::pin::Pin<&mut Self>, cx: &mut std::task::Context<'_>) -> std::task::Poll<Result<(), std::io::Error>> {
+        std::pin::Pin::new(&mut self.get_mut().io).poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use tokio::{io::{AsyncReadExt, AsyncWriteExt}, net::TcpListener};
+
+    use super::*;
+
+    #[test]
+    fn echo_skips_key_sequences() {
+        let mut escape = Escape::None;
+        let mut echo = Vec::new();
+        for &b in b"ls\x1b[A\x1bOP\x1b[1;5C\x03\x7f\xc3\xa9\r" {
+            escape.echo(b, &mut echo);
+        }
+        assert_eq!(echo, b"ls\x08 \x08\xc3\xa9\r\n");
+    }
+
+    #[tokio::test]
+    async fn loopback() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let session = Session::new("127.0.0.1", port, RawTcpProfile::default()).await.unwrap();
+        let (mut server, _) = listener.accept().await.unwrap();
+        let mut pty = session.open_pty().await.unwrap();
+
+        pty.write_all(b"hi\x1b[D\r").await.unwrap();
+        let mut buf = [0u8; 7];
+        tokio::time::timeout(Duration::from_secs(5), server.read_exact(&mut buf)).await.unwrap().unwrap();
+        assert_eq!(&buf, b"hi\x1b[D\r\n");
+        let mut echo = [0u8; 4];
+        tokio::time::timeout(Duration::from_secs(5), pty.read_exact(&mut echo)).await.unwrap().unwrap();
+        assert_eq!(&echo, b"hi\r\n");
+
+        server.write_all(b"a\nb\r\n").await.unwrap();
+        let mut buf = [0u8; 6];
+        tokio::time::timeout(Duration::from_secs(5), pty.read_exact(&mut buf)).await.unwrap().unwrap();
+        assert_eq!(&buf, b"a\r\nb\r\n");
+    }
+}
@@ -0,0 +1,216 @@
+use std::{collections::HashMap, sync::{Arc, atomic::{AtomicU64, Ordering}}, time::{Duration, Instant, SystemTime}};
+
+use tokio::sync::oneshot;
+
+
+
+
+#[derive(Default)]
+pub struct Stats {
+    pub bytes_in: AtomicU64,
+    pub bytes_out: AtomicU64,
+    // milliseconds since the unix epoch
+    pub last_activity: AtomicU64,
+}
+
+impl Stats {
+    fn new() -> Self {
+        let stats = Stats::default();
+        stats.touch();
+        stats
+    }
+
+    pub fn add_in(&self, n: usize) {
+        self.bytes_in.fetch_add(n as u64, Ordering::Relaxed);
+        self.touch();
+    }
+
+    pub fn add_out(&self, n: usize) {
+        self.bytes_out.fetch_add(n as u64, Ordering::Relaxed);
+        self.touch();
+    }
+
+    fn touch(&self) {
+        self.last_activity.store(now_millis(), Ordering::Relaxed);
+    }
+
+    pub fn idle(&self) -> Duration {
+        Duration::from_millis(now_millis().saturating_sub(self.last_activity.load(Ordering::Relaxed)))
+    }
+}
+
+fn now_millis() -> u64 {
+    SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap_or_default().as_millis() as u64
+}
+
+#[derive(Clone, Debug)]
+pub enum ChannelKind {
+    Pty,
+    Exec(String),
+    Tunnel(String, u16),
+}
+
+#[derive(Clone, Debug)]
+pub struct ChannelSnapshot {
+    pub serial: u64,
+    pub kind: ChannelKind,
+    pub opened_at: SystemTime,
+    pub uptime: Duration,
+    pub bytes_in: u64,
+    pub bytes_out: u64,
+}
+
+#[derive(Clone, Debug)]
+pub struct SessionSnapshot {
+    pub serial: u64,
+    pub profile: String,
+    pub cached: bool,
+    pub refs: usize,
+    pub connected_at: SystemTime,
+    pub uptime: Duration,
+    pub bytes_in: u64,
+    pub bytes_out: u64,
+    pub channels: Vec<ChannelSnapshot>,
+}
+
+struct ChannelRecord {
+    kind: ChannelKind,
+    opened_at: SystemTime,
+    started: Instant,
+    stats: Arc<Stats>,
+    close: Option<oneshot::Sender<()>>,
+}
+
+struct SessionRecord {
+    profile: String,
+    cached: bool,
+    connected_at: SystemTime,
+    started: Instant,
+    stats: Arc<Stats>,
+    channels: HashMap<u64, ChannelRecord>,
+}
+
+// Handed to pty and tunnel guards so they can count traffic and notice a forced close.
+pub struct ChannelHandle {
+    pub serial: u64,
+    pub session_serial: u64,
+    pub stats: Arc<Stats>,
+    pub session_stats: Arc<Stats>,
+    pub close: oneshot::Receiver<()>,
+}
+
+#[derive(Default)]
+pub struct Registry {
+    next: AtomicU64,
+    sessions: std::sync::Mutex<HashMap<u64, SessionRecord>>,
+}
+
+impl Registry {
+    fn serial(&self) -> u64 {
+        self.next.fetch_add(1, Ordering::Relaxed) + 1
+    }
+
+    pub fn add_session(&self, profile: &str, cached: bool) -> u64 {
+        let serial = self.serial();
+        self.sessions.lock().unwrap().insert(serial, SessionRecord {
+            profile: profile.to_string(),
+            cached,
+            connected_at: SystemTime::now(),
+            started: Instant::now(),
+            stats: Arc::new(Stats::new()),
+            channels: HashMap::new(),
+        });
+        serial
+    }
+
+    pub fn remove_session(&self, serial: u64) {
+        self.sessions.lock().unwrap().remove(&serial);
+    }
+
+    pub fn add_channel(&self, session_serial: u64, kind: ChannelKind) -> ChannelHandle {
+        let serial = self.serial();
+        let (tx, rx) = oneshot::channel();
+        let stats = Arc::new(Stats::new());
+        let mut sessions = self.sessions.lock().unwrap();
+        let session_stats = match sessions.get_mut(&session_serial) {
+            Some(s) => {
+                s.channels.insert(serial, ChannelRecord {
+                    kind,
+                    opened_at: SystemTime::now(),
+                    started: Instant::now(),
+                    stats: stats.clone(),
+                    close: Some(tx),
+                });
+                s.stats.clone()
+            }
+            None => Arc::new(Stats::default()),
+        };
+        ChannelHandle { serial, session_serial, stats, session_stats, close: rx }
+    }
+
+    pub fn remove_channel(&self, session_serial: u64, serial: u64) {
+        if let Some(s) = self.sessions.lock().unwrap().get_mut(&session_serial) {
+            s.channels.remove(&serial);
+        }
+    }
+
+    pub fn idle_for(&self, serial: u64) -> Option<Duration> {
+        self.sessions.lock().unwrap().get(&serial).map(|s| s.stats.idle())
+    }
+
+    pub fn close_channel(&self, serial: u64) -> bool {
+        for s in self.sessions.lock().unwrap().values_mut() {
+            if let Some(ch) = s.channels.get_mut(&serial) {
+                if let Some(tx) = ch.close.take() {
+                    let _ = tx.send(());
+                }
+                return true;
+            }
+        }
+        false
+    }
+
+    // closes every channel of the session, returns its profile and whether it is cached
+    pub fn close_session(&self, serial: u64) -> Option<(String, bool)> {
+        let mut sessions = self.sessions.lock().unwrap();
+        let s = sessions.get_mut(&serial)?;
+        for ch in s.channels.values_mut() {
+            if let Some(tx) = ch.close.take() {
+                let _ = tx.send(());
+            }
+        }
+        Some((s.profile.clone(), s.cached))
+    }
+
+    pub fn snapshot(&self, refs: &HashMap<u64, usize>) -> Vec<SessionSnapshot> {
+        let sessions = self.sessions.lock().unwrap();
+        let mut list: Vec<SessionSnapshot> = sessions.iter()
+            .map(|(serial, s)| {
+                let mut channels: Vec<ChannelSnapshot> = s.channels.iter()
+                    .map(|(serial, ch)| ChannelSnapshot {
+                        serial: *serial,
+                        kind: ch.kind.clone(),
+                        opened_at: ch.opened_at,
+                        uptime: ch.started.elapsed(),
+                        bytes_in: ch.stats.bytes_in.load(Ordering::Relaxed),
+                        bytes_out: ch.stats.bytes_out.load(Ordering::Relaxed),
+                    })
+                    .collect();
+                channels.sort_by_key(|c| c.serial);
+                SessionSnapshot {
+                    serial: *serial,
+                    profile: s.profile.clone(),
+                    cached: s.cached,
+                    refs: refs.get(serial).copied().unwrap_or(if s.cached { 0 } else { 1 }),
+                    connected_at: s.connected_at,
+                    uptime: s.started.elapsed(),
+                    bytes_in: s.stats.bytes_in.load(Ordering::Relaxed),
+                    bytes_out: s.stats.bytes_out.load(Ordering::Relaxed),
+                    channels,
+                }
+            })
+            .collect();
+        list.sort_by_key(|s| s.serial);
+        list
+    }
+}
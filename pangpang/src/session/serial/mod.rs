@@ -0,0 +1,228 @@
+use tokio_serial::SerialPort;
+
+use crate::errors;
+
+use super::{PpStream, PpSession, PpPty};
+
+
+
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Parity {
+    None,
+    Odd,
+    Even,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FlowControl {
+    None,
+    Software,
+    Hardware,
+}
+
+#[derive(Clone)]
+pub struct SerialProfile {
+    pub device: String,
+    pub baud_rate: u32,
+    pub data_bits: u8,
+    pub parity: Parity,
+    pub stop_bits: u8,
+    pub flow_control: FlowControl,
+}
+
+impl Default for SerialProfile {
+    fn default() -> Self {
+        Self {
+            device: String::new(),
+            baud_rate: 9600,
+            data_bits: 8,
+            parity: Parity::None,
+            stop_bits: 1,
+            flow_control: FlowControl::None,
+        }
+    }
+}
+
+pub struct Session {
+    cfg: SerialProfile,
+}
+
+impl Session {
+    pub fn new(cfg: SerialProfile) -> Self {
+        Self { cfg }
+    }
+
+    fn open(&self) -> Result<tokio_serial::SerialStream, errors::Error> {
+        let data_bits = match self.cfg.data_bits {
+            5 => tokio_serial::DataBits::Five,
+            6 => tokio_serial::DataBits::Six,
+            7 => tokio_serial::DataBits::Seven,
+            8 => tokio_serial::DataBits::Eight,
+            n => return Err(errors::Error::InvalidProfile(format!("unsupported data bits {}", n))),
+        };
+        let stop_bits = match self.cfg.stop_bits {
+            1 => tokio_serial::StopBits::One,
+            2 => tokio_serial::StopBits::Two,
+            n => return Err(errors::Error::InvalidProfile(format!("unsupported stop bits {}", n))),
+        };
+        let parity = match self.cfg.parity {
+            Parity::None => tokio_serial::Parity::None,
+            Parity::Odd => tokio_serial::Parity::Odd,
+            Parity::Even => tokio_serial::Parity::Even,
+        };
+        let flow_control = match self.cfg.flow_control {
+            FlowControl::None => tokio_serial::FlowControl::None,
+            FlowControl::Software => tokio_serial::FlowControl::Software,
+            FlowControl::Hardware => tokio_serial::FlowControl::Hardware,
+        };
+        let builder = tokio_serial::new(&self.cfg.device, self.cfg.baud_rate)
+            .data_bits(data_bits)
+            .stop_bits(stop_bits)
+            .parity(parity)
+            .flow_control(flow_control);
+        tokio_serial::SerialStream::open(&builder)
+            .map_err(|e| errors::Error::StdIoError(e.into()))
+    }
+}
+
+#[async_trait::async_trait]
+impl PpSession for Session {
+    async fn open_tunnel(
+        &self,
+        host: &String,
+        port: u16,
+    ) -> Result<Box<dyn PpStream>, errors::Error> {
+        Err(errors::Error::Unsupported(format!("serial session can not open tunnel to {}:{}", host, port)))
+    }
+
+    async fn open_pty(&self) -> Result<Box<dyn PpPty>, errors::Error> {
+        let port = self.open()?;
+        log::info!("serial port {} opened at {} baud", self.cfg.device, self.cfg.baud_rate);
+        Ok(Box::new(SerialPty { port }))
+    }
+}
+
+pub struct SerialPty {
+    port: tokio_serial::SerialStream,
+}
+
+#[async_trait::async_trait]
+impl PpPty for SerialPty {
+    async fn resize(&mut self, _width: usize, _height: usize) -> Result<(), errors::Error> {
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    async fn send_break(&mut self) -> Result<(), errors::Error> {
+        use std::os::unix::io::AsRawFd;
+        let err = |e: tokio_serial::Error| errors::Error::WritePtyError(format!("serial break error: {:?}", e));
+        // the break is cleared through a duplicate fd so the terminal loop is not stalled meanwhile
+        let fd = unsafe { libc::dup(self.port.as_raw_fd()) };
+        if fd < 0 {
+            return Err(std::io::Error::last_os_error().into());
+        }
+        if let Err(e) = self.port.set_break() {
+            unsafe { libc::close(fd) };
+            return Err(err(e));
+        }
+        tokio::spawn(async move {
+            tokio::time::sleep(std::time::Duration::from_millis(250)).await;
+            if unsafe { libc::ioctl(fd, libc::TIOCCBRK as _) } < 0 {
+                log::error!("serial clear break error: {:?}", std::io::Error::last_os_error());
+            }
+            unsafe { libc::close(fd) };
+        });
+        Ok(())
+    }
+
+    #[cfg(not(unix))]
+    async fn send_break(&mut self) -> Result<(), errors::Error> {
+        let err = |e: tokio_serial::Error| errors::Error::WritePtyError(format!("serial break error: {:?}", e));
+        self.port.set_break().map_err(err)?;
+        tokio::time::sleep(std::time::Duration::from_millis(250)).await;
+        self.port.clear_break().map_err(err)
+    }
+}
+
+impl tokio::io::AsyncRead for SerialPty {
+    fn poll_read(
+        self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+        buf: &mut tokio::io::ReadBuf<'_>,
+    ) -> std::task::Poll<std::io::Result<()>> {
+        std::pin::Pin::new(&mut self.get_mut().port).poll_read(cx, buf)
+    }
+}
+
+impl tokio::io::AsyncWrite for SerialPty {
+    fn poll_write(
+        self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+        buf: &[u8],
+    ) -> std::task::Poll<Result<usize, std::io::Error>> {
+        std::pin::Pin::new(&mut self.get_mut().port).poll_write(cx, buf)
+    }
+
+    fn poll_flush(self: std::pin::Pin<&mut Self>, cx: &mut std::task::Context<'_>) -> std::task::Poll<Result<(), std::io::Error>> {
+        std::pin::Pin::new(&mut self.get_mut().port).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: std::pin::Pin<&mut Self>, cx: &mut std::task::Context<'_>) -> std::task::Poll<Result<(), std::io::Error>> {
+        std::pin::Pin::new(&mut self.get_mut().port).poll_shutdown(cx)
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio_serial::SerialPort;
+
+    use super::*;
+
+    // the far end of a pseudo-terminal pair and a session on the near end
+    fn pair() -> (tokio_serial::SerialStream, Session) {
+        let (master, slave) = tokio_serial::SerialStream::pair().unwrap();
+        let device = slave.name().unwrap();
+        drop(slave);
+        (master, Session::new(SerialProfile { device, baud_rate: 115200, ..Default::default() }))
+    }
+
+    #[tokio::test]
+    async fn read_write() {
+        let (mut master, session) = pair();
+        let mut pty = session.open_pty().await.unwrap();
+
+        pty.write_all(b"ping").await.unwrap();
+        let mut buf = [0u8; 4];
+        tokio::time::timeout(Duration::from_secs(5), master.read_exact(&mut buf)).await.unwrap().unwrap();
+        assert_eq!(&buf, b"ping");
+
+        master.write_all(b"pong").await.unwrap();
+        tokio::time::timeout(Duration::from_secs(5), pty.read_exact(&mut buf)).await.unwrap().unwrap();
+        assert_eq!(&buf, b"pong");
+    }
+
+    #[tokio::test]
+    async fn break_does_not_block() {
+        let (mut master, session) = pair();
+        let mut pty = session.open_pty().await.unwrap();
+        let started = Instant::now();
+        pty.send_break().await.unwrap();
+        assert!(started.elapsed() < Duration::from_millis(200));
+
+        pty.write_all(b"after").await.unwrap();
+        let mut buf = [0u8; 5];
+        tokio::time::timeout(Duration::from_secs(5), master.read_exact(&mut buf)).await.unwrap().unwrap();
+        assert_eq!(&buf, b"after");
+    }
+
+    #[tokio::test]
+    async fn invalid_settings() {
+        let (_master, session) = pair();
+        let cfg = SerialProfile { data_bits: 9, ..session.cfg.clone() };
+        assert!(matches!(Session::new(cfg).open_pty().await, Err(errors::Error::InvalidProfile(_))));
+    }
+}
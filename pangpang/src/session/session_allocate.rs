@@ -1,9 +1,9 @@
 use std::sync::Arc;
 
-use crate::errors;
-use super::ssh;
+use crate::{errors, profile::{Profile, TerminalSettings}};
+use super::{ssh, local, telnet, serial, raw_tcp, container, mosh};
 
-use super::{PpSession, PpSessionManager};
+use super::{PpSession, PpSessionManager, event::SessionEventKind};
 
 
 
@@ -11,13 +11,60 @@ use super::{PpSession, PpSessionManager};
 pub struct Allocator;
 
 impl Allocator {
-    pub async fn ssh_alloc(&self, mgr: &PpSessionManager, addr: &String, port: u16, user: &String, transport: Option<String>, cfg: ssh::SshProfile) -> Result<Arc<dyn PpSession>, errors::Error> {
+    pub async fn ssh_alloc(&self, mgr: &PpSessionManager, id: &String, prof: &Profile, cfg: ssh::SshProfile) -> Result<Arc<dyn PpSession>, errors::Error> {
+        let s = if let Some(transport) = &prof.transport {
+            let transport = mgr.open_tunnel(transport, &prof.address, prof.port).await?;
+            ssh::Session::new_with_stream(transport, &prof.username, &prof.terminal, cfg).await?
+        } else {
+            ssh::Session::new(&prof.address, prof.port, &prof.username, &prof.terminal, cfg).await?
+        };
+        mgr.emit(id, SessionEventKind::Authenticated(s.auth_method().to_string()));
+        Ok(Arc::new(s))
+    }
+
+    pub async fn local_alloc(&self, term: &TerminalSettings, cfg: local::LocalProfile) -> Result<Arc<dyn PpSession>, errors::Error> {
+        Ok(Arc::new(local::Session::new(term, cfg)))
+    }
+
+    pub async fn telnet_alloc(&self, mgr: &PpSessionManager, addr: &String, port: u16, transport: Option<String>, term: &TerminalSettings) -> Result<Arc<dyn PpSession>, errors::Error> {
         let s = if let Some(id) = transport {
             let transport = mgr.open_tunnel(&id, addr, port).await?;
-            ssh::Session::new_with_stream(transport, user, cfg).await?
+            telnet::Session::new_with_stream(Box::new(transport), term)
         } else {
-            ssh::Session::new(addr, port, user, cfg).await?
+            telnet::Session::new(addr, port, term).await?
         };
         Ok(Arc::new(s))
     }
+
+    pub async fn serial_alloc(&self, cfg: serial::SerialProfile) -> Result<Arc<dyn PpSession>, errors::Error> {
+        Ok(Arc::new(serial::Session::new(cfg)))
+    }
+
+    pub async fn raw_tcp_alloc(&self, mgr: &PpSessionManager, addr: &String, port: u16, transport: Option<String>, cfg: raw_tcp::RawTcpProfile) -> Result<Arc<dyn PpSession>, errors::Error> {
+        let s = if let Some(id) = transport {
+            let transport = mgr.open_tunnel(&id, addr, port).await?;
+            raw_tcp::Session::new_with_stream(Box::new(transport), cfg)
+        } else {
+            raw_tcp::Session::new(addr, port, cfg).await?
+        };
+        Ok(Arc::new(s))
+    }
+
+    pub async fn container_alloc(&self, mgr: &PpSessionManager, host: Option<String>, term: &TerminalSettings, cfg: container::ContainerProfile) -> Result<Arc<dyn PpSession>, errors::Error> {
+        let host = match host {
+            Some(id) => {
+                if !mgr.profile(&id).await?.capacity().contains(crate::profile::Capacity::EXEC) {
+                    return Err(errors::Error::Unsupported(format!("container host {} can not exec commands", id)));
+                }
+                Some(mgr.open_session(&id).await?)
+            }
+            None => None,
+        };
+        Ok(Arc::new(container::Session::new(host, term, cfg)))
+    }
+
+    pub async fn mosh_alloc(&self, mgr: &PpSessionManager, id: &String, prof: &Profile, cfg: mosh::MoshProfile) -> Result<Arc<dyn PpSession>, errors::Error> {
+        let ssh = self.ssh_alloc(mgr, id, prof, cfg.ssh.clone()).await?;
+        Ok(Arc::new(mosh::Session::bootstrap(ssh, &prof.address, &prof.terminal, &cfg).await?))
+    }
 }
\ No newline at end of file
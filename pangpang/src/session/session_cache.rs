@@ -0,0 +1,144 @@
+use std::{collections::HashMap, sync::Arc, time::{Duration, Instant}};
+
+use tokio::sync::Mutex;
+
+use super::{PpSession, registry::Registry};
+
+
+
+
+#[derive(Clone, Debug)]
+pub struct CachePolicy {
+    // how long a session stays cached after its last user released it
+    pub linger: Duration,
+    // unused sessions without any traffic for this long are closed before the linger ends,
+    // sessions with open tabs are never closed for being idle
+    pub max_idle: Option<Duration>,
+    // unused sessions beyond this count are evicted, least recently used first
+    pub max_cached: usize,
+}
+
+impl Default for CachePolicy {
+    fn default() -> Self {
+        Self {
+            linger: Duration::from_secs(300),
+            max_idle: None,
+            max_cached: 16,
+        }
+    }
+}
+
+pub(crate) struct CachedSession {
+    pub refs: usize,
+    pub session: Arc<dyn PpSession>,
+    pub serial: u64,
+    pub last_used: Instant,
+}
+
+pub(crate) enum Release {
+    InUse(usize),
+    Lingering,
+    Evicted,
+    Gone,
+}
+
+#[derive(Default)]
+pub(crate) struct SessionCache {
+    entries: Mutex<HashMap<String, CachedSession>>,
+    policy: std::sync::Mutex<CachePolicy>,
+}
+
+impl SessionCache {
+    pub fn policy(&self) -> CachePolicy {
+        self.policy.lock().unwrap().clone()
+    }
+
+    pub fn set_policy(&self, policy: CachePolicy) {
+        *self.policy.lock().unwrap() = policy;
+    }
+
+    pub async fn acquire(&self, id: &String) -> Option<(Arc<dyn PpSession>, u64, usize)> {
+        let mut entries = self.entries.lock().await;
+        let e = entries.get_mut(id)?;
+        e.refs += 1;
+        e.last_used = Instant::now();
+        Some((e.session.clone(), e.serial, e.refs))
+    }
+
+    // returns the sessions evicted to stay under the cap
+    pub async fn insert(&self, id: &String, session: Arc<dyn PpSession>, serial: u64) -> Vec<(String, u64)> {
+        let mut entries = self.entries.lock().await;
+        entries.insert(id.to_owned(), CachedSession { refs: 1, session, serial, last_used: Instant::now() });
+        Self::evict_over_cap(&mut entries, self.policy().max_cached)
+    }
+
+    pub async fn release(&self, id: &String, serial: u64) -> Release {
+        let mut entries = self.entries.lock().await;
+        let e = match entries.get_mut(id) {
+            Some(e) if e.serial == serial => e,
+            _ => return Release::Gone,
+        };
+        e.refs -= 1;
+        e.last_used = Instant::now();
+        if e.refs > 0 {
+            return Release::InUse(e.refs);
+        }
+        let policy = self.policy();
+        if policy.linger.is_zero() {
+            entries.remove(id);
+            return Release::Evicted;
+        }
+        Release::Lingering
+    }
+
+    pub async fn evict(&self, id: &String, serial: u64) -> bool {
+        let mut entries = self.entries.lock().await;
+        if matches!(entries.get(id), Some(e) if e.serial == serial) {
+            entries.remove(id);
+            return true;
+        }
+        false
+    }
+
+    pub async fn refs(&self) -> HashMap<u64, usize> {
+        self.entries.lock().await.values().map(|e| (e.serial, e.refs)).collect()
+    }
+
+    // drops unused sessions that lingered or idled too long, or exceed the cap
+    pub async fn reap(&self, registry: &Registry) -> Vec<(String, u64)> {
+        let policy = self.policy();
+        let idle = |serial: u64| match policy.max_idle {
+            Some(max_idle) => registry.idle_for(serial).map(|d| d >= max_idle).unwrap_or(false),
+            None => false,
+        };
+        let mut entries = self.entries.lock().await;
+        let mut expired: Vec<(String, u64)> = entries.iter()
+            .filter(|(_, e)| e.refs == 0 && (e.last_used.elapsed() >= policy.linger || idle(e.serial)))
+            .map(|(id, e)| (id.clone(), e.serial))
+            .collect();
+        for (id, _) in &expired {
+            entries.remove(id);
+        }
+        expired.extend(Self::evict_over_cap(&mut entries, policy.max_cached));
+        expired
+    }
+
+    fn evict_over_cap(entries: &mut HashMap<String, CachedSession>, max_cached: usize) -> Vec<(String, u64)> {
+        let mut evicted = Vec::new();
+        while entries.len() > max_cached {
+            let lru = entries.iter()
+                .filter(|(_, e)| e.refs == 0)
+                .min_by_key(|(_, e)| e.last_used)
+                .map(|(id, e)| (id.clone(), e.serial));
+            match lru {
+                Some((id, serial)) => {
+                    entries.remove(&id);
+                    evicted.push((id, serial));
+                }
+                // everything left is in use
+                None => break,
+            }
+        }
+        evicted
+    }
+}
@@ -1,22 +1,48 @@
 use std::{ops::Deref, sync::Arc};
 
-use tokio::sync::Mutex;
-
-use super::{PpSession, SessionCacheType};
+use super::{PpSession, event::{self, EventSender, SessionEventKind}, registry::{Registry, ChannelKind, ChannelHandle}, session_cache::{SessionCache, Release}};
 
 pub struct PpSessionGuard {
     inner: Arc<dyn PpSession>,
     id: Option<String>,
-    cache: Arc<Mutex<SessionCacheType>>,
+    cache: Arc<SessionCache>,
+    profile: String,
+    serial: u64,
+    events: EventSender,
+    registry: Arc<Registry>,
 }
 
 impl PpSessionGuard {
-    pub fn new(
+    pub(crate) fn new(
         inner: Arc<dyn PpSession>,
         id: Option<String>,
-        cache: Arc<Mutex<SessionCacheType>>,
+        cache: Arc<SessionCache>,
+        profile: String,
+        serial: u64,
+        events: EventSender,
+        registry: Arc<Registry>,
     ) -> Self {
-        Self { inner, id, cache }
+        Self { inner, id, cache, profile, serial, events, registry }
+    }
+
+    pub fn serial(&self) -> u64 {
+        self.serial
+    }
+
+    pub(crate) fn register(&self, kind: ChannelKind) -> ChannelHandle {
+        self.registry.add_channel(self.serial, kind)
+    }
+
+    pub(crate) fn unregister(&self, channel: u64) {
+        self.registry.remove_channel(self.serial, channel);
+    }
+
+    pub fn profile(&self) -> &String {
+        &self.profile
+    }
+
+    pub(crate) fn emit(&self, kind: SessionEventKind) {
+        event::emit(&self.events, &self.profile, kind);
     }
 }
 
@@ -26,18 +52,29 @@ impl Drop for PpSessionGuard {
         if let Some(id) = &self.id {
             let cache = self.cache.clone();
             let session_id = id.clone();
+            let events = self.events.clone();
+            let registry = self.registry.clone();
+            let serial = self.serial;
             tokio::spawn(async move {
-                let mut cache = cache.lock().await;
-                if let Some((counter, _)) = cache.get_mut(&session_id) {
-                    *counter -= 1;
-                    if 0 == *counter {
-                        cache.remove(&session_id);
+                match cache.release(&session_id, serial).await {
+                    Release::InUse(counter) => event::emit(&events, &session_id, SessionEventKind::Released(counter)),
+                    Release::Lingering => {
+                        log::info!("pangpang session lingering in cache: {}", session_id);
+                        event::emit(&events, &session_id, SessionEventKind::Released(0));
+                    }
+                    Release::Evicted => {
                         log::info!("pangpang session removed from cache: {}", session_id);
+                        registry.remove_session(serial);
+                        event::emit(&events, &session_id, SessionEventKind::Released(0));
+                        event::emit(&events, &session_id, SessionEventKind::Evicted);
                     }
-                } else {
-                    unreachable!("session must be cached")
+                    // force closed and evicted already, maybe even reconnected
+                    Release::Gone => log::info!("pangpang session {} was evicted before release", session_id),
                 }
             });
+        } else {
+            self.registry.remove_session(self.serial);
+            self.emit(SessionEventKind::Closed);
         }
     }
 }
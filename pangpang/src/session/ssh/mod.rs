@@ -15,33 +15,72 @@ mod ssh_tunnel_stream;
 
 use super::{PpStream, PpSession, PpPty, PpTunnelGuard};
 
-use crate::errors;
+use crate::{errors, profile::TerminalSettings};
 
 
-#[derive(Clone)]
+#[derive(Clone, Default)]
 pub struct SshProfile {
     pub password: String,
+    pub identity_file: Option<String>,
+    pub local_forwards: Vec<PortForward>,
+    pub remote_forwards: Vec<PortForward>,
+}
+
+#[derive(Clone, Debug)]
+pub struct PortForward {
+    pub bind_address: Option<String>,
+    pub bind_port: u16,
+    pub host: String,
+    pub port: u16,
 }
 
 pub struct Session {
     s: Arc<Mutex<thrussh::client::Handle<handler::PpSshHandler>>>,
+    term: String,
+    auth: &'static str,
 }
 
 impl Session {
-    pub async fn new(addr: &String, port: u16, username: &String, cfg: SshProfile) -> Result<Self, errors::Error> {
+    #[allow(clippy::ptr_arg)]
+    pub async fn new(addr: &String, port: u16, username: &String, term: &TerminalSettings, cfg: SshProfile) -> Result<Self, errors::Error> {
         let config = Arc::new(thrussh::client::Config::default());
         let mut s = thrussh::client::connect(config, (addr.as_str(), port), handler::PpSshHandler).await?;
-        s.authenticate_password(username, cfg.password).await?;
+        let auth = authenticate(&mut s, username, cfg).await?;
         let s = Arc::new(Mutex::new(s));
-        Ok(Self { s })
+        Ok(Self { s, term: term.term.clone(), auth })
     }
 
-    pub async fn new_with_stream(stream: PpTunnelGuard, username: &String, cfg: SshProfile) -> Result<Self, errors::Error> {
+    pub async fn new_with_stream(stream: PpTunnelGuard, username: &String, term: &TerminalSettings, cfg: SshProfile) -> Result<Self, errors::Error> {
         let config = Arc::new(thrussh::client::Config::default());
         let mut s = thrussh::client::connect_stream(config, stream, handler::PpSshHandler).await?;
-        s.authenticate_password(username, cfg.password).await?;
+        let auth = authenticate(&mut s, username, cfg).await?;
         let s = Arc::new(Mutex::new(s));
-        Ok(Self { s })
+        Ok(Self { s, term: term.term.clone(), auth })
+    }
+
+    // the method the server accepted, publickey or password
+    pub fn auth_method(&self) -> &'static str {
+        self.auth
+    }
+}
+
+async fn authenticate(s: &mut thrussh::client::Handle<handler::PpSshHandler>, username: &String, cfg: SshProfile) -> Result<&'static str, errors::Error> {
+    if let Some(path) = &cfg.identity_file {
+        match thrussh_keys::load_secret_key(path, None) {
+            Ok(key) => {
+                if s.authenticate_publickey(username, Arc::new(key)).await? {
+                    return Ok("publickey");
+                }
+                log::info!("public key {} rejected for {}, trying password", path, username);
+            }
+            // passphrase protected keys are not supported yet
+            Err(e) => log::warn!("can not load key {}: {:?}, trying password", path, e),
+        }
+    }
+    if s.authenticate_password(username, cfg.password).await? {
+        Ok("password")
+    } else {
+        Err(errors::Error::AuthenticationFailed(username.to_owned()))
     }
 }
 
@@ -63,7 +102,7 @@ impl PpSession for Session {
         let mut ch = self.s.lock().await.channel_open_session().await?;
         ch.request_pty(
             false,
-            "xterm-256color",
+            &self.term,
             80,
             20,
             0,
@@ -74,7 +113,19 @@ impl PpSession for Session {
         let term = ssh_tunnel_stream::SshTunnelStream::from(ch);
         Ok(Box::new(term))
     }
-    async fn open_port_forward(&self) {
+    async fn open_pty_in(&self, cwd: &String) -> Result<Box<dyn PpPty>, errors::Error> {
+        let command = format!("cd {} 2>/dev/null; exec \"${{SHELL:-/bin/sh}}\" -l", super::shell_quote(cwd));
+        self.exec(&command, true).await
+    }
+    async fn exec(&self, command: &String, pty: bool) -> Result<Box<dyn PpPty>, errors::Error> {
+        let mut ch = self.s.lock().await.channel_open_session().await?;
+        if pty {
+            ch.request_pty(false, &self.term, 80, 20, 0, 0, &[]).await?;
+        }
+        ch.exec(false, command.as_str()).await?;
+        Ok(Box::new(ssh_tunnel_stream::SshTunnelStream::from(ch)))
+    }
+    async fn open_port_forward(&self) -> Result<(), errors::Error> {
         todo!()
     }
 }
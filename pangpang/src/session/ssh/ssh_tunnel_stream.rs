@@ -11,6 +11,7 @@ use crate::errors;
 pub struct SshTunnelStream {
     channel: thrussh::client::Channel,
     read_buf: Vec<u8>,
+    exit_status: Option<u32>,
 }
 
 impl From<thrussh::client::Channel> for SshTunnelStream {
@@ -18,6 +19,7 @@ impl From<thrussh::client::Channel> for SshTunnelStream {
         Self {
             channel: ch,
             read_buf: Vec::new(),
+            exit_status: None,
         }
     }
 }
@@ -66,7 +68,7 @@ impl AsyncRead for SshTunnelStream {
         cx: &mut std::task::Context<'_>,
         buf: &mut tokio::io::ReadBuf<'_>,
     ) -> std::task::Poll<std::io::Result<()>> {
-        let mut this = self.get_mut();
+        let this = self.get_mut();
         loop {
             if !this.read_buf.is_empty() {
                 let length = this.read_buf.len().min(buf.remaining());
@@ -79,6 +81,10 @@ impl AsyncRead for SshTunnelStream {
                         this.read_buf = data.to_vec();
                         continue
                     }
+                    ChannelMsg::ExitStatus { exit_status } => {
+                        this.exit_status = Some(exit_status);
+                        continue
+                    }
                     ChannelMsg::Eof | ChannelMsg::Close => {
                         let e = std::io::Error::from(std::io::ErrorKind::UnexpectedEof);
                         std::task::Poll::Ready(Err(e))
@@ -100,4 +106,8 @@ impl PpPty for SshTunnelStream {
             _ => Ok(())
         }
     }
+
+    async fn exit_status(&mut self) -> Option<i32> {
+        self.exit_status.map(|s| s as i32)
+    }
 }
\ No newline at end of file
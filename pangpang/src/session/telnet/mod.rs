@@ -0,0 +1,101 @@
+use tokio::sync::{Mutex, mpsc};
+
+use crate::{errors, profile::TerminalSettings};
+
+use super::{PpStream, PpSession, PpPty};
+
+mod protocol;
+
+
+
+
+#[derive(Clone, Default)]
+pub struct TelnetProfile {}
+
+pub struct Session {
+    stream: Mutex<Option<Box<dyn PpStream>>>,
+    term: String,
+}
+
+impl Session {
+    pub async fn new(addr: &str, port: u16, term: &TerminalSettings) -> Result<Self, errors::Error> {
+        let stream = tokio::net::TcpStream::connect((addr, port)).await?;
+        stream.set_nodelay(true)?;
+        Ok(Self::new_with_stream(Box::new(stream), term))
+    }
+
+    pub fn new_with_stream(stream: Box<dyn PpStream>, term: &TerminalSettings) -> Self {
+        Self {
+            stream: Mutex::new(Some(stream)),
+            term: term.term.clone(),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl PpSession for Session {
+    async fn open_tunnel(
+        &self,
+        host: &String,
+        port: u16,
+    ) -> Result<Box<dyn PpStream>, errors::Error> {
+        Err(errors::Error::Unsupported(format!("telnet session can not open tunnel to {}:{}", host, port)))
+    }
+
+    async fn open_pty(&self) -> Result<Box<dyn PpPty>, errors::Error> {
+        let stream = match self.stream.lock().await.take() {
+            Some(s) => s,
+            None => return Err(errors::Error::PpStreamError("telnet connection already in use".to_string())),
+        };
+        let (local, remote) = tokio::io::duplex(4096);
+        let (resize, resize_rx) = mpsc::channel(8);
+        let term = self.term.clone();
+        tokio::spawn(async move {
+            if let Err(e) = protocol::pump(stream, remote, resize_rx, term).await {
+                log::error!("telnet connection closed with error: {:?}", e);
+            }
+        });
+        Ok(Box::new(TelnetPty { io: local, resize }))
+    }
+}
+
+pub struct TelnetPty {
+    io: tokio::io::DuplexStream,
+    resize: mpsc::Sender<(u16, u16)>,
+}
+
+#[async_trait::async_trait]
+impl PpPty for TelnetPty {
+    async fn resize(&mut self, width: usize, height: usize) -> Result<(), errors::Error> {
+        self.resize.send((width as u16, height as u16)).await
+            .map_err(|_| errors::Error::WritePtyError("telnet connection closed".to_string()))
+    }
+}
+
+impl tokio::io::AsyncRead for TelnetPty {
+    fn poll_read(
+        self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+        buf: &mut tokio::io::ReadBuf<'_>,
+    ) -> std::task::Poll<std::io::Result<()>> {
+        std::pin::Pin::new(&mut self.get_mut().io).poll_read(cx, buf)
+    }
+}
+
+impl tokio::io::AsyncWrite for TelnetPty {
+    fn poll_write(
+        self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+        buf: &[u8],
+    ) -> std::task::Poll<Result<usize, std::io::Error>> {
+        std::pin::Pin::new(&mut self.get_mut().io).poll_write(cx, buf)
+    }
+
+    fn poll_flush(self: std::pin::Pin<&mut Self>, cx: &mut std::task::Context<'_>) -> std::task::Poll<Result<(), std::io::Error>> {
+        std::pin::Pin::new(&mut self.get_mut().io).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: std::pin::Pin<&mut Self>, cx: &mut std::task::Context<'_>) -> std::task::Poll<Result<(), std::io::Error>> {
+        std::pin::Pin::new(&mut self.get_mut().io).poll_shutdown(cx)
+    }
+}
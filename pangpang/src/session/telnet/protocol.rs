@@ -0,0 +1,293 @@
+use std::collections::HashSet;
+
+use tokio::{io::{AsyncReadExt, AsyncWriteExt, DuplexStream}, sync::mpsc};
+
+use crate::session::PpStream;
+
+
+
+
+const SE: u8 = 240;
+const SB: u8 = 250;
+const WILL: u8 = 251;
+const WONT: u8 = 252;
+const DO: u8 = 253;
+const DONT: u8 = 254;
+const IAC: u8 = 255;
+
+const OPT_ECHO: u8 = 1;
+const OPT_SGA: u8 = 3;
+const OPT_TTYPE: u8 = 24;
+const OPT_NAWS: u8 = 31;
+
+const TTYPE_IS: u8 = 0;
+const TTYPE_SEND: u8 = 1;
+
+enum State {
+    Data,
+    Iac,
+    Command(u8),
+    Sub(Vec<u8>),
+    SubIac(Vec<u8>),
+}
+
+struct Telnet {
+    state: State,
+    term: String,
+    size: (u16, u16),
+    // options enabled on our side (WILL) and on the server side (DO)
+    local: HashSet<u8>,
+    remote: HashSet<u8>,
+    // options we asked for and are waiting on an answer
+    local_pending: HashSet<u8>,
+    remote_pending: HashSet<u8>,
+    last_cr: bool,
+}
+
+impl Telnet {
+    fn new(term: String) -> Self {
+        Self {
+            state: State::Data,
+            term,
+            size: (80, 20),
+            local: HashSet::new(),
+            remote: HashSet::new(),
+            local_pending: HashSet::new(),
+            remote_pending: HashSet::new(),
+            last_cr: false,
+        }
+    }
+
+    fn start(&mut self, reply: &mut Vec<u8>) {
+        for opt in [OPT_NAWS, OPT_TTYPE] {
+            self.local_pending.insert(opt);
+            reply.extend_from_slice(&[IAC, WILL, opt]);
+        }
+        for opt in [OPT_SGA, OPT_ECHO] {
+            self.remote_pending.insert(opt);
+            reply.extend_from_slice(&[IAC, DO, opt]);
+        }
+    }
+
+    fn receive(&mut self, input: &[u8], data: &mut Vec<u8>, reply: &mut Vec<u8>) {
+        for &b in input {
+            self.state = match std::mem::replace(&mut self.state, State::Data) {
+                State::Data if b == IAC => State::Iac,
+                State::Data => {
+                    if !(self.last_cr && b == 0) {
+                        data.push(b);
+                    }
+                    self.last_cr = b == b'\r';
+                    State::Data
+                }
+                State::Iac => match b {
+                    IAC => {
+                        data.push(IAC);
+                        self.last_cr = false;
+                        State::Data
+                    }
+                    WILL | WONT | DO | DONT => State::Command(b),
+                    SB => State::Sub(Vec::new()),
+                    _ => State::Data,
+                },
+                State::Command(cmd) => {
+                    self.negotiate(cmd, b, reply);
+                    State::Data
+                }
+                State::Sub(buf) if b == IAC => State::SubIac(buf),
+                State::Sub(mut buf) => {
+                    buf.push(b);
+                    State::Sub(buf)
+                }
+                State::SubIac(buf) if b == SE => {
+                    self.subnegotiate(&buf, reply);
+                    State::Data
+                }
+                State::SubIac(mut buf) => {
+                    buf.push(b);
+                    State::Sub(buf)
+                }
+            };
+        }
+    }
+
+    fn negotiate(&mut self, cmd: u8, opt: u8, reply: &mut Vec<u8>) {
+        match cmd {
+            WILL => {
+                if !matches!(opt, OPT_ECHO | OPT_SGA) {
+                    reply.extend_from_slice(&[IAC, DONT, opt]);
+                } else if self.remote.insert(opt) && !self.remote_pending.remove(&opt) {
+                    reply.extend_from_slice(&[IAC, DO, opt]);
+                }
+            }
+            WONT => {
+                self.remote_pending.remove(&opt);
+                if self.remote.remove(&opt) {
+                    reply.extend_from_slice(&[IAC, DONT, opt]);
+                }
+            }
+            DO => {
+                if !matches!(opt, OPT_NAWS | OPT_TTYPE | OPT_SGA) {
+                    reply.extend_from_slice(&[IAC, WONT, opt]);
+                } else if self.local.insert(opt) {
+                    if !self.local_pending.remove(&opt) {
+                        reply.extend_from_slice(&[IAC, WILL, opt]);
+                    }
+                    if opt == OPT_NAWS {
+                        self.window_size(reply);
+                    }
+                }
+            }
+            DONT => {
+                self.local_pending.remove(&opt);
+                if self.local.remove(&opt) {
+                    reply.extend_from_slice(&[IAC, WONT, opt]);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn subnegotiate(&mut self, buf: &[u8], reply: &mut Vec<u8>) {
+        if buf == [OPT_TTYPE, TTYPE_SEND] && self.local.contains(&OPT_TTYPE) {
+            reply.extend_from_slice(&[IAC, SB, OPT_TTYPE, TTYPE_IS]);
+            reply.extend_from_slice(self.term.to_uppercase().as_bytes());
+            reply.extend_from_slice(&[IAC, SE]);
+        }
+    }
+
+    fn resize(&mut self, width: u16, height: u16, reply: &mut Vec<u8>) {
+        self.size = (width, height);
+        if self.local.contains(&OPT_NAWS) {
+            self.window_size(reply);
+        }
+    }
+
+    fn window_size(&self, reply: &mut Vec<u8>) {
+        reply.extend_from_slice(&[IAC, SB, OPT_NAWS]);
+        let (w, h) = self.size;
+        for b in w.to_be_bytes().iter().chain(h.to_be_bytes().iter()) {
+            if *b == IAC {
+                reply.push(IAC);
+            }
+            reply.push(*b);
+        }
+        reply.extend_from_slice(&[IAC, SE]);
+    }
+
+    fn send(&self, input: &[u8], out: &mut Vec<u8>) {
+        for (i, &b) in input.iter().enumerate() {
+            match b {
+                IAC => out.extend_from_slice(&[IAC, IAC]),
+                b'\r' if input.get(i + 1) != Some(&b'\n') => out.extend_from_slice(&[b'\r', 0]),
+                _ => out.push(b),
+            }
+        }
+    }
+}
+
+pub(super) async fn pump(mut stream: Box<dyn PpStream>, mut user: DuplexStream, mut resize: mpsc::Receiver<(u16, u16)>, term: String) -> Result<(), std::io::Error> {
+    let mut telnet = Telnet::new(term);
+    let mut reply = Vec::new();
+    telnet.start(&mut reply);
+    stream.write_all(&reply).await?;
+    let mut net_buf = [0u8; 4096];
+    let mut user_buf = [0u8; 1500];
+    loop {
+        let mut data = Vec::new();
+        reply.clear();
+        tokio::select! {
+            n = stream.read(&mut net_buf) => {
+                let n = n?;
+                if n == 0 {
+                    return Ok(());
+                }
+                telnet.receive(&net_buf[..n], &mut data, &mut reply);
+                if !data.is_empty() {
+                    user.write_all(&data).await?;
+                }
+            }
+            n = user.read(&mut user_buf) => {
+                let n = n?;
+                if n == 0 {
+                    return stream.shutdown().await;
+                }
+                telnet.send(&user_buf[..n], &mut reply);
+            }
+            Some((w, h)) = resize.recv() => {
+                telnet.resize(w, h, &mut reply);
+            }
+        }
+        if !reply.is_empty() {
+            stream.write_all(&reply).await?;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::{io::{AsyncReadExt, AsyncWriteExt}, net::{TcpListener, TcpStream}};
+
+    use crate::{profile::TerminalSettings, session::{PpPty, PpSession}};
+
+    use super::*;
+
+    async fn connect() -> (Box<dyn PpPty>, TcpStream) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let session = crate::session::telnet::Session::new("127.0.0.1", port, &TerminalSettings::default()).await.unwrap();
+        let (server, _) = listener.accept().await.unwrap();
+        (session.open_pty().await.unwrap(), server)
+    }
+
+    async fn expect(server: &mut TcpStream, bytes: &[u8]) {
+        let mut buf = vec![0u8; bytes.len()];
+        tokio::time::timeout(std::time::Duration::from_secs(5), server.read_exact(&mut buf)).await.unwrap().unwrap();
+        assert_eq!(buf, bytes);
+    }
+
+    #[tokio::test]
+    async fn negotiation() {
+        let (mut pty, mut server) = connect().await;
+        expect(&mut server, &[IAC, WILL, OPT_NAWS, IAC, WILL, OPT_TTYPE, IAC, DO, OPT_SGA, IAC, DO, OPT_ECHO]).await;
+
+        // answers to our own requests are not repeated, unknown options are refused
+        server.write_all(&[IAC, DO, OPT_NAWS, IAC, DO, OPT_TTYPE, IAC, WILL, OPT_ECHO, IAC, DO, 99, IAC, WILL, 98]).await.unwrap();
+        expect(&mut server, &[IAC, SB, OPT_NAWS, 0, 80, 0, 20, IAC, SE, IAC, WONT, 99, IAC, DONT, 98]).await;
+
+        server.write_all(&[IAC, SB, OPT_TTYPE, TTYPE_SEND, IAC, SE]).await.unwrap();
+        let mut reply = vec![IAC, SB, OPT_TTYPE, TTYPE_IS];
+        reply.extend_from_slice(b"XTERM-256COLOR");
+        reply.extend_from_slice(&[IAC, SE]);
+        expect(&mut server, &reply).await;
+
+        // the window size escapes a 255 byte
+        pty.resize(255, 40).await.unwrap();
+        expect(&mut server, &[IAC, SB, OPT_NAWS, 0, IAC, IAC, 0, 40, IAC, SE]).await;
+
+        // an option the server turns off is acknowledged once
+        server.write_all(&[IAC, WONT, OPT_ECHO, IAC, WONT, OPT_ECHO, IAC, DONT, OPT_NAWS]).await.unwrap();
+        expect(&mut server, &[IAC, DONT, OPT_ECHO, IAC, WONT, OPT_NAWS]).await;
+    }
+
+    #[tokio::test]
+    async fn iac_escaping() {
+        let (mut pty, mut server) = connect().await;
+        expect(&mut server, &[IAC, WILL, OPT_NAWS, IAC, WILL, OPT_TTYPE, IAC, DO, OPT_SGA, IAC, DO, OPT_ECHO]).await;
+
+        // doubled IAC and CR NUL from the server come out as one byte, a NOP (241) is dropped
+        server.write_all(&[b'a', IAC, IAC, b'b', b'\r', 0, b'c', IAC, 241, b'd']).await.unwrap();
+        let mut buf = [0u8; 6];
+        pty.read_exact(&mut buf).await.unwrap();
+        assert_eq!(buf, [b'a', IAC, b'b', b'\r', b'c', b'd']);
+
+        // and the other way round
+        pty.write_all(&[b'x', IAC, b'\r', b'y', b'\r', b'\n']).await.unwrap();
+        expect(&mut server, &[b'x', IAC, IAC, b'\r', 0, b'y', b'\r', b'\n']).await;
+
+        drop(pty);
+        let mut rest = Vec::new();
+        server.read_to_end(&mut rest).await.unwrap();
+        assert!(rest.is_empty());
+    }
+}
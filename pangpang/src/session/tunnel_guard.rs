@@ -1,17 +1,35 @@
 use tokio::io::{AsyncWrite, AsyncRead};
 
-use super::{PpStream, PpSessionGuard};
+use super::{PpStream, PpSessionGuard, event::SessionEventKind, registry::{ChannelKind, ChannelHandle}};
 
 
 
 pub struct PpTunnelGuard {
     inner: Box<dyn PpStream>,
     _session: PpSessionGuard,
+    channel: ChannelHandle,
+    closed: bool,
 }
 
 impl PpTunnelGuard {
-    pub fn new(inner: Box<dyn PpStream>, s: PpSessionGuard) -> Self {
-        PpTunnelGuard { inner: inner, _session: s }
+    pub fn new(inner: Box<dyn PpStream>, s: PpSessionGuard, kind: ChannelKind) -> Self {
+        let channel = s.register(kind);
+        PpTunnelGuard { inner, _session: s, channel, closed: false }
+    }
+
+    // a forced close from the connections view ends the stream like an eof
+    fn closed(&mut self, cx: &mut std::task::Context<'_>) -> bool {
+        if !self.closed && std::future::Future::poll(std::pin::Pin::new(&mut self.channel.close), cx).is_ready() {
+            self.closed = true;
+        }
+        self.closed
+    }
+}
+
+impl Drop for PpTunnelGuard {
+    fn drop(&mut self) {
+        self._session.unregister(self.channel.serial);
+        self._session.emit(SessionEventKind::TunnelClosed);
     }
 }
 
@@ -21,7 +39,18 @@ impl AsyncRead for PpTunnelGuard {
         cx: &mut std::task::Context<'_>,
         buf: &mut tokio::io::ReadBuf<'_>,
     ) -> std::task::Poll<std::io::Result<()>> {
-        std::pin::Pin::new(self.get_mut().inner.as_mut()).poll_read(cx, buf)
+        let this = self.get_mut();
+        if this.closed(cx) {
+            return std::task::Poll::Ready(Ok(()));
+        }
+        let before = buf.filled().len();
+        let r = std::pin::Pin::new(this.inner.as_mut()).poll_read(cx, buf);
+        if let std::task::Poll::Ready(Ok(())) = r {
+            let n = buf.filled().len() - before;
+            this.channel.stats.add_in(n);
+            this.channel.session_stats.add_in(n);
+        }
+        r
     }
 }
 impl AsyncWrite for PpTunnelGuard {
@@ -30,7 +59,16 @@ impl AsyncWrite for PpTunnelGuard {
         cx: &mut std::task::Context<'_>,
         buf: &[u8],
     ) -> std::task::Poll<Result<usize, std::io::Error>> {
-        std::pin::Pin::new(self.get_mut().inner.as_mut()).poll_write(cx, buf)
+        let this = self.get_mut();
+        if this.closed(cx) {
+            return std::task::Poll::Ready(Err(std::io::ErrorKind::BrokenPipe.into()));
+        }
+        let r = std::pin::Pin::new(this.inner.as_mut()).poll_write(cx, buf);
+        if let std::task::Poll::Ready(Ok(n)) = r {
+            this.channel.stats.add_out(n);
+            this.channel.session_stats.add_out(n);
+        }
+        r
     }
 
     fn poll_flush(self: std::pin::Pin<&mut Self>, cx: &mut std::task::Context<'_>) -> std::task::Poll<Result<(), std::io::Error>> {
@@ -0,0 +1,814 @@
+use std::{collections::{BTreeMap, HashMap}, path::PathBuf, time::{Duration, Instant, SystemTime}};
+
+use crate::{errors, profile::{Profile, Protocol, Inherit, TerminalSettings}, session::ssh::SshProfile, terminal::{LogFormat, LogSettings, TriggerRule}};
+
+use super::{Reload, Storage};
+
+
+
+
+#[derive(Clone)]
+pub enum InventorySource {
+    File(PathBuf),
+    Script(PathBuf),
+}
+
+#[derive(Default)]
+struct Group {
+    hosts: Vec<String>,
+    children: Vec<String>,
+    vars: HashMap<String, String>,
+}
+
+#[derive(Default)]
+struct Inventory {
+    groups: BTreeMap<String, Group>,
+    hostvars: BTreeMap<String, HashMap<String, String>>,
+}
+
+pub struct InventoryStorage {
+    source: InventorySource,
+    interval: Duration,
+    refreshed: Option<Instant>,
+    modified: Option<SystemTime>,
+    // mtime of the load in flight, kept once its profiles are in
+    loading: Option<SystemTime>,
+    map: HashMap<String, Profile>,
+}
+
+impl InventoryStorage {
+    pub fn new(source: InventorySource, interval: Duration) -> Result<Self, errors::Error> {
+        let mut s = Self {
+            source,
+            interval,
+            refreshed: None,
+            modified: None,
+            loading: None,
+            map: HashMap::new(),
+        };
+        s.refresh()?;
+        Ok(s)
+    }
+}
+
+impl InventorySource {
+    fn load(&self) -> Result<Inventory, errors::Error> {
+        match self {
+            InventorySource::File(path) => {
+                let content = std::fs::read_to_string(path)?;
+                match path.extension().and_then(|e| e.to_str()) {
+                    Some("yml") | Some("yaml") => parse_yaml(&content),
+                    Some("json") => parse_json(&content),
+                    _ => Ok(parse_ini(&content)),
+                }
+            }
+            InventorySource::Script(path) => {
+                let output = std::process::Command::new(path).arg("--list").output()?;
+                if !output.status.success() {
+                    return Err(errors::Error::ConfigParseError(format!(
+                        "inventory script {} exited with {}: {}",
+                        path.display(), output.status, String::from_utf8_lossy(&output.stderr)
+                    )));
+                }
+                parse_json(&String::from_utf8_lossy(&output.stdout))
+            }
+        }
+    }
+}
+
+impl Storage for InventoryStorage {
+    fn get(&self, id: &String) -> Result<Profile, errors::Error> {
+        if let Some(p) = self.map.get(id) {
+            Ok(p.clone())
+        } else {
+            Err(errors::Error::ProfileNotFound(id.to_owned()))
+        }
+    }
+
+    fn iter(&self) -> Box<dyn Iterator<Item = (&String, &Profile)> + '_> {
+        Box::new(self.map.iter())
+    }
+
+    fn refresh(&mut self) -> Result<bool, errors::Error> {
+        match self.reload() {
+            Some(load) => {
+                self.replace(load()?);
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+
+    fn reload(&mut self) -> Option<Reload> {
+        match &self.source {
+            InventorySource::File(path) => {
+                let modified = std::fs::metadata(path).and_then(|m| m.modified()).ok();
+                if modified.is_some() && modified == self.modified {
+                    return None;
+                }
+                self.loading = modified;
+            }
+            InventorySource::Script(_) => {
+                if self.refreshed.map(|t| t.elapsed() < self.interval).unwrap_or(false) {
+                    return None;
+                }
+            }
+        }
+        self.refreshed = Some(Instant::now());
+        let source = self.source.clone();
+        Some(Box::new(move || Ok(source.load()?.profiles())))
+    }
+
+    fn replace(&mut self, profiles: Vec<Profile>) {
+        self.map = profiles.into_iter().map(|p| (p.id(), p)).collect();
+        self.modified = self.loading.take();
+        log::info!("inventory refreshed, {} profiles", self.map.len());
+    }
+}
+
+impl Inventory {
+    fn group(&mut self, name: &str) -> &mut Group {
+        self.groups.entry(name.to_string()).or_default()
+    }
+
+    fn add_host(&mut self, group: &str, host: &str, vars: HashMap<String, String>) {
+        let g = self.group(group);
+        if !g.hosts.iter().any(|h| h == host) {
+            g.hosts.push(host.to_string());
+        }
+        self.hostvars.entry(host.to_string()).or_default().extend(vars);
+    }
+
+    fn group_paths(&self) -> HashMap<String, Vec<String>> {
+        let mut paths: HashMap<String, Vec<String>> = HashMap::new();
+        let mut stack = vec![("all".to_string(), Vec::new())];
+        while let Some((name, path)) = stack.pop() {
+            if paths.contains_key(&name) {
+                continue;
+            }
+            if let Some(g) = self.groups.get(&name) {
+                for child in g.children.iter().rev() {
+                    let mut p = path.clone();
+                    p.push(child.clone());
+                    stack.push((child.clone(), p));
+                }
+            }
+            paths.insert(name, path);
+        }
+        for name in self.groups.keys() {
+            if !paths.contains_key(name) {
+                paths.insert(name.clone(), vec![name.clone()]);
+            }
+        }
+        paths
+    }
+
+    fn profiles(&self) -> Vec<Profile> {
+        let paths = self.group_paths();
+        let mut ids: HashMap<&String, String> = HashMap::new();
+        for host in self.hostvars.keys() {
+            ids.insert(host, Profile::stable_id(&format!("inventory:{}", host)));
+        }
+        let mut profiles = Vec::new();
+        let mut hops: BTreeMap<String, Profile> = BTreeMap::new();
+        for (host, hostvars) in &self.hostvars {
+            let mut groups: Vec<(&String, &Vec<String>)> = self.groups.iter()
+                .filter(|(_, g)| g.hosts.contains(host))
+                .filter_map(|(name, _)| paths.get(name).map(|p| (name, p)))
+                .collect();
+            groups.sort_by_key(|(name, p)| (p.len(), *name));
+
+            let mut vars: HashMap<String, String> = HashMap::new();
+            let mut tags: Vec<String> = Vec::new();
+            if let Some(all) = self.groups.get("all") {
+                vars.extend(all.vars.clone());
+            }
+            for (_, path) in &groups {
+                for ancestor in path.iter() {
+                    if let Some(g) = self.groups.get(ancestor) {
+                        vars.extend(g.vars.clone());
+                    }
+                    if !tags.contains(ancestor) {
+                        tags.push(ancestor.clone());
+                    }
+                }
+            }
+            vars.extend(hostvars.clone());
+            let folder = groups.last().map(|(_, p)| (*p).clone()).unwrap_or_default();
+            let mut profile = host_profile(ids[host].clone(), host, folder, &vars);
+            profile.tags = tags.into_iter().filter(|t| t != "ungrouped").collect();
+
+            if let Some(jump) = jump_host(&vars) {
+                let transport = match ids.get(&jump) {
+                    Some(id) => id.clone(),
+                    None => {
+                        let hop = hops.entry(jump.clone()).or_insert_with(|| hop_profile(&jump));
+                        hop.id()
+                    }
+                };
+                profile.transport = Some(transport);
+            }
+            profiles.push(profile);
+        }
+        profiles.extend(hops.into_values());
+        profiles
+    }
+}
+
+fn host_profile(id: String, host: &str, folder: Vec<String>, vars: &HashMap<String, String>) -> Profile {
+    let var = |keys: &[&str]| keys.iter().find_map(|k| vars.get(*k)).cloned();
+    Profile {
+        id,
+        name: host.to_string(),
+        folder,
+        tags: Vec::new(),
+        notes: String::new(),
+        username: var(&["ansible_user", "ansible_ssh_user"])
+            .or_else(|| std::env::var("USER").ok())
+            .unwrap_or_default(),
+        address: var(&["ansible_host", "ansible_ssh_host"]).unwrap_or_else(|| host.to_string()),
+        port: var(&["ansible_port", "ansible_ssh_port"]).and_then(|p| p.parse().ok()).unwrap_or(22),
+        transport: None,
+        protocol: Protocol::Ssh(SshProfile {
+            password: var(&["ansible_password", "ansible_ssh_pass"]).unwrap_or_default(),
+            identity_file: var(&["ansible_ssh_private_key_file", "ansible_private_key_file"]),
+            ..Default::default()
+        }),
+        terminal: terminal_settings(vars),
+        parent: None,
+        inherit: Inherit::empty(),
+        template: false,
+    }
+}
+
+fn hop_profile(jump: &String) -> Profile {
+    let (user, host) = match jump.rsplit_once('@') {
+        Some((u, h)) => (Some(u.to_string()), h.to_string()),
+        None => (None, jump.clone()),
+    };
+    let (host, port) = match host.rsplit_once(':') {
+        Some((h, p)) if p.parse::<u16>().is_ok() => (h.to_string(), Some(p.to_string())),
+        _ => (host, None),
+    };
+    let mut vars = HashMap::new();
+    vars.insert("ansible_host".to_string(), host.clone());
+    if let Some(user) = user {
+        vars.insert("ansible_user".to_string(), user);
+    }
+    if let Some(port) = port {
+        vars.insert("ansible_port".to_string(), port);
+    }
+    let id = Profile::stable_id(&format!("inventory-hop:{}", jump));
+    host_profile(id, jump, vec!["jump hosts".to_string()], &vars)
+}
+
+fn terminal_settings(vars: &HashMap<String, String>) -> TerminalSettings {
+    TerminalSettings {
+        logging: log_settings(vars),
+        triggers: trigger_rules(vars),
+        ..Default::default()
+    }
+}
+
+// pangpang_log names the file, the other pangpang_log_* vars are only read along with it
+fn log_settings(vars: &HashMap<String, String>) -> Option<LogSettings> {
+    let flag = |key: &str| vars.get(key).map(|v| matches!(v.to_lowercase().as_str(), "true" | "yes" | "1"));
+    let mut logging = LogSettings { path: vars.get("pangpang_log")?.clone(), ..Default::default() };
+    if let Some(format) = vars.get("pangpang_log_format") {
+        logging.format = if format == "raw" { LogFormat::Raw } else { LogFormat::Text };
+    }
+    if let Some(size) = vars.get("pangpang_log_max_size").and_then(|s| s.parse::<u64>().ok()) {
+        logging.max_size = Some(size).filter(|s| *s > 0);
+    }
+    logging.log_input = flag("pangpang_log_input").unwrap_or(logging.log_input);
+    logging.timestamps = flag("pangpang_log_timestamps").unwrap_or(logging.timestamps);
+    Some(logging)
+}
+
+// pangpang_triggers holds a json list, a broken list is dropped as a whole
+fn trigger_rules(vars: &HashMap<String, String>) -> Vec<TriggerRule> {
+    match vars.get("pangpang_triggers").map(|t| TriggerRule::parse_list(t)) {
+        Some(Ok(rules)) => rules,
+        Some(Err(e)) => {
+            log::error!("pangpang_triggers ignored: {:?}", e);
+            Vec::new()
+        }
+        None => Vec::new(),
+    }
+}
+
+fn jump_host(vars: &HashMap<String, String>) -> Option<String> {
+    if let Some(jump) = vars.get("pangpang_jump") {
+        return Some(jump.clone());
+    }
+    for key in ["ansible_ssh_common_args", "ansible_ssh_extra_args"] {
+        let args: Vec<&str> = match vars.get(key) {
+            Some(a) => a.split_whitespace().map(|s| s.trim_matches(|c| c == '\'' || c == '"')).collect(),
+            None => continue,
+        };
+        for (i, arg) in args.iter().enumerate() {
+            let jump = if *arg == "-J" {
+                args.get(i + 1).map(|s| s.to_string())
+            } else {
+                arg.strip_prefix("ProxyJump=").or_else(|| arg.strip_prefix("-oProxyJump=")).map(|s| s.to_string())
+            };
+            if let Some(jump) = jump {
+                return jump.split(',').next_back().map(|s| s.to_string());
+            }
+        }
+    }
+    None
+}
+
+
+fn parse_ini(content: &str) -> Inventory {
+    let mut inv = Inventory::default();
+    let mut section = ("ungrouped".to_string(), "hosts");
+    for line in content.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+            continue;
+        }
+        if line.starts_with('[') && line.ends_with(']') {
+            let name = &line[1..line.len() - 1];
+            section = match name.split_once(':') {
+                Some((g, "vars")) => (g.to_string(), "vars"),
+                Some((g, "children")) => (g.to_string(), "children"),
+                _ => (name.to_string(), "hosts"),
+            };
+            inv.group(&section.0);
+            continue;
+        }
+        let mut fields = split_args(line).into_iter();
+        let first = match fields.next() {
+            Some(f) => f,
+            None => continue,
+        };
+        match section.1 {
+            "vars" => {
+                if let Some((k, v)) = line.split_once('=') {
+                    inv.group(&section.0).vars.insert(k.trim().to_string(), unquote(v.trim()));
+                }
+            }
+            "children" => {
+                inv.group(&first);
+                let g = inv.group(&section.0);
+                if !g.children.contains(&first) {
+                    g.children.push(first);
+                }
+            }
+            _ => {
+                let vars: HashMap<String, String> = fields
+                    .filter_map(|f| f.split_once('=').map(|(k, v)| (k.to_string(), unquote(v))))
+                    .collect();
+                for host in expand_host_range(&first) {
+                    inv.add_host(&section.0, &host, vars.clone());
+                }
+            }
+        }
+    }
+    link_top_groups(&mut inv);
+    inv
+}
+
+fn parse_yaml(content: &str) -> Result<Inventory, errors::Error> {
+    let root: serde_yaml::Value = serde_yaml::from_str(content)
+        .map_err(|e| errors::Error::ConfigParseError(e.to_string()))?;
+    let mut inv = Inventory::default();
+    if let serde_yaml::Value::Mapping(groups) = root {
+        for (name, group) in groups {
+            if let Some(name) = name.as_str() {
+                parse_yaml_group(&mut inv, name, &group);
+            }
+        }
+    }
+    link_top_groups(&mut inv);
+    Ok(inv)
+}
+
+fn parse_yaml_group(inv: &mut Inventory, name: &str, group: &serde_yaml::Value) {
+    inv.group(name);
+    if let Some(hosts) = group.get("hosts").and_then(|h| h.as_mapping()) {
+        for (host, vars) in hosts {
+            if let Some(host) = host.as_str() {
+                for h in expand_host_range(host) {
+                    inv.add_host(name, &h, yaml_vars(vars));
+                }
+            }
+        }
+    }
+    inv.group(name).vars.extend(yaml_vars(group.get("vars").unwrap_or(&serde_yaml::Value::Null)));
+    if let Some(children) = group.get("children").and_then(|c| c.as_mapping()) {
+        for (child, child_group) in children {
+            if let Some(child) = child.as_str() {
+                let g = inv.group(name);
+                if !g.children.iter().any(|c| c == child) {
+                    g.children.push(child.to_string());
+                }
+                parse_yaml_group(inv, child, child_group);
+            }
+        }
+    }
+}
+
+fn yaml_vars(value: &serde_yaml::Value) -> HashMap<String, String> {
+    let mut vars = HashMap::new();
+    if let Some(map) = value.as_mapping() {
+        for (k, v) in map {
+            let v = match v {
+                serde_yaml::Value::String(s) => s.clone(),
+                serde_yaml::Value::Number(n) => n.to_string(),
+                serde_yaml::Value::Bool(b) => b.to_string(),
+                _ => continue,
+            };
+            if let Some(k) = k.as_str() {
+                vars.insert(k.to_string(), v);
+            }
+        }
+    }
+    vars
+}
+
+fn parse_json(content: &str) -> Result<Inventory, errors::Error> {
+    let root: serde_json::Value = serde_json::from_str(content)
+        .map_err(|e| errors::Error::ConfigParseError(e.to_string()))?;
+    let mut inv = Inventory::default();
+    let groups = match root.as_object() {
+        Some(g) => g,
+        None => return Err(errors::Error::ConfigParseError("inventory is not a json object".to_string())),
+    };
+    for (name, group) in groups {
+        if name == "_meta" {
+            continue;
+        }
+        let (hosts, children, vars) = match group {
+            serde_json::Value::Array(hosts) => (Some(hosts), None, None),
+            serde_json::Value::Object(g) => (
+                g.get("hosts").and_then(|h| h.as_array()),
+                g.get("children").and_then(|c| c.as_array()),
+                g.get("vars"),
+            ),
+            _ => continue,
+        };
+        inv.group(name);
+        for host in hosts.into_iter().flatten().filter_map(|h| h.as_str()) {
+            inv.add_host(name, host, HashMap::new());
+        }
+        for child in children.into_iter().flatten().filter_map(|c| c.as_str()) {
+            inv.group(child);
+            inv.group(name).children.push(child.to_string());
+        }
+        if let Some(vars) = vars {
+            inv.group(name).vars.extend(json_vars(vars));
+        }
+    }
+    if let Some(hostvars) = root.pointer("/_meta/hostvars").and_then(|h| h.as_object()) {
+        for (host, vars) in hostvars {
+            inv.hostvars.entry(host.clone()).or_default().extend(json_vars(vars));
+        }
+    }
+    link_top_groups(&mut inv);
+    Ok(inv)
+}
+
+fn json_vars(value: &serde_json::Value) -> HashMap<String, String> {
+    let mut vars = HashMap::new();
+    if let Some(map) = value.as_object() {
+        for (k, v) in map {
+            let v = match v {
+                serde_json::Value::String(s) => s.clone(),
+                serde_json::Value::Number(n) => n.to_string(),
+                serde_json::Value::Bool(b) => b.to_string(),
+                _ => continue,
+            };
+            vars.insert(k.clone(), v);
+        }
+    }
+    vars
+}
+
+fn link_top_groups(inv: &mut Inventory) {
+    let mut referenced: Vec<String> = inv.groups.values().flat_map(|g| g.children.clone()).collect();
+    referenced.push("all".to_string());
+    let top: Vec<String> = inv.groups.keys().filter(|g| !referenced.contains(g)).cloned().collect();
+    let all = inv.group("all");
+    for g in top {
+        if !all.children.contains(&g) {
+            all.children.push(g);
+        }
+    }
+}
+
+fn expand_host_range(pattern: &str) -> Vec<String> {
+    let (start, end) = match (pattern.find('['), pattern.find(']')) {
+        (Some(s), Some(e)) if s < e => (s, e),
+        _ => return vec![pattern.to_string()],
+    };
+    let (prefix, range, suffix) = (&pattern[..start], &pattern[start + 1..end], &pattern[end + 1..]);
+    // [from:to] or [from:to:step]
+    let mut bounds = range.splitn(3, ':');
+    let (from, to) = match (bounds.next(), bounds.next()) {
+        (Some(from), Some(to)) => (from, to),
+        _ => return vec![pattern.to_string()],
+    };
+    let step = match bounds.next().map(|s| s.parse::<usize>()) {
+        None => 1,
+        Some(Ok(step)) if step > 0 => step,
+        Some(_) => return vec![pattern.to_string()],
+    };
+    let mut hosts = Vec::new();
+    if let (Ok(a), Ok(b)) = (from.parse::<u32>(), to.parse::<u32>()) {
+        for n in (a..=b).step_by(step) {
+            for rest in expand_host_range(suffix) {
+                hosts.push(format!("{}{:0width$}{}", prefix, n, rest, width = from.len()));
+            }
+        }
+    } else if let (Some(a), Some(b)) = (from.chars().next(), to.chars().next()) {
+        for c in (a..=b).step_by(step) {
+            for rest in expand_host_range(suffix) {
+                hosts.push(format!("{}{}{}", prefix, c, rest));
+            }
+        }
+    }
+    hosts
+}
+
+fn split_args(line: &str) -> Vec<String> {
+    let mut args = Vec::new();
+    let mut current = String::new();
+    let mut quote: Option<char> = None;
+    for c in line.chars() {
+        match (c, quote) {
+            ('"', None) | ('\'', None) => {
+                quote = Some(c);
+                current.push(c);
+            }
+            (c, Some(q)) if c == q => {
+                quote = None;
+                current.push(c);
+            }
+            (c, None) if c.is_whitespace() => {
+                if !current.is_empty() {
+                    args.push(std::mem::take(&mut current));
+                }
+            }
+            (c, _) => current.push(c),
+        }
+    }
+    if !current.is_empty() {
+        args.push(current);
+    }
+    args
+}
+
+fn unquote(s: &str) -> String {
+    s.trim_matches(|c| c == '"' || c == '\'').to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, path::PathBuf};
+
+    use super::*;
+
+    fn by_name(profiles: Vec<Profile>) -> HashMap<String, Profile> {
+        profiles.into_iter().map(|p| (p.name.clone(), p)).collect()
+    }
+
+    fn fixture(name: &str, content: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("pangpang-inventory-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join(name);
+        std::fs::write(&path, content).unwrap();
+        path
+    }
+
+    #[test]
+    fn host_ranges() {
+        assert_eq!(expand_host_range("web[01:03]"), vec!["web01", "web02", "web03"]);
+        assert_eq!(expand_host_range("web[01:10:2].lan"), vec!["web01.lan", "web03.lan", "web05.lan", "web07.lan", "web09.lan"]);
+        assert_eq!(expand_host_range("db-[a:e:2]"), vec!["db-a", "db-c", "db-e"]);
+        assert_eq!(expand_host_range("r[1:2]n[1:2]"), vec!["r1n1", "r1n2", "r2n1", "r2n2"]);
+        assert_eq!(expand_host_range("plain.example.com"), vec!["plain.example.com"]);
+        assert_eq!(expand_host_range("web[1:3:0]"), vec!["web[1:3:0]"]);
+        assert_eq!(expand_host_range("web[1]"), vec!["web[1]"]);
+    }
+
+    #[test]
+    fn log_vars() {
+        let vars = |pairs: &[(&str, &str)]| -> HashMap<String, String> {
+            pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+        };
+        assert!(log_settings(&vars(&[("pangpang_log_format", "raw")])).is_none());
+        let logging = log_settings(&vars(&[
+            ("pangpang_log", "/tmp/{profile}.log"),
+            ("pangpang_log_format", "raw"),
+            ("pangpang_log_max_size", "0"),
+            ("pangpang_log_input", "yes"),
+            ("pangpang_log_timestamps", "False"),
+        ])).unwrap();
+        assert_eq!(logging.path, "/tmp/{profile}.log");
+        assert_eq!(logging.format, LogFormat::Raw);
+        assert_eq!(logging.max_size, None);
+        assert!(logging.log_input);
+        assert!(!logging.timestamps);
+        let defaults = log_settings(&vars(&[("pangpang_log", "out.log")])).unwrap();
+        assert_eq!(defaults.format, LogFormat::Text);
+        assert_eq!(defaults.max_size, LogSettings::default().max_size);
+    }
+
+    #[test]
+    fn trigger_vars() {
+        let mut vars = HashMap::new();
+        assert!(trigger_rules(&vars).is_empty());
+        vars.insert("pangpang_triggers".to_string(), r#"[{"match": "assword:", "secret": "db"}, {"match": "ERROR", "highlight": true}]"#.to_string());
+        let rules = trigger_rules(&vars);
+        assert_eq!(rules.len(), 2);
+        assert_eq!(rules[0].pattern, "assword:");
+        vars.insert("pangpang_triggers".to_string(), r#"[{"match": "x"}]"#.to_string());
+        assert!(trigger_rules(&vars).is_empty());
+    }
+
+    #[test]
+    fn ini_groups_and_vars() {
+        let inv = parse_ini(r#"
+# comment
+bastion ansible_host=10.0.0.1 ansible_user=ops
+
+[web]
+web[1:2] ansible_port=2222
+
+[db]
+db1 ansible_host=10.0.1.1 ansible_user="dba"
+
+[prod:children]
+web
+db
+
+[prod:vars]
+ansible_user=deploy
+
+[web:vars]
+ansible_user=www
+"#);
+        let profiles = by_name(inv.profiles());
+        assert_eq!(profiles.len(), 4);
+
+        let web1 = &profiles["web1"];
+        assert_eq!(web1.address, "web1");
+        assert_eq!(web1.port, 2222);
+        assert_eq!(web1.username, "www");
+        assert_eq!(web1.folder, vec!["prod", "web"]);
+        assert_eq!(web1.tags, vec!["prod", "web"]);
+
+        let db1 = &profiles["db1"];
+        assert_eq!(db1.address, "10.0.1.1");
+        assert_eq!(db1.port, 22);
+        assert_eq!(db1.username, "dba");
+        assert_eq!(db1.folder, vec!["prod", "db"]);
+
+        let bastion = &profiles["bastion"];
+        assert_eq!(bastion.username, "ops");
+        assert_eq!(bastion.folder, vec!["ungrouped"]);
+        assert!(bastion.tags.is_empty());
+    }
+
+    #[test]
+    fn ini_jump_hosts() {
+        let inv = parse_ini(r#"
+bastion ansible_user=ops
+app1 ansible_user=app ansible_ssh_common_args='-o ProxyJump=bastion'
+app2 ansible_user=app pangpang_jump=admin@gw.example.com:2200
+"#);
+        let profiles = by_name(inv.profiles());
+        assert_eq!(profiles.len(), 4);
+        assert_eq!(profiles["app1"].transport, Some(profiles["bastion"].id()));
+        let hop = &profiles["admin@gw.example.com:2200"];
+        assert_eq!(hop.address, "gw.example.com");
+        assert_eq!(hop.port, 2200);
+        assert_eq!(hop.username, "admin");
+        assert_eq!(profiles["app2"].transport, Some(hop.id()));
+    }
+
+    #[test]
+    fn yaml_groups_and_vars() {
+        let inv = parse_yaml(r#"
+all:
+  vars:
+    ansible_user: root
+  hosts:
+    lonely:
+  children:
+    prod:
+      vars:
+        ansible_port: 2200
+      children:
+        web:
+          hosts:
+            web[01:03:2]:
+              ansible_user: www
+        db:
+          hosts:
+            db1:
+              ansible_host: 10.0.1.1
+              ansible_port: 5022
+"#).unwrap();
+        let profiles = by_name(inv.profiles());
+        assert_eq!(profiles.len(), 4);
+
+        let web03 = &profiles["web03"];
+        assert_eq!(web03.username, "www");
+        assert_eq!(web03.port, 2200);
+        assert_eq!(web03.folder, vec!["prod", "web"]);
+        assert!(!profiles.contains_key("web02"));
+
+        let db1 = &profiles["db1"];
+        assert_eq!(db1.username, "root");
+        assert_eq!(db1.address, "10.0.1.1");
+        assert_eq!(db1.port, 5022);
+
+        assert_eq!(profiles["lonely"].port, 22);
+    }
+
+    #[test]
+    fn file_storage() {
+        let path = fixture("hosts.yml", "web:\n  hosts:\n    web1:\n      ansible_user: www\n");
+        let storage = InventoryStorage::new(InventorySource::File(path), Duration::from_secs(60)).unwrap();
+        let profiles: Vec<&Profile> = storage.iter().map(|(_, p)| p).collect();
+        assert_eq!(profiles.len(), 1);
+        assert_eq!(profiles[0].name, "web1");
+        assert_eq!(storage.get(&profiles[0].id()).unwrap().username, "www");
+    }
+
+    #[test]
+    fn file_reload_after_failure() {
+        let path = fixture("reload.yml", "web:\n  hosts:\n    web1:\n");
+        let touch = |secs: u64| {
+            let time = SystemTime::UNIX_EPOCH + Duration::from_secs(secs);
+            std::fs::File::options().write(true).open(&path).unwrap().set_modified(time).unwrap();
+        };
+        touch(1_000);
+        let mut storage = InventoryStorage::new(InventorySource::File(path.clone()), Duration::from_secs(60)).unwrap();
+        assert!(storage.reload().is_none());
+
+        // half written, retried until a load goes through
+        std::fs::write(&path, "web:\n  hosts: [\n").unwrap();
+        touch(2_000);
+        assert!(storage.reload().unwrap()().is_err());
+        assert!(storage.reload().unwrap()().is_err());
+
+        std::fs::write(&path, "web:\n  hosts:\n    web1:\n    web2:\n").unwrap();
+        assert!(storage.refresh().unwrap());
+        assert_eq!(storage.iter().count(), 2);
+        assert!(storage.reload().is_none());
+    }
+
+    #[cfg(unix)]
+    fn script(name: &str, body: &str) -> PathBuf {
+        use std::os::unix::fs::PermissionsExt;
+        let path = fixture(name, &format!("#!/bin/sh\n{}", body));
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+        path
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn script_list_and_meta() {
+        let path = script("inventory.sh", r#"
+[ "$1" = "--list" ] || exit 2
+cat <<'JSON'
+{
+    "web": {"hosts": ["web1", "web2"], "vars": {"ansible_user": "www"}},
+    "db": ["db1"],
+    "prod": {"children": ["web", "db"]},
+    "_meta": {"hostvars": {
+        "web2": {"ansible_host": "10.0.0.2", "ansible_port": 2222},
+        "db1": {"ansible_user": "dba"}
+    }}
+}
+JSON
+"#);
+        let mut storage = InventoryStorage::new(InventorySource::Script(path), Duration::from_secs(60)).unwrap();
+        let profiles = by_name(storage.iter().map(|(_, p)| p.clone()).collect());
+        assert_eq!(profiles.len(), 3);
+        assert_eq!(profiles["web1"].username, "www");
+        assert_eq!(profiles["web2"].address, "10.0.0.2");
+        assert_eq!(profiles["web2"].port, 2222);
+        assert_eq!(profiles["db1"].username, "dba");
+        assert_eq!(profiles["db1"].folder, vec!["prod", "db"]);
+
+        // within the interval the script is not run again
+        assert!(storage.reload().is_none());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn script_failure() {
+        let path = script("broken.sh", "echo oops >&2\nexit 1\n");
+        match InventoryStorage::new(InventorySource::Script(path), Duration::from_secs(60)) {
+            Err(errors::Error::ConfigParseError(e)) => assert!(e.contains("oops")),
+            Err(e) => panic!("unexpected error {:?}", e),
+            Ok(_) => panic!("broken script loaded"),
+        }
+    }
+}
@@ -0,0 +1,189 @@
+use std::{collections::HashMap, sync::Arc, time::Duration};
+
+use tokio::sync::Mutex;
+
+use crate::{profile::{Profile, Protocol, Inherit, TerminalSettings}, errors, session::{ssh::SshProfile, local::LocalProfile}};
+
+pub mod inventory;
+
+pub type Reload = Box<dyn FnOnce() -> Result<Vec<Profile>, errors::Error> + Send>;
+
+#[allow(clippy::ptr_arg)]
+pub trait Storage: Send + Sync {
+    fn get(&self, id: &String) -> Result<Profile, errors::Error>;
+    fn iter(&self) -> Box<dyn Iterator<Item = (&String, &Profile)> + '_>;
+    fn insert(&mut self, profile: Profile) -> Result<(), errors::Error> {
+        Err(errors::Error::ReadOnlyStorage(profile.id()))
+    }
+    fn refresh(&mut self) -> Result<bool, errors::Error> {
+        Ok(false)
+    }
+    // a reload too slow to hold the storage lock for, run it and hand the profiles to replace
+    fn reload(&mut self) -> Option<Reload> {
+        None
+    }
+    fn replace(&mut self, _profiles: Vec<Profile>) {}
+    // storages without a secret store fall back to PANGPANG_SECRET_<NAME> environment variables
+    fn secret(&self, name: &String) -> Result<String, errors::Error> {
+        let var = format!("PANGPANG_SECRET_{}", name.to_uppercase().replace(|c: char| !c.is_ascii_alphanumeric(), "_"));
+        std::env::var(&var).map_err(|_| errors::Error::ProfileNotFound(format!("secret {}", name)))
+    }
+    fn resolve(&self, id: &String) -> Result<Profile, errors::Error> {
+        let mut chain = vec![self.get(id)?];
+        while let Some(parent) = chain.last().unwrap().parent.clone() {
+            if chain.iter().any(|p| p.id == parent) {
+                return Err(errors::Error::InvalidProfile(format!("inheritance cycle at {}", parent)));
+            }
+            chain.push(self.get(&parent)?);
+        }
+        let mut resolved = chain.pop().unwrap();
+        while let Some(mut child) = chain.pop() {
+            child.inherit_from(&resolved);
+            resolved = child;
+        }
+        Ok(resolved)
+    }
+}
+
+// picks the storage from command line flags, the mock storage when there are none
+pub fn open(flag: Option<&str>, path: Option<&String>) -> Result<Arc<Mutex<dyn Storage>>, errors::Error> {
+    use inventory::{InventoryStorage, InventorySource};
+    use crate::import::openssh;
+    let interval = Duration::from_secs(60);
+    match (flag, path) {
+        (Some("--inventory"), Some(path)) => InventoryStorage::new(InventorySource::File(path.into()), interval)
+            .map(|s| Arc::new(Mutex::new(s)) as _),
+        (Some("--inventory-script"), Some(path)) => InventoryStorage::new(InventorySource::Script(path.into()), interval)
+            .map(|s| Arc::new(Mutex::new(s)) as _),
+        (Some("--ssh-config"), _) => openssh::OpenSshStorage::new(openssh::default_config_path())
+            .map(|s| Arc::new(Mutex::new(s)) as _),
+        _ => Ok(Arc::new(Mutex::new(MockStorage::new()))),
+    }
+}
+
+pub fn migrate_transport(storage: &dyn Storage, profile: &mut Profile) {
+    let transport = match &profile.transport {
+        Some(t) if storage.get(t).is_err() => t.clone(),
+        _ => return,
+    };
+    if let Some((id, _)) = storage.iter().find(|(_, p)| p.target() == transport) {
+        log::info!("migrate transport of {} from {} to {}", profile.name, transport, id);
+        profile.transport = Some(id.clone());
+    }
+}
+
+pub struct MockStorage {
+    map: HashMap<String, Profile>,
+}
+
+impl Default for MockStorage {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl MockStorage {
+    pub fn new() -> Self {
+        let mut s = Self {
+            map: HashMap::new(),
+        };
+
+        let template = Profile {
+            id: Profile::stable_id("dev template"),
+            name: "dev template".to_string(),
+            folder: vec!["templates".to_string()],
+            tags: Vec::new(),
+            notes: String::new(),
+            username: "root".to_string(),
+            address: String::new(),
+            port: 22,
+            transport: None,
+            protocol: Protocol::Ssh(SshProfile{
+                password: "123456".to_string(),
+                ..Default::default()
+            }),
+            terminal: TerminalSettings::default(),
+            parent: None,
+            inherit: Inherit::empty(),
+            template: true,
+        };
+        let profile_1 = Profile {
+            id: Profile::stable_id("localhost"),
+            name: "localhost".to_string(),
+            folder: vec!["local".to_string()],
+            tags: vec!["dev".to_string()],
+            notes: String::new(),
+            username: String::new(),
+            address: "localhost".to_string(),
+            port: 22,
+            transport: None,
+            protocol: Protocol::Ssh(SshProfile::default()),
+            terminal: TerminalSettings::default(),
+            parent: Some(template.id()),
+            inherit: Inherit::USERNAME | Inherit::AUTH | Inherit::TERMINAL,
+            template: false,
+        };
+        let profile_2 = Profile {
+            id: Profile::stable_id("localhost:8022"),
+            name: "localhost:8022".to_string(),
+            folder: vec!["local".to_string(), "jump".to_string()],
+            tags: vec!["dev".to_string(), "jump".to_string()],
+            notes: "reached through localhost:22".to_string(),
+            username: "root".to_string(),
+            address: "localhost".to_string(),
+            port: 8022,
+            transport: Some(profile_1.id()),
+            protocol: Protocol::Ssh(SshProfile{
+                password: "123456".to_string(),
+                ..Default::default()
+            }),
+            terminal: TerminalSettings::default(),
+            parent: None,
+            inherit: Inherit::empty(),
+            template: false,
+        };
+
+        let local = Profile {
+            id: Profile::stable_id("local shell"),
+            name: "local shell".to_string(),
+            folder: vec!["local".to_string()],
+            tags: vec!["local".to_string()],
+            notes: String::new(),
+            username: String::new(),
+            address: String::new(),
+            port: 0,
+            transport: None,
+            protocol: Protocol::Local(LocalProfile::default()),
+            terminal: TerminalSettings::default(),
+            parent: None,
+            inherit: Inherit::empty(),
+            template: false,
+        };
+
+        s.map.insert(template.id(), template);
+        s.map.insert(profile_1.id(), profile_1);
+        s.map.insert(profile_2.id(), profile_2);
+        s.map.insert(local.id(), local);
+        
+        s
+    }
+}
+
+impl Storage for MockStorage {
+    fn get(&self, id: &String) -> Result<Profile, errors::Error> {
+        if let Some(p) = self.map.get(id) {
+            Ok(p.clone())
+        } else {
+            Err(errors::Error::ProfileNotFound(id.to_owned()))
+        }
+    }
+
+    fn iter(&self) -> Box<dyn Iterator<Item = (&String, &Profile)> + '_> {
+        Box::new(self.map.iter())
+    }
+
+    fn insert(&mut self, mut profile: Profile) -> Result<(), errors::Error> {
+        migrate_transport(self, &mut profile);
+        self.map.insert(profile.id(), profile);
+        Ok(())
+    }
+}
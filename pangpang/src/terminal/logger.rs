@@ -0,0 +1,263 @@
+use std::{fs::{File, OpenOptions}, io::{BufWriter, Write}, path::PathBuf};
+
+use chrono::{DateTime, Local};
+
+use crate::{errors, profile::Profile};
+
+use super::text::{Line, TextLines};
+
+
+
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LogFormat {
+    // bytes exactly as read from the pty, escape sequences included
+    Raw,
+    // printable text only, one line per terminal line
+    Text,
+}
+
+#[derive(Clone)]
+pub struct LogSettings {
+    // {profile}, {id}, {date} and {time} are replaced when the terminal opens
+    pub path: String,
+    pub format: LogFormat,
+    // a new part (.1, .2, ...) is started once a file reaches this size
+    pub max_size: Option<u64>,
+    // typed lines are written to a second file next to the output log
+    pub log_input: bool,
+    // prefix text lines with the local time, raw logs stay byte exact
+    pub timestamps: bool,
+}
+
+impl Default for LogSettings {
+    fn default() -> Self {
+        Self {
+            path: "~/pangpang-logs/{profile}-{date}-{time}.log".to_string(),
+            format: LogFormat::Text,
+            max_size: Some(10 << 20),
+            log_input: false,
+            timestamps: true,
+        }
+    }
+}
+
+pub struct SessionLogger {
+    output: LogFile,
+    // None for raw logs
+    text: Option<LineBuffer>,
+    input: Option<(LogFile, LineBuffer)>,
+}
+
+impl SessionLogger {
+    pub fn open(settings: &LogSettings, profile: &Profile) -> Result<Self, errors::Error> {
+        let path = expand(&settings.path, profile, &Local::now());
+        let output = LogFile::create(path.clone(), settings.max_size)?;
+        let text = match settings.format {
+            LogFormat::Raw => None,
+            LogFormat::Text => Some(LineBuffer::new(settings.timestamps)),
+        };
+        let input = if settings.log_input {
+            let mut input_path = path.into_os_string();
+            input_path.push(".input");
+            Some((LogFile::create(input_path.into(), settings.max_size)?, LineBuffer::input(settings.timestamps)))
+        } else {
+            None
+        };
+        log::info!("logging terminal of {} to {}", profile.id, output.path.display());
+        Ok(Self { output, text, input })
+    }
+
+    pub fn output(&mut self, data: &[u8]) -> std::io::Result<()> {
+        match &mut self.text {
+            Some(text) => {
+                for line in text.advance(data, b'\n') {
+                    self.output.write(&line)?;
+                }
+            }
+            None => self.output.write(data)?,
+        }
+        self.output.flush()
+    }
+
+    pub fn input(&mut self, data: &[u8]) -> std::io::Result<()> {
+        if let Some((file, text)) = &mut self.input {
+            // enter sends a carriage return
+            for line in text.advance(data, b'\r') {
+                file.write(&line)?;
+            }
+            file.flush()?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for SessionLogger {
+    fn drop(&mut self) {
+        if let Some(line) = self.text.as_mut().and_then(|t| t.finish()) {
+            let _ = self.output.write(&line);
+        }
+        let _ = self.output.flush();
+        if let Some((file, text)) = &mut self.input {
+            if let Some(line) = text.finish() {
+                let _ = file.write(&line);
+            }
+            let _ = file.flush();
+        }
+    }
+}
+
+struct LogFile {
+    path: PathBuf,
+    part: usize,
+    file: BufWriter<File>,
+    size: u64,
+    max_size: Option<u64>,
+}
+
+impl LogFile {
+    fn create(path: PathBuf, max_size: Option<u64>) -> Result<Self, errors::Error> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let size = file.metadata()?.len();
+        Ok(Self { path, part: 0, file: BufWriter::new(file), size, max_size })
+    }
+
+    fn write(&mut self, data: &[u8]) -> std::io::Result<()> {
+        if matches!(self.max_size, Some(max) if self.size > 0 && self.size + data.len() as u64 > max) {
+            self.rotate()?;
+        }
+        self.file.write_all(data)?;
+        self.size += data.len() as u64;
+        Ok(())
+    }
+
+    fn rotate(&mut self) -> std::io::Result<()> {
+        self.file.flush()?;
+        self.part += 1;
+        let mut path = self.path.clone().into_os_string();
+        path.push(format!(".{}", self.part));
+        self.file = BufWriter::new(OpenOptions::new().create(true).append(true).open(&path)?);
+        self.size = 0;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.file.flush()
+    }
+}
+
+// ansi stripped lines, prefixed with their start time when asked
+struct LineBuffer {
+    lines: TextLines,
+    timestamps: bool,
+}
+
+impl LineBuffer {
+    fn new(timestamps: bool) -> Self {
+        Self { lines: TextLines::new(), timestamps }
+    }
+
+    fn input(timestamps: bool) -> Self {
+        Self { lines: TextLines::input(), timestamps }
+    }
+
+    fn advance(&mut self, data: &[u8], eol: u8) -> Vec<Vec<u8>> {
+        let lines = self.lines.advance(data, eol);
+        lines.into_iter().map(|l| self.format(l)).collect()
+    }
+
+    fn finish(&mut self) -> Option<Vec<u8>> {
+        let line = self.lines.finish()?;
+        Some(self.format(line))
+    }
+
+    fn format(&self, line: Line) -> Vec<u8> {
+        let mut out = Vec::with_capacity(line.text.len() + 24);
+        if self.timestamps {
+            out.extend_from_slice(line.started.format("[%Y-%m-%d %H:%M:%S] ").to_string().as_bytes());
+        }
+        out.extend_from_slice(&line.text);
+        out.push(b'\n');
+        out
+    }
+}
+
+fn expand(template: &str, profile: &Profile, now: &DateTime<Local>) -> PathBuf {
+    let name = if profile.name.is_empty() { profile.target() } else { profile.name.clone() };
+    let name: String = name.chars()
+        .map(|c| if c.is_alphanumeric() || "-_.@".contains(c) { c } else { '_' })
+        .collect();
+    let path = template
+        .replace("{profile}", &name)
+        .replace("{id}", &profile.id)
+        .replace("{date}", &now.format("%Y-%m-%d").to_string())
+        .replace("{time}", &now.format("%H%M%S").to_string());
+    match path.strip_prefix("~/") {
+        Some(rest) => crate::import::home_dir().join(rest),
+        None => PathBuf::from(path),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    fn dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("pangpang-logger-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn read(path: PathBuf) -> String {
+        std::fs::read_to_string(path).unwrap()
+    }
+
+    #[test]
+    fn expand_template() {
+        let mut profile = crate::import::ssh_profile("web: prod/1".to_string(), Vec::new(), "root".to_string(), "web".to_string(), 22);
+        profile.id = "abc".to_string();
+        let now = Local.with_ymd_and_hms(2021, 3, 4, 5, 6, 7).unwrap();
+        assert_eq!(expand("/logs/{profile}-{id}-{date}-{time}.log", &profile, &now), PathBuf::from("/logs/web__prod_1-abc-2021-03-04-050607.log"));
+        profile.name.clear();
+        assert_eq!(expand("~/{profile}.log", &profile, &now), crate::import::home_dir().join("root@web_22.log"));
+    }
+
+    #[test]
+    fn rotation() {
+        let path = dir("rotation").join("out.log");
+        let mut file = LogFile::create(path.clone(), Some(8)).unwrap();
+        for chunk in [&b"12345"[..], b"678", b"abcdefghijk", b"x"] {
+            file.write(chunk).unwrap();
+        }
+        file.flush().unwrap();
+        // a chunk larger than the limit still goes to one part
+        assert_eq!(read(path.clone()), "12345678");
+        assert_eq!(read(path.with_extension("log.1")), "abcdefghijk");
+        assert_eq!(read(path.with_extension("log.2")), "x");
+    }
+
+    #[test]
+    fn text_and_input() {
+        let dir = dir("text");
+        let settings = LogSettings {
+            path: dir.join("{id}.log").to_string_lossy().into_owned(),
+            format: LogFormat::Text,
+            max_size: None,
+            log_input: true,
+            timestamps: false,
+        };
+        let mut profile = crate::import::ssh_profile(String::new(), Vec::new(), String::new(), "host".to_string(), 22);
+        profile.id = "session".to_string();
+        let mut logger = SessionLogger::open(&settings, &profile).unwrap();
+        logger.output(b"\x1b[32m$\x1b[0m ls\r\nfile\r\n$ ").unwrap();
+        logger.input(b"lx\x7fs\r").unwrap();
+        drop(logger);
+        assert_eq!(read(dir.join("session.log")), "$ ls\nfile\n$ \n");
+        assert_eq!(read(dir.join("session.log.input")), "ls\n");
+    }
+}
@@ -2,23 +2,67 @@
 
 
 
-use alacritty_terminal::{event::EventListener, term::RenderableContent};
+use std::sync::{Arc, atomic::{AtomicBool, Ordering}};
 
+use alacritty_terminal::{event::{Event, EventListener}, term::RenderableContent};
+
+#[allow(clippy::module_inception)]
 mod terminal;
+mod osc7;
+mod search;
+mod text;
+mod logger;
+mod trigger;
+pub use trigger::{TriggerAction, TriggerEngine, TriggerRule, TriggerSource};
+mod recorder;
+mod player;
+pub use recorder::default_recording_path;
+pub use player::{Player, PlaybackState};
+pub use logger::{LogFormat, LogSettings, SessionLogger};
+pub use search::SearchState;
 pub use terminal::Terminal;
 pub mod msg;
 pub use tokio::sync::mpsc::channel;
 
-pub struct TerminalEventListener;
+#[derive(Clone, Default)]
+pub struct TerminalEventListener {
+    bell: Arc<AtomicBool>,
+}
+
+impl TerminalEventListener {
+    // true once for every run of bells since the last call
+    pub fn take_bell(&self) -> bool {
+        self.bell.swap(false, Ordering::Relaxed)
+    }
+}
+
 impl EventListener for TerminalEventListener {
-    //fn send_event(&self, event: alacritty_terminal::event::Event) {
-    //    println!("event listener: {:?}", event);
-    //}
+    fn send_event(&self, event: Event) {
+        if let Event::Bell = event {
+            self.bell.store(true, Ordering::Relaxed);
+        }
+    }
 }
 
 pub type TerminalRender<'a> = RenderableContent<'a>;
 pub trait Render: Send + Sync {
     fn draw(&mut self, render: TerminalRender);
+    // serial of the session the pty belongs to, several terminals can share one
+    fn session(&mut self, _serial: u64) {}
+    // reported by the shell through OSC 7
+    fn working_directory(&mut self, _cwd: String) {}
+    // called before draw whenever the search result may have moved
+    fn search(&mut self, _state: &SearchState) {}
+    // path of the running asciicast recording
+    fn recording(&mut self, _path: Option<String>) {}
+    fn playback(&mut self, _state: &PlaybackState) {}
+    // raised by a trigger, with the line that matched
+    fn notify(&mut self, _message: String, _line: String) {}
+    // grid lines a trigger marked, negative ones are in the scrollback
+    fn highlights(&mut self, _lines: &[i32]) {}
+    fn bell(&mut self) {}
+    // the pty is gone, with the error when it did not end by itself
+    fn closed(&mut self, _error: Option<String>) {}
 }
 
 
@@ -12,5 +12,20 @@ pub enum PpTerminalMessage {
     Scroll(i32),
     SelectionStart(i32, usize),
     SelectionUpdate(i32, usize),
-    Copy(i32, usize)
+    Copy(i32, usize),
+    Break,
+    // pattern, is regex; an empty pattern ends the search
+    Search(String, bool),
+    SearchNext,
+    SearchPrevious,
+    // start recording to the path, or stop with None
+    Record(Option<std::path::PathBuf>),
+    Playback(PlaybackControl),
+}
+
+pub enum PlaybackControl {
+    Pause,
+    Resume,
+    Seek(std::time::Duration),
+    Speed(f32),
 }
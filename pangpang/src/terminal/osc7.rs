@@ -0,0 +1,67 @@
+// shells report their working directory with OSC 7: ESC ] 7 ; file://host/path BEL
+// alacritty ignores that sequence, so the raw pty stream is scanned for it here
+
+const MAX_LEN: usize = 4096;
+
+enum State {
+    Ground,
+    Escape,
+    Osc,
+    OscEscape,
+}
+
+pub struct CwdTracker {
+    state: State,
+    buffer: Vec<u8>,
+}
+
+impl CwdTracker {
+    pub fn new() -> Self {
+        Self { state: State::Ground, buffer: Vec::new() }
+    }
+
+    // returns the last directory reported in the given bytes
+    pub fn advance(&mut self, bytes: &[u8]) -> Option<String> {
+        let mut cwd = None;
+        for byte in bytes {
+            self.state = match (&self.state, *byte) {
+                (State::Ground, 0x1b) => State::Escape,
+                (State::Ground, _) => State::Ground,
+                (State::Escape, b']') => {
+                    self.buffer.clear();
+                    State::Osc
+                }
+                (State::Escape, 0x1b) => State::Escape,
+                (State::Escape, _) => State::Ground,
+                (State::Osc, 0x07) => {
+                    cwd = self.finish().or(cwd);
+                    State::Ground
+                }
+                (State::Osc, 0x1b) => State::OscEscape,
+                (State::Osc, b) if self.buffer.len() < MAX_LEN => {
+                    self.buffer.push(b);
+                    State::Osc
+                }
+                (State::Osc, _) => State::Ground,
+                (State::OscEscape, b'\\') => {
+                    cwd = self.finish().or(cwd);
+                    State::Ground
+                }
+                (State::OscEscape, b']') => {
+                    self.buffer.clear();
+                    State::Osc
+                }
+                (State::OscEscape, _) => State::Ground,
+            };
+        }
+        cwd
+    }
+
+    fn finish(&mut self) -> Option<String> {
+        let url = std::str::from_utf8(self.buffer.strip_prefix(b"7;")?).ok()?;
+        let path = url.strip_prefix("file://")?;
+        // skip the host part, the path starts at the first slash
+        let path = &path[path.find('/')?..];
+        Some(crate::import::percent_decode(path))
+    }
+}
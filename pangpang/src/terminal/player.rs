@@ -0,0 +1,287 @@
+use std::{path::Path, sync::Arc, time::{Duration, Instant}};
+
+use alacritty_terminal::{
+    ansi::Processor,
+    config::MockConfig,
+    grid,
+    index::{Column, Line, Point, Side},
+    selection::{Selection, SelectionType},
+    term::SizeInfo,
+    Term,
+};
+use clipboard::ClipboardProvider;
+use tokio::sync::Mutex;
+
+use crate::errors;
+
+use super::{TerminalEventListener, Render, msg::{PpTerminalMessage, PpTerminalMessageReceiver, PlaybackControl}};
+
+
+
+
+enum EventKind {
+    Output(Vec<u8>),
+    Resize(usize, usize),
+}
+
+struct Event {
+    time: f64,
+    kind: EventKind,
+}
+
+#[derive(Clone, Debug)]
+pub struct PlaybackState {
+    pub position: Duration,
+    pub duration: Duration,
+    pub paused: bool,
+    pub speed: f32,
+}
+
+// replays an asciicast v2 recording, input is ignored
+pub struct Player {
+    events: Vec<Event>,
+    size: (usize, usize),
+    cfg: Arc<MockConfig>,
+    input: PpTerminalMessageReceiver,
+    ui_render: Arc<Mutex<dyn Render>>,
+    term: Term<TerminalEventListener>,
+    processor: Processor,
+    // opened on the first copy, playing needs no display
+    clipboard: Option<clipboard::ClipboardContext>,
+    // next event to apply
+    next: usize,
+    // recording time at `anchor`
+    position: f64,
+    anchor: Instant,
+    paused: bool,
+    speed: f32,
+}
+
+impl Player {
+    pub fn open(path: &Path, input: PpTerminalMessageReceiver, ui_render: Arc<Mutex<dyn Render>>) -> Result<Self, errors::Error> {
+        let content = std::fs::read_to_string(path)?;
+        let mut lines = content.lines();
+        let header: serde_json::Value = serde_json::from_str(lines.next().unwrap_or_default())
+            .map_err(|e| errors::Error::ConfigParseError(format!("{}: bad asciicast header: {}", path.display(), e)))?;
+        if header["version"].as_u64() != Some(2) {
+            return Err(errors::Error::Unsupported(format!("{}: only asciicast v2 can be played", path.display())));
+        }
+        let size = (
+            header["width"].as_u64().unwrap_or(80) as usize,
+            header["height"].as_u64().unwrap_or(24) as usize,
+        );
+        let mut events = Vec::new();
+        for line in lines.filter(|l| !l.trim().is_empty()) {
+            let (time, code, data): (f64, String, String) = serde_json::from_str(line)
+                .map_err(|e| errors::Error::ConfigParseError(format!("{}: bad asciicast event {}: {}", path.display(), line, e)))?;
+            let kind = match code.as_str() {
+                "o" => EventKind::Output(data.into_bytes()),
+                "r" => match data.split_once('x').and_then(|(w, h)| Some((w.parse().ok()?, h.parse().ok()?))) {
+                    Some((w, h)) => EventKind::Resize(w, h),
+                    None => continue,
+                },
+                // input and markers do not change the screen
+                _ => continue,
+            };
+            events.push(Event { time, kind });
+        }
+        let cfg = Arc::new(MockConfig::default());
+        Ok(Self {
+            term: Term::new(&cfg, size_info(size), TerminalEventListener::default()),
+            events, size, cfg, input, ui_render,
+            processor: Processor::new(),
+            clipboard: None,
+            next: 0,
+            position: 0.0,
+            anchor: Instant::now(),
+            paused: false,
+            speed: 1.0,
+        })
+    }
+
+    pub async fn run(&mut self) -> Result<(), errors::Error> {
+        self.draw().await;
+        loop {
+            let wait = match self.events.get(self.next) {
+                Some(e) if !self.paused => Some(Duration::from_secs_f64(((e.time - self.now()) / self.speed as f64).max(0.0))),
+                _ => None,
+            };
+            tokio::select! {
+                _ = tokio::time::sleep(wait.unwrap_or_default()), if wait.is_some() => {
+                    // everything that is due, so fast recordings do not lag behind
+                    let now = self.now();
+                    while matches!(self.events.get(self.next), Some(e) if e.time <= now) {
+                        self.apply(self.next);
+                        self.next += 1;
+                    }
+                    self.position = now;
+                    self.anchor = Instant::now();
+                    self.draw().await;
+                }
+                m = self.input.recv() => {
+                    match m {
+                        Some(msg) => self.handle_input(msg).await,
+                        None => return Ok(()),
+                    }
+                }
+            }
+        }
+    }
+
+    fn now(&self) -> f64 {
+        if self.paused {
+            self.position
+        } else {
+            self.position + self.anchor.elapsed().as_secs_f64() * self.speed as f64
+        }
+    }
+
+    fn duration(&self) -> f64 {
+        self.events.last().map(|e| e.time).unwrap_or(0.0)
+    }
+
+    fn apply(&mut self, i: usize) {
+        match &self.events[i].kind {
+            EventKind::Output(data) => {
+                for byte in data {
+                    self.processor.advance(&mut self.term, *byte);
+                }
+            }
+            EventKind::Resize(w, h) => self.term.resize(size_info((*w, *h))),
+        }
+    }
+
+    fn seek(&mut self, target: f64) {
+        let target = target.max(0.0).min(self.duration());
+        // the screen can not be rewound, replay from the start instead
+        if target < self.position {
+            self.term = Term::new(&self.cfg, size_info(self.size), TerminalEventListener::default());
+            self.processor = Processor::new();
+            self.next = 0;
+        }
+        while matches!(self.events.get(self.next), Some(e) if e.time <= target) {
+            self.apply(self.next);
+            self.next += 1;
+        }
+        self.position = target;
+        self.anchor = Instant::now();
+    }
+
+    async fn draw(&mut self) {
+        let state = PlaybackState {
+            position: Duration::from_secs_f64(self.now().min(self.duration())),
+            duration: Duration::from_secs_f64(self.duration()),
+            paused: self.paused || self.next >= self.events.len(),
+            speed: self.speed,
+        };
+        let mut render = self.ui_render.lock().await;
+        render.playback(&state);
+        render.draw(self.term.renderable_content());
+    }
+
+    async fn handle_input(&mut self, msg: PpTerminalMessage) {
+        match msg {
+            PpTerminalMessage::Playback(control) => match control {
+                PlaybackControl::Pause => {
+                    self.position = self.now();
+                    self.paused = true;
+                }
+                PlaybackControl::Resume => {
+                    // resuming at the end starts over
+                    if self.next >= self.events.len() {
+                        self.seek(0.0);
+                    } else if self.paused {
+                        self.anchor = Instant::now();
+                    }
+                    self.paused = false;
+                }
+                PlaybackControl::Seek(target) => self.seek(target.as_secs_f64()),
+                PlaybackControl::Speed(speed) => {
+                    self.position = self.now();
+                    self.anchor = Instant::now();
+                    self.speed = speed.max(0.1);
+                }
+            },
+            PpTerminalMessage::Scroll(delta) => self.term.scroll_display(grid::Scroll::Delta(delta)),
+            PpTerminalMessage::SelectionStart(line, column) => {
+                self.term.selection = Some(Selection::new(SelectionType::Simple, point(line, column), Side::Left));
+            }
+            PpTerminalMessage::SelectionUpdate(line, column) => {
+                if let Some(sr) = &mut self.term.selection {
+                    sr.update(point(line, column), Side::Left);
+                }
+            }
+            PpTerminalMessage::Copy(_, _) => {
+                // nothing to paste into, so copying is all a click does
+                if let Some(s) = self.term.selection_to_string() {
+                    if self.clipboard.is_none() {
+                        self.clipboard = ClipboardProvider::new()
+                            .map_err(|e| log::warn!("no clipboard: {:?}", e))
+                            .ok();
+                    }
+                    if let Some(Err(e)) = self.clipboard.as_mut().map(|c| c.set_contents(s)) {
+                        log::warn!("copy failed: {:?}", e);
+                    }
+                }
+                self.term.selection = None;
+            }
+            // the recording keeps its own size and takes no input
+            _ => return,
+        }
+        self.draw().await;
+    }
+}
+
+fn point(line: i32, column: usize) -> Point {
+    Point { line: Line(line), column: Column(column) }
+}
+
+fn size_info((width, height): (usize, usize)) -> SizeInfo {
+    SizeInfo::new(width as f32, height as f32, 1.0, 1.0, 0.0, 0.0, false)
+}
+
+#[cfg(test)]
+mod tests {
+    use alacritty_terminal::grid::Dimensions;
+
+    use crate::terminal::{channel, TerminalRender, recorder::Recorder};
+
+    use super::*;
+
+    struct NoRender;
+
+    impl Render for NoRender {
+        fn draw(&mut self, _render: TerminalRender) {}
+    }
+
+    #[test]
+    fn record_and_play() {
+        let dir = std::env::temp_dir().join(format!("pangpang-player-{}", std::process::id()));
+        let path = dir.join("session.cast");
+        let mut recorder = Recorder::create(&path, 20, 5, "xterm-256color").unwrap();
+        // é arrives in two reads
+        recorder.output(b"h\xc3").unwrap();
+        recorder.output(b"\xa9llo\r\n").unwrap();
+        recorder.resize(30, 6).unwrap();
+        recorder.output(b"\x1b[1mdone\x1b[0m").unwrap();
+        drop(recorder);
+
+        let (_tx, rx) = channel(1);
+        let mut player = Player::open(&path, rx, Arc::new(Mutex::new(NoRender))).unwrap();
+        assert_eq!(player.size, (20, 5));
+        let events: Vec<_> = player.events.iter().map(|e| match &e.kind {
+            EventKind::Output(data) => String::from_utf8(data.clone()).unwrap(),
+            EventKind::Resize(w, h) => format!("{}x{}", w, h),
+        }).collect();
+        assert_eq!(events, vec!["h", "\u{e9}llo\r\n", "30x6", "\x1b[1mdone\x1b[0m"]);
+
+        player.seek(player.duration());
+        assert_eq!(player.next, 4);
+        assert_eq!((player.term.columns(), player.term.screen_lines()), (30, 6));
+        let row = |line: i32| -> String {
+            let row = &player.term.grid()[Line(line)];
+            (0..4).map(|c| row[Column(c)].c).collect()
+        };
+        assert_eq!((row(0), row(1)), ("h\u{e9}ll".to_string(), "done".to_string()));
+    }
+}
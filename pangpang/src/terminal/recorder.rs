@@ -0,0 +1,79 @@
+use std::{fs::File, io::{BufWriter, Write}, path::{Path, PathBuf}, time::{Instant, SystemTime}};
+
+use chrono::Local;
+
+use crate::errors;
+
+
+
+
+// asciicast v2: a json header line, then one [time, code, data] array per event
+pub struct Recorder {
+    path: PathBuf,
+    file: BufWriter<File>,
+    start: Instant,
+    // tail of a utf-8 character split between two reads
+    pending: Vec<u8>,
+}
+
+impl Recorder {
+    pub fn create(path: &Path, width: usize, height: usize, term: &str) -> Result<Self, errors::Error> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let mut file = BufWriter::new(File::create(path)?);
+        let timestamp = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap_or_default().as_secs();
+        let header = serde_json::json!({
+            "version": 2,
+            "width": width,
+            "height": height,
+            "timestamp": timestamp,
+            "env": { "TERM": term },
+        });
+        writeln!(file, "{}", header)?;
+        file.flush()?;
+        log::info!("recording terminal to {}", path.display());
+        Ok(Self { path: path.to_owned(), file, start: Instant::now(), pending: Vec::new() })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn output(&mut self, data: &[u8]) -> std::io::Result<()> {
+        self.pending.extend_from_slice(data);
+        let valid = match std::str::from_utf8(&self.pending) {
+            Err(e) if e.error_len().is_none() => e.valid_up_to(),
+            _ => self.pending.len(),
+        };
+        let rest = self.pending.split_off(valid);
+        let text = String::from_utf8_lossy(&self.pending).into_owned();
+        self.pending = rest;
+        if text.is_empty() {
+            return Ok(());
+        }
+        self.event("o", &text)
+    }
+
+    pub fn resize(&mut self, width: usize, height: usize) -> std::io::Result<()> {
+        self.event("r", &format!("{}x{}", width, height))
+    }
+
+    fn event(&mut self, code: &str, data: &str) -> std::io::Result<()> {
+        let time = self.start.elapsed().as_secs_f64();
+        let line = serde_json::to_string(&(time, code, data))
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+        writeln!(self.file, "{}", line)?;
+        self.file.flush()
+    }
+}
+
+pub fn default_recording_path(title: &str) -> PathBuf {
+    let title: String = title.chars()
+        .map(|c| if c.is_alphanumeric() || "-_.@".contains(c) { c } else { '_' })
+        .collect();
+    let now = Local::now();
+    crate::import::home_dir()
+        .join("pangpang-recordings")
+        .join(format!("{}-{}.cast", title, now.format("%Y-%m-%d-%H%M%S")))
+}
@@ -0,0 +1,92 @@
+use alacritty_terminal::{
+    grid::Dimensions,
+    index::{Column, Direction, Line, Point},
+    term::search::{Match, RegexIter, RegexSearch},
+    Term,
+};
+
+use crate::errors;
+
+
+
+
+#[derive(Clone, Default)]
+pub struct SearchState {
+    pub matches: Vec<Match>,
+    pub focused: Option<usize>,
+    pub error: Option<String>,
+}
+
+pub struct Search {
+    dfas: RegexSearch,
+    state: SearchState,
+}
+
+impl Search {
+    pub fn new(pattern: &str, regex: bool) -> Result<Self, errors::Error> {
+        let pattern = if regex { pattern.to_string() } else { regex::escape(pattern) };
+        let dfas = RegexSearch::new(&pattern)
+            .map_err(|e| errors::Error::InvalidPattern(format!("{}: {}", pattern, e)))?;
+        Ok(Self { dfas, state: SearchState::default() })
+    }
+
+    pub fn state(&self) -> &SearchState {
+        &self.state
+    }
+
+    // searches the whole scrollback
+    pub fn update<T>(&mut self, term: &Term<T>) {
+        let focused = self.focused();
+        self.state.matches = self.scan(term, term.topmost_line());
+        self.refocus(focused);
+    }
+
+    // history lines do not change once written, so after output the matches there only
+    // move up by the scrolled lines and just the screen is searched again
+    pub fn follow<T>(&mut self, term: &Term<T>, scrolled: usize) {
+        let up = |p: &Point| Point::new(Line(p.line.0 - scrolled as i32), p.column);
+        let focused = self.focused().map(|p| up(&p));
+        let top = term.topmost_line();
+        let mut matches: Vec<Match> = self.state.matches.iter()
+            .map(|m| up(m.start())..=up(m.end()))
+            .filter(|m| m.start().line >= top && m.end().line < Line(0))
+            .collect();
+        matches.extend(self.scan(term, Line(0)));
+        self.state.matches = matches;
+        self.refocus(focused);
+    }
+
+    fn scan<T>(&self, term: &Term<T>, from: Line) -> Vec<Match> {
+        let start = Point::new(from, Column(0));
+        let end = Point::new(term.bottommost_line(), term.last_column());
+        RegexIter::new(start, end, Direction::Right, term, &self.dfas).collect()
+    }
+
+    // keeps the focus on the same text if it is still there, else on the newest match
+    fn refocus(&mut self, focused: Option<Point>) {
+        self.state.focused = match focused {
+            Some(p) => self.state.matches.iter().position(|m| *m.start() >= p),
+            None => None,
+        }
+        .or_else(|| self.state.matches.len().checked_sub(1));
+    }
+
+    // moves the focus down (forward) or up the scrollback, wrapping around
+    pub fn step(&mut self, forward: bool) -> Option<Point> {
+        let n = self.state.matches.len();
+        if n == 0 {
+            return None;
+        }
+        let next = match (self.state.focused, forward) {
+            (Some(i), true) => (i + 1) % n,
+            (Some(i), false) => (i + n - 1) % n,
+            (None, _) => n - 1,
+        };
+        self.state.focused = Some(next);
+        Some(*self.state.matches[next].start())
+    }
+
+    pub fn focused(&self) -> Option<Point> {
+        self.state.focused.and_then(|i| self.state.matches.get(i)).map(|m| *m.start())
+    }
+}
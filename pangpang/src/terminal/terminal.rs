@@ -1,13 +1,13 @@
 
 use std::sync::Arc;
 
-use alacritty_terminal::{ansi::Processor, config::MockConfig, term::SizeInfo, Term, grid};
+use alacritty_terminal::{ansi::Processor, config::MockConfig, term::SizeInfo, Term, grid::{self, Dimensions}};
 use clipboard::ClipboardProvider;
 use tokio::{io::{AsyncReadExt, AsyncWriteExt}, sync::Mutex};
 
-use crate::{session::PpPty, errors};
+use crate::{session::PpPty, errors, profile::TerminalSettings, storage::Storage};
 
-use super::{TerminalEventListener, Render, msg::{PpTerminalMessage, PpTerminalMessageReceiver}};
+use super::{TerminalEventListener, Render, SearchState, SessionLogger, TriggerAction, TriggerEngine, recorder::Recorder, trigger::Fired, osc7::CwdTracker, search::Search, msg::{PpTerminalMessage, PpTerminalMessageReceiver}};
 
 
 
@@ -20,33 +20,211 @@ pub struct Terminal {
     term: AlacrittyTerminal,
     processor: Processor,
     clipboard: clipboard::ClipboardContext,
+    cwd: CwdTracker,
+    search: Option<Search>,
+    logger: Option<SessionLogger>,
+    recorder: Option<Recorder>,
+    term_name: String,
+    size: (usize, usize),
+    triggers: Option<(TriggerEngine, Arc<Mutex<dyn Storage>>)>,
+    // grid lines marked by triggers, moved up as the output scrolls
+    highlights: Vec<i32>,
+    scrollback: usize,
+    events: TerminalEventListener,
 }
 
 
 impl Terminal {
-    pub fn new(pty: Box<dyn PpPty>, input: PpTerminalMessageReceiver, ui_render: Arc<Mutex<dyn Render>>) -> Self {
-        let cfg = Arc::new(MockConfig::default());
+    pub fn new(pty: Box<dyn PpPty>, input: PpTerminalMessageReceiver, ui_render: Arc<Mutex<dyn Render>>, settings: &TerminalSettings) -> Self {
+        let mut cfg = MockConfig::default();
+        cfg.scrolling.set_history(settings.scrollback as u32);
+        let cfg = Arc::new(cfg);
         let size = SizeInfo::new(80.0, 20.0, 1.0, 1.0, 0.0, 0.0, false);
+        let events = TerminalEventListener::default();
         Self {
             pty, input, ui_render,
-            term: AlacrittyTerminal::new(&cfg, size, TerminalEventListener),
+            term: AlacrittyTerminal::new(&cfg, size, events.clone()),
             processor: Processor::new(),
             clipboard: clipboard::ClipboardProvider::new().unwrap(),
+            cwd: CwdTracker::new(),
+            search: None,
+            logger: None,
+            recorder: None,
+            term_name: settings.term.clone(),
+            size: (80, 20),
+            triggers: None,
+            highlights: Vec::new(),
+            scrollback: settings.scrollback,
+            events,
+        }
+    }
+
+    // the storage is asked for secrets when a rule sends one
+    pub fn trigger_on(&mut self, engine: TriggerEngine, storage: Arc<Mutex<dyn Storage>>) {
+        self.triggers = Some((engine, storage));
+    }
+
+    async fn fire(&mut self, fired: Vec<Fired>) -> Result<(), errors::Error> {
+        for f in fired {
+            match f.action {
+                TriggerAction::Send(text) => {
+                    self.log(text.as_bytes(), true);
+                    self.pty.write_all(text.as_bytes()).await
+                        .map_err(|e| errors::Error::WritePtyError(format!("trigger input to pty error: {:?}", e)))?;
+                }
+                TriggerAction::SendSecret(name) => {
+                    let secret = match &self.triggers {
+                        Some((_, storage)) => storage.lock().await.secret(&name),
+                        None => continue,
+                    };
+                    // never logged
+                    match secret {
+                        Ok(secret) => self.pty.write_all(format!("{}\r", secret).as_bytes()).await
+                            .map_err(|e| errors::Error::WritePtyError(format!("trigger input to pty error: {:?}", e)))?,
+                        Err(e) => log::warn!("trigger secret {} unavailable: {:?}", name, e),
+                    }
+                }
+                TriggerAction::Highlight => self.highlight(f.rows_up).await,
+                TriggerAction::Notify(message) => self.ui_render.lock().await.notify(message, f.text),
+            }
+        }
+        Ok(())
+    }
+
+    // the ui colours the lines, the grid itself is left alone
+    async fn highlight(&mut self, rows_up: usize) {
+        let line = self.term.grid().cursor.point.line.0 - rows_up as i32;
+        if line < self.term.topmost_line().0 || self.highlights.contains(&line) {
+            return;
+        }
+        self.highlights.push(line);
+        self.ui_render.lock().await.highlights(&self.highlights);
+    }
+
+    // alacritty does not count scrolled lines, the history grows by them until it is full,
+    // after that every line feed that did not move the cursor down pushed the screen up
+    // lines the output pushed up the grid, None when the history was cleared
+    fn scrolled(&self, before: (usize, i32), data: &[u8]) -> Option<usize> {
+        let history = self.term.grid().history_size();
+        let cursor = self.term.grid().cursor.point.line.0;
+        if history < before.0 {
+            None
+        } else if history < self.scrollback {
+            Some(history - before.0)
+        } else {
+            let feeds = data.iter().filter(|b| **b == b'\n').count() as i32;
+            Some((history - before.0).max((feeds - (cursor - before.1)).max(0) as usize))
+        }
+    }
+
+    async fn follow_scroll(&mut self, scrolled: Option<usize>) {
+        if self.highlights.is_empty() {
+            return;
+        }
+        let scrolled = match scrolled {
+            Some(0) => return,
+            Some(n) => n,
+            // cleared, the marked lines are gone
+            None => {
+                self.highlights.clear();
+                self.ui_render.lock().await.highlights(&self.highlights);
+                return;
+            }
+        };
+        let top = self.term.topmost_line().0;
+        self.highlights.retain_mut(|line| {
+            *line -= scrolled as i32;
+            *line >= top
+        });
+        self.ui_render.lock().await.highlights(&self.highlights);
+    }
+
+    pub fn log_to(&mut self, logger: SessionLogger) {
+        self.logger = Some(logger);
+    }
+
+    // a broken log must not take the terminal down with it
+    fn log(&mut self, data: &[u8], input: bool) {
+        if let Some(logger) = &mut self.logger {
+            let result = if input { logger.input(data) } else { logger.output(data) };
+            if let Err(e) = result {
+                log::error!("session log failed, logging stopped: {:?}", e);
+                self.logger = None;
+            }
+        }
+    }
+
+    async fn record(&mut self, path: Option<std::path::PathBuf>) {
+        self.recorder = None;
+        if let Some(path) = path {
+            match Recorder::create(&path, self.size.0, self.size.1, &self.term_name) {
+                Ok(recorder) => self.recorder = Some(recorder),
+                Err(e) => log::error!("start recording to {} failed: {:?}", path.display(), e),
+            }
+        }
+        let path = self.recorder.as_ref().map(|r| r.path().display().to_string());
+        self.ui_render.lock().await.recording(path);
+    }
+
+    async fn record_event(&mut self, data: Option<&[u8]>) {
+        if let Some(recorder) = &mut self.recorder {
+            let result = match data {
+                Some(data) => recorder.output(data),
+                None => recorder.resize(self.size.0, self.size.1),
+            };
+            if let Err(e) = result {
+                log::error!("recording failed, stopped: {:?}", e);
+                self.recorder = None;
+                self.ui_render.lock().await.recording(None);
+            }
         }
     }
 
     pub async fn run(&mut self) -> Result<(),errors::Error> {
+        let result = self.serve().await;
+        let error = result.as_ref().err().map(|e| format!("{:?}", e));
+        self.ui_render.lock().await.closed(error);
+        result
+    }
+
+    async fn serve(&mut self) -> Result<(),errors::Error> {
         loop {
             let mut buffer = [0u8; 1500];
             tokio::select! {
                 n = self.pty.read(&mut buffer) => {
                     match n {
+                        Ok(0) => return self.exited().await,
                         Ok(len) => {
+                            self.log(&buffer[..len], false);
+                            self.record_event(Some(&buffer[..len])).await;
+                            let before = (self.term.grid().history_size(), self.term.grid().cursor.point.line.0);
                             for byte in &buffer[..len] {
                                 self.processor.advance(&mut self.term, *byte);
                             }
-                            self.ui_render.lock().await.draw(self.term.renderable_content());
+                            let scrolled = self.scrolled(before, &buffer[..len]);
+                            self.follow_scroll(scrolled).await;
+                            if let Some((engine, _)) = &mut self.triggers {
+                                let fired = engine.advance(&buffer[..len]);
+                                self.fire(fired).await?;
+                            }
+                            let cwd = self.cwd.advance(&buffer[..len]);
+                            let mut render = self.ui_render.lock().await;
+                            if self.events.take_bell() {
+                                render.bell();
+                            }
+                            if let Some(cwd) = cwd {
+                                render.working_directory(cwd);
+                            }
+                            if let Some(search) = &mut self.search {
+                                match scrolled {
+                                    Some(n) => search.follow(&self.term, n),
+                                    None => search.update(&self.term),
+                                }
+                                render.search(search.state());
+                            }
+                            render.draw(self.term.renderable_content());
                         }
+                        Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return self.exited().await,
                         Err(e) => {
                             return Err(errors::Error::ReadPtyError(format!("read pty error: {:?}", e)));
                         }
@@ -70,9 +248,22 @@ impl Terminal {
         }
     }
 
+    async fn exited(&mut self) -> Result<(), errors::Error> {
+        let msg = match self.pty.exit_status().await {
+            Some(code) => format!("\r\n[process exited with status {}]\r\n", code),
+            None => "\r\n[connection closed]\r\n".to_string(),
+        };
+        for byte in msg.bytes() {
+            self.processor.advance(&mut self.term, byte);
+        }
+        self.ui_render.lock().await.draw(self.term.renderable_content());
+        Ok(())
+    }
+
     async fn handle_input(&mut self, msg: PpTerminalMessage) -> Result<(), errors::Error> {
         match msg {
             PpTerminalMessage::Input(s) => {
+                self.log(&s, true);
                 match self.pty.write_all(s.as_slice()).await {
                     Err(e) => Err(errors::Error::WritePtyError(format!("input to pty error: {:?}", e))),
                     _ => {
@@ -85,8 +276,59 @@ impl Terminal {
             }
             PpTerminalMessage::ReSize(width, height) => {
                 self.term.resize(SizeInfo::new(width as f32, height as f32, 1.0, 1.0, 0.0, 0.0, false));
+                self.size = (width, height);
+                // lines reflow on resize, the matches have to be found again
+                if let Some(search) = &mut self.search {
+                    search.update(&self.term);
+                    self.ui_render.lock().await.search(search.state());
+                }
+                self.record_event(None).await;
                 self.pty.resize(width, height).await
             }
+            PpTerminalMessage::Record(path) => {
+                self.record(path).await;
+                Ok(())
+            }
+            PpTerminalMessage::Playback(_) => Ok(()),
+            PpTerminalMessage::Break => {
+                if let Err(e) = self.pty.send_break().await {
+                    log::warn!("send break failed: {:?}", e);
+                }
+                Ok(())
+            }
+            PpTerminalMessage::Search(pattern, regex) => {
+                self.search = None;
+                let mut render = self.ui_render.lock().await;
+                if pattern.is_empty() {
+                    render.search(&SearchState::default());
+                } else {
+                    match Search::new(&pattern, regex) {
+                        Ok(mut search) => {
+                            search.update(&self.term);
+                            if let Some(point) = search.focused() {
+                                self.term.scroll_to_point(point);
+                            }
+                            render.search(search.state());
+                            self.search = Some(search);
+                        }
+                        Err(e) => render.search(&SearchState { error: Some(format!("{:?}", e)), ..Default::default() }),
+                    }
+                }
+                render.draw(self.term.renderable_content());
+                Ok(())
+            }
+            PpTerminalMessage::SearchNext | PpTerminalMessage::SearchPrevious => {
+                let forward = matches!(msg, PpTerminalMessage::SearchNext);
+                if let Some(search) = &mut self.search {
+                    if let Some(point) = search.step(forward) {
+                        self.term.scroll_to_point(point);
+                    }
+                    let mut render = self.ui_render.lock().await;
+                    render.search(search.state());
+                    render.draw(self.term.renderable_content());
+                }
+                Ok(())
+            }
             PpTerminalMessage::Scroll(delta) => {
                 self.term.scroll_display(grid::Scroll::Delta(delta));
                 self.ui_render.lock().await.draw(self.term.renderable_content());
@@ -144,6 +386,7 @@ impl Terminal {
                     for byte in self.clipboard.get_contents().unwrap().bytes() {
                         data.push(byte);
                     }
+                    self.log(&data, true);
                     if let Err(e) = self.pty.write_all(data.as_slice()).await {
                         return Err(errors::Error::WritePtyError(format!("paste to pty error: {:?}", e)));
                     }
@@ -0,0 +1,135 @@
+use chrono::{DateTime, Local};
+
+
+
+
+enum State {
+    Ground,
+    Escape,
+    Csi,
+    // OSC, DCS and friends, ended by BEL or ST
+    Str,
+    StrEscape,
+    Charset,
+}
+
+pub struct Line {
+    pub text: Vec<u8>,
+    pub started: DateTime<Local>,
+}
+
+// strips escape sequences and control characters from a pty stream, collecting whole lines
+pub struct TextLines {
+    state: State,
+    line: Vec<u8>,
+    started: Option<DateTime<Local>>,
+    // the keyboard sends DEL for backspace, a pty echoing it back does not erase
+    del_erases: bool,
+}
+
+impl TextLines {
+    pub fn new() -> Self {
+        Self { state: State::Ground, line: Vec::new(), started: None, del_erases: false }
+    }
+
+    // for what the user typed rather than what the pty printed
+    pub fn input() -> Self {
+        Self { del_erases: true, ..Self::new() }
+    }
+
+    // the line that has not been ended yet, e.g. a prompt
+    pub fn partial(&self) -> &[u8] {
+        &self.line
+    }
+
+    pub fn advance(&mut self, data: &[u8], eol: u8) -> Vec<Line> {
+        let mut lines = Vec::new();
+        for byte in data {
+            self.state = match (&self.state, *byte) {
+                (State::Ground, 0x1b) => State::Escape,
+                (State::Ground, b) if b == eol => {
+                    lines.push(self.take());
+                    State::Ground
+                }
+                (State::Ground, b) if b == 0x08 || (b == 0x7f && self.del_erases) => {
+                    // drop a whole utf-8 character
+                    while matches!(self.line.last(), Some(b) if b & 0xc0 == 0x80) {
+                        self.line.pop();
+                    }
+                    self.line.pop();
+                    State::Ground
+                }
+                (State::Ground, b) if b == b'\t' || (b >= 0x20 && b != 0x7f) => {
+                    if self.started.is_none() {
+                        self.started = Some(Local::now());
+                    }
+                    self.line.push(b);
+                    State::Ground
+                }
+                (State::Ground, _) => State::Ground,
+                (State::Escape, b'[') => State::Csi,
+                (State::Escape, b']') | (State::Escape, b'P') | (State::Escape, b'X')
+                | (State::Escape, b'^') | (State::Escape, b'_') => State::Str,
+                (State::Escape, b'(') | (State::Escape, b')') | (State::Escape, b'*') | (State::Escape, b'+') => State::Charset,
+                (State::Escape, _) => State::Ground,
+                (State::Csi, b) if (0x40..=0x7e).contains(&b) => State::Ground,
+                (State::Csi, _) => State::Csi,
+                (State::Str, 0x07) => State::Ground,
+                (State::Str, 0x1b) => State::StrEscape,
+                (State::Str, _) => State::Str,
+                (State::StrEscape, b'\\') => State::Ground,
+                (State::StrEscape, _) => State::Str,
+                (State::Charset, _) => State::Ground,
+            };
+        }
+        lines
+    }
+
+    pub fn finish(&mut self) -> Option<Line> {
+        if self.line.is_empty() {
+            None
+        } else {
+            Some(self.take())
+        }
+    }
+
+    fn take(&mut self) -> Line {
+        Line {
+            text: std::mem::take(&mut self.line),
+            started: self.started.take().unwrap_or_else(Local::now),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn texts(lines: Vec<Line>) -> Vec<String> {
+        lines.into_iter().map(|l| String::from_utf8(l.text).unwrap()).collect()
+    }
+
+    #[test]
+    fn strips_escapes() {
+        let mut lines = TextLines::new();
+        let out = lines.advance(b"\x1b[1;31mred\x1b[0m plain\r\n\x1b]0;title\x07\x1b]2;other\x1b\\\x1b(Bboth\n", b'\n');
+        assert_eq!(texts(out), vec!["red plain", "both"]);
+        // a sequence split across reads
+        assert!(lines.advance(b"$ \x1b[3", b'\n').is_empty());
+        assert_eq!(lines.partial(), b"$ ");
+        assert_eq!(texts(lines.advance(b"2mls\x1b[m\tx", b'\n')), Vec::<String>::new());
+        assert_eq!(texts(lines.finish().into_iter().collect()), vec!["$ ls\tx"]);
+        assert!(lines.finish().is_none());
+    }
+
+    #[test]
+    fn erase() {
+        let mut output = TextLines::new();
+        assert_eq!(texts(output.advance("ab\u{e9}\x08\x08c\x7f\n".as_bytes(), b'\n')), vec!["ac"]);
+
+        let mut input = TextLines::input();
+        assert_eq!(texts(input.advance("l\u{e9}\x7f\x7fls -l\x7fa\r".as_bytes(), b'\r')), vec!["ls -a"]);
+        // nothing left to erase
+        assert_eq!(texts(input.advance(b"\x7f\x7fpwd\r", b'\r')), vec!["pwd"]);
+    }
+}
@@ -0,0 +1,273 @@
+use std::time::{Duration, Instant};
+
+use regex::{bytes, Regex};
+
+use crate::errors;
+
+use super::text::TextLines;
+
+
+
+
+// raw matches may span two reads, this much of the previous one is kept
+const RAW_TAIL: usize = 256;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TriggerSource {
+    // the ansi stripped line, matched again while it is still unfinished (prompts)
+    Line,
+    Raw,
+}
+
+#[derive(Clone, Debug)]
+pub enum TriggerAction {
+    Send(String),
+    // name of a secret in the storage
+    SendSecret(String),
+    Highlight,
+    Notify(String),
+}
+
+#[derive(Clone, Debug)]
+pub struct TriggerRule {
+    pub pattern: String,
+    pub source: TriggerSource,
+    pub action: TriggerAction,
+    // fire only the first time
+    pub once: bool,
+    // minimum time between two firings
+    pub interval: Duration,
+}
+
+impl TriggerRule {
+    // {"match": "...", "raw": false, "send" | "secret" | "notify": "...", "highlight": true, "once": false, "interval": 1}
+    pub fn from_json(value: &serde_json::Value) -> Result<Self, errors::Error> {
+        let invalid = || errors::Error::ConfigParseError(format!("invalid trigger: {}", value));
+        let text = |key: &str| value.get(key).and_then(|v| v.as_str()).map(|s| s.to_string());
+        let action = if let Some(s) = text("send") {
+            TriggerAction::Send(s)
+        } else if let Some(s) = text("secret") {
+            TriggerAction::SendSecret(s)
+        } else if let Some(s) = text("notify") {
+            TriggerAction::Notify(s)
+        } else if value.get("highlight").and_then(|v| v.as_bool()) == Some(true) {
+            TriggerAction::Highlight
+        } else {
+            return Err(invalid());
+        };
+        Ok(Self {
+            pattern: text("match").ok_or_else(invalid)?,
+            source: match value.get("raw").and_then(|v| v.as_bool()) {
+                Some(true) => TriggerSource::Raw,
+                _ => TriggerSource::Line,
+            },
+            action,
+            once: value.get("once").and_then(|v| v.as_bool()).unwrap_or(false),
+            interval: Duration::from_secs_f64(value.get("interval").and_then(|v| v.as_f64()).unwrap_or(1.0).max(0.0)),
+        })
+    }
+
+    pub fn parse_list(json: &str) -> Result<Vec<Self>, errors::Error> {
+        let value: serde_json::Value = serde_json::from_str(json)
+            .map_err(|e| errors::Error::ConfigParseError(format!("invalid trigger list: {}", e)))?;
+        value.as_array()
+            .ok_or_else(|| errors::Error::ConfigParseError("trigger list must be an array".to_string()))?
+            .iter()
+            .map(Self::from_json)
+            .collect()
+    }
+
+    // the engine skips rules whose pattern does not compile, editors check first
+    pub fn check(&self) -> Result<(), errors::Error> {
+        let compiled = match self.source {
+            TriggerSource::Line => Regex::new(&self.pattern).map(|_| ()),
+            TriggerSource::Raw => bytes::Regex::new(&self.pattern).map(|_| ()),
+        };
+        compiled.map_err(|e| errors::Error::ConfigParseError(format!("invalid trigger {}: {}", self.pattern, e)))
+    }
+}
+
+pub struct Fired {
+    pub action: TriggerAction,
+    pub text: String,
+    // rows above the cursor row the matched line ended up on
+    pub rows_up: usize,
+}
+
+enum Matcher {
+    Line(Regex),
+    Raw(bytes::Regex),
+}
+
+struct Trigger {
+    matcher: Matcher,
+    state: TriggerState,
+}
+
+struct TriggerState {
+    rule: TriggerRule,
+    last: Option<Instant>,
+    done: bool,
+    // fired on the unfinished line already, do not fire again when it completes
+    partial_hit: bool,
+}
+
+impl TriggerState {
+    fn fire(&mut self, text: String, rows_up: usize, fired: &mut Vec<Fired>) {
+        if self.done || matches!(self.last, Some(last) if last.elapsed() < self.rule.interval) {
+            return;
+        }
+        self.last = Some(Instant::now());
+        self.done = self.rule.once;
+        fired.push(Fired { action: self.rule.action.clone(), text, rows_up });
+    }
+}
+
+pub struct TriggerEngine {
+    triggers: Vec<Trigger>,
+    lines: TextLines,
+    tail: Vec<u8>,
+}
+
+impl TriggerEngine {
+    pub fn new(rules: &[TriggerRule]) -> Self {
+        let triggers = rules.iter()
+            .filter_map(|rule| {
+                let matcher = match rule.source {
+                    TriggerSource::Line => Regex::new(&rule.pattern).map(Matcher::Line),
+                    TriggerSource::Raw => bytes::Regex::new(&rule.pattern).map(Matcher::Raw),
+                };
+                match matcher {
+                    Ok(matcher) => Some(Trigger {
+                        matcher,
+                        state: TriggerState { rule: rule.clone(), last: None, done: false, partial_hit: false },
+                    }),
+                    Err(e) => {
+                        log::error!("trigger {} ignored: {}", rule.pattern, e);
+                        None
+                    }
+                }
+            })
+            .collect();
+        Self { triggers, lines: TextLines::new(), tail: Vec::new() }
+    }
+
+    pub fn advance(&mut self, data: &[u8]) -> Vec<Fired> {
+        let mut fired = Vec::new();
+        let completed = self.lines.advance(data, b'\n');
+        let partial = String::from_utf8_lossy(self.lines.partial()).into_owned();
+        let mut window = std::mem::take(&mut self.tail);
+        let old = window.len();
+        window.extend_from_slice(data);
+
+        for Trigger { matcher, state: t } in self.triggers.iter_mut() {
+            match matcher {
+                Matcher::Line(re) => {
+                    for (i, line) in completed.iter().enumerate() {
+                        if i == 0 && std::mem::take(&mut t.partial_hit) {
+                            continue;
+                        }
+                        let text = String::from_utf8_lossy(&line.text);
+                        if re.is_match(&text) {
+                            t.fire(text.into_owned(), completed.len() - i, &mut fired);
+                        }
+                    }
+                    if !completed.is_empty() {
+                        t.partial_hit = false;
+                    }
+                    if !t.partial_hit && re.is_match(&partial) {
+                        t.partial_hit = true;
+                        t.fire(partial.clone(), 0, &mut fired);
+                    }
+                }
+                Matcher::Raw(re) => {
+                    let hit = re.find_iter(&window).find(|m| m.end() > old);
+                    if let Some(m) = hit {
+                        t.fire(String::from_utf8_lossy(m.as_bytes()).into_owned(), 0, &mut fired);
+                    }
+                }
+            }
+        }
+
+        let keep = window.len().saturating_sub(RAW_TAIL);
+        self.tail = window.split_off(keep);
+        fired
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(pattern: &str, source: TriggerSource, once: bool, interval: Duration) -> TriggerRule {
+        TriggerRule {
+            pattern: pattern.to_string(),
+            source,
+            action: TriggerAction::Notify(pattern.to_string()),
+            once,
+            interval,
+        }
+    }
+
+    fn texts(fired: &[Fired]) -> Vec<(&str, usize)> {
+        fired.iter().map(|f| (f.text.as_str(), f.rows_up)).collect()
+    }
+
+    #[test]
+    fn partial_line() {
+        let mut engine = TriggerEngine::new(&[rule("assword: $", TriggerSource::Line, false, Duration::ZERO)]);
+        assert!(engine.advance(b"Last login: today\r\n").is_empty());
+        assert_eq!(texts(&engine.advance(b"\x1b[1mPass")), vec![]);
+        assert_eq!(texts(&engine.advance(b"word: \x1b[0m")), vec![("Password: ", 0)]);
+        // the prompt completing is the same hit
+        assert!(engine.advance(b"\r\n").is_empty());
+        assert_eq!(texts(&engine.advance(b"Sorry\r\nPassword: ")), vec![("Password: ", 0)]);
+    }
+
+    #[test]
+    fn completed_lines() {
+        let mut engine = TriggerEngine::new(&[rule("error", TriggerSource::Line, false, Duration::ZERO)]);
+        let fired = engine.advance(b"error one\nok\nerror two\n$ ");
+        assert_eq!(texts(&fired), vec![("error one", 3), ("error two", 1)]);
+    }
+
+    #[test]
+    fn once() {
+        let mut engine = TriggerEngine::new(&[rule("ready", TriggerSource::Line, true, Duration::ZERO)]);
+        assert_eq!(engine.advance(b"ready\nready\n").len(), 1);
+        assert!(engine.advance(b"ready\n").is_empty());
+    }
+
+    #[test]
+    fn interval() {
+        let mut slow = TriggerEngine::new(&[rule("tick", TriggerSource::Line, false, Duration::from_secs(60))]);
+        assert_eq!(slow.advance(b"tick\n").len(), 1);
+        assert!(slow.advance(b"tick\ntick\n").is_empty());
+
+        let mut fast = TriggerEngine::new(&[rule("tick", TriggerSource::Line, false, Duration::ZERO)]);
+        assert_eq!(fast.advance(b"tick\n").len(), 1);
+        assert_eq!(fast.advance(b"tick\ntick\n").len(), 2);
+    }
+
+    #[test]
+    fn raw_split_across_reads() {
+        let mut engine = TriggerEngine::new(&[rule(r"\x1b\]1337;Done\x07", TriggerSource::Raw, false, Duration::ZERO)]);
+        assert!(engine.advance(b"building\x1b]13").is_empty());
+        assert_eq!(texts(&engine.advance(b"37;Done\x07 ok")), vec![("\x1b]1337;Done\x07", 0)]);
+        // still in the kept tail, but not matched twice
+        assert!(engine.advance(b"more output").is_empty());
+    }
+
+    #[test]
+    fn invalid_rules() {
+        let rules = TriggerRule::parse_list(r#"[{"match": "(", "notify": "x"}, {"match": "ok", "send": "y\n", "once": true}]"#).unwrap();
+        assert_eq!(rules.len(), 2);
+        assert!(rules[0].check().is_err());
+        assert!(rules[1].check().is_ok());
+        assert!(matches!(rules[1].action, TriggerAction::Send(ref s) if s == "y\n"));
+        // the bad regex is dropped, the rest still fires
+        let mut engine = TriggerEngine::new(&rules);
+        assert_eq!(engine.advance(b"ok\n").len(), 1);
+        assert!(TriggerRule::parse_list(r#"[{"match": "x"}]"#).is_err());
+    }
+}
@@ -0,0 +1,140 @@
+use std::{sync::Arc, time::Duration};
+
+use eframe::egui;
+use pangpang::{
+    pangpang_run_sync::{Mutex, PpMessage, PpMsgSender, oneshot},
+    session::{CachePolicy, registry::{ChannelKind, SessionSnapshot}},
+    storage::Storage,
+};
+
+
+
+const REFRESH_INTERVAL: f64 = 1.0;
+
+pub struct ConnectionsView {
+    pub open: bool,
+    pending: Option<oneshot::Receiver<Vec<SessionSnapshot>>>,
+    sessions: Vec<SessionSnapshot>,
+    last_refresh: f64,
+    linger: u64,
+    // zero means never
+    max_idle: u64,
+    max_cached: usize,
+}
+
+impl ConnectionsView {
+    pub fn new() -> Self {
+        Self {
+            open: false,
+            pending: None,
+            sessions: Vec::new(),
+            last_refresh: f64::NEG_INFINITY,
+            linger: CachePolicy::default().linger.as_secs(),
+            max_idle: CachePolicy::default().max_idle.map(|d| d.as_secs()).unwrap_or(0),
+            max_cached: CachePolicy::default().max_cached,
+        }
+    }
+
+    pub fn show(&mut self, ctx: &egui::CtxRef, cfg: &Arc<Mutex<dyn Storage>>, sender: &PpMsgSender) {
+        if !self.open {
+            return;
+        }
+        self.refresh(ctx, sender);
+        let mut open = self.open;
+        egui::Window::new("Connections")
+            .open(&mut open)
+            .default_width(600.0)
+            .show(ctx, |ui| {
+                ui.collapsing("cache policy", |ui| self.policy_ui(ui, sender));
+                if self.sessions.is_empty() {
+                    ui.label("no open sessions");
+                }
+                egui::ScrollArea::vertical().max_height(500.0).show(ui, |ui| {
+                    for s in &self.sessions {
+                        let name = cfg.blocking_lock().get(&s.profile)
+                            .map(|p| if p.name.is_empty() { p.target() } else { p.name })
+                            .unwrap_or_else(|_| s.profile.clone());
+                        ui.horizontal(|ui| {
+                            ui.strong(&name);
+                            let cached = if s.cached { format!("cached, {} refs", s.refs) } else { "not cached".to_string() };
+                            ui.label(format!("{}  up {}  in {}  out {}", cached, uptime(s.uptime), bytes(s.bytes_in), bytes(s.bytes_out)));
+                            if ui.button("close").clicked() {
+                                let _ = sender.blocking_send(PpMessage::CloseSession(s.serial));
+                            }
+                        });
+                        ui.indent(s.serial, |ui| {
+                            for ch in &s.channels {
+                                ui.horizontal(|ui| {
+                                    let kind = match &ch.kind {
+                                        ChannelKind::Pty => "pty".to_string(),
+                                        ChannelKind::Exec(cmd) => format!("exec {}", cmd),
+                                        ChannelKind::Tunnel(host, port) => format!("tunnel {}:{}", host, port),
+                                    };
+                                    ui.label(format!("{}  up {}  in {}  out {}", kind, uptime(ch.uptime), bytes(ch.bytes_in), bytes(ch.bytes_out)));
+                                    if ui.small_button("close").clicked() {
+                                        let _ = sender.blocking_send(PpMessage::CloseChannel(ch.serial));
+                                    }
+                                });
+                            }
+                        });
+                        ui.separator();
+                    }
+                });
+            });
+        self.open = open;
+    }
+
+    fn policy_ui(&mut self, ui: &mut egui::Ui, sender: &PpMsgSender) {
+        ui.horizontal(|ui| {
+            ui.label("linger (s)");
+            ui.add(egui::DragValue::new(&mut self.linger).clamp_range(0..=86400));
+            ui.label("max idle unused (s, 0 = never)");
+            ui.add(egui::DragValue::new(&mut self.max_idle).clamp_range(0..=86400));
+            ui.label("max cached");
+            ui.add(egui::DragValue::new(&mut self.max_cached).clamp_range(1..=256));
+            if ui.button("apply").clicked() {
+                let policy = CachePolicy {
+                    linger: Duration::from_secs(self.linger),
+                    max_idle: Some(self.max_idle).filter(|s| *s > 0).map(Duration::from_secs),
+                    max_cached: self.max_cached,
+                };
+                let _ = sender.blocking_send(PpMessage::SetCachePolicy(policy));
+            }
+        });
+    }
+
+    fn refresh(&mut self, ctx: &egui::CtxRef, sender: &PpMsgSender) {
+        if let Some(rx) = &mut self.pending {
+            match rx.try_recv() {
+                Ok(sessions) => {
+                    self.sessions = sessions;
+                    self.pending = None;
+                }
+                Err(oneshot::error::TryRecvError::Empty) => {}
+                Err(oneshot::error::TryRecvError::Closed) => self.pending = None,
+            }
+        }
+        let now = ctx.input().time;
+        if self.pending.is_none() && now - self.last_refresh >= REFRESH_INTERVAL {
+            let (tx, rx) = oneshot::channel();
+            if sender.blocking_send(PpMessage::Snapshot(tx)).is_ok() {
+                self.pending = Some(rx);
+                self.last_refresh = now;
+            }
+        }
+        ctx.request_repaint();
+    }
+}
+
+fn uptime(d: Duration) -> String {
+    let s = d.as_secs();
+    format!("{:02}:{:02}:{:02}", s / 3600, s / 60 % 60, s % 60)
+}
+
+fn bytes(n: u64) -> String {
+    match n {
+        n if n >= 1 << 20 => format!("{:.1}M", n as f64 / (1 << 20) as f64),
+        n if n >= 1 << 10 => format!("{:.1}K", n as f64 / (1 << 10) as f64),
+        n => format!("{}B", n),
+    }
+}
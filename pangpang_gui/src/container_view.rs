@@ -0,0 +1,161 @@
+use std::sync::Arc;
+
+use eframe::egui;
+use pangpang::{
+    errors,
+    pangpang_run_sync::{Mutex, PpMessage, PpMsgSender, oneshot},
+    profile::{Inherit, Profile, Protocol, TerminalSettings},
+    session::container::{ContainerInfo, ContainerProfile, ContainerRuntime},
+    storage::Storage,
+};
+
+
+
+type ListReceiver = oneshot::Receiver<Result<Vec<ContainerInfo>, errors::Error>>;
+
+pub struct ContainerView {
+    pub open: bool,
+    host: Option<String>,
+    runtime: ContainerRuntime,
+    command: String,
+    pending: Option<ListReceiver>,
+    containers: Vec<ContainerInfo>,
+    message: String,
+}
+
+impl ContainerView {
+    pub fn new() -> Self {
+        Self {
+            open: false,
+            host: None,
+            runtime: ContainerRuntime::Docker,
+            command: ContainerProfile::default().command,
+            pending: None,
+            containers: Vec::new(),
+            message: String::new(),
+        }
+    }
+
+    pub fn show(&mut self, ctx: &egui::CtxRef, cfg: &Arc<Mutex<dyn Storage>>, sender: &PpMsgSender) -> Option<(String, String)> {
+        self.poll();
+        let mut open = self.open;
+        let mut opened = None;
+        egui::Window::new("Containers")
+            .open(&mut open)
+            .default_width(500.0)
+            .show(ctx, |ui| {
+                let hosts: Vec<(String, String)> = cfg.blocking_lock().iter()
+                    .filter(|(_, p)| matches!(p.protocol, Protocol::Ssh(_)) && !p.template)
+                    .map(|(id, p)| (id.clone(), if p.name.is_empty() { p.target() } else { p.name.clone() }))
+                    .collect();
+                let host_name = |id: &Option<String>| match id {
+                    Some(id) => hosts.iter().find(|(h, _)| h == id).map(|(_, n)| n.clone()).unwrap_or_else(|| id.clone()),
+                    None => "local".to_string(),
+                };
+                egui::ComboBox::from_label("host")
+                    .selected_text(host_name(&self.host))
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(&mut self.host, None, "local");
+                        for (id, name) in &hosts {
+                            ui.selectable_value(&mut self.host, Some(id.clone()), name);
+                        }
+                    });
+                egui::ComboBox::from_label("runtime")
+                    .selected_text(self.runtime.command())
+                    .show_ui(ui, |ui| {
+                        for r in ContainerRuntime::all() {
+                            ui.selectable_value(&mut self.runtime, r, r.command());
+                        }
+                    });
+                ui.horizontal(|ui| {
+                    ui.label("command");
+                    ui.text_edit_singleline(&mut self.command);
+                });
+                ui.horizontal(|ui| {
+                    let refresh = ui.add_enabled(self.pending.is_none(), egui::Button::new("Refresh"));
+                    if refresh.clicked() {
+                        let (tx, rx) = oneshot::channel();
+                        let msg = PpMessage::ListContainers(self.host.clone(), self.runtime, tx);
+                        if sender.blocking_send(msg).is_ok() {
+                            self.pending = Some(rx);
+                            self.message = "loading...".to_string();
+                        }
+                    }
+                    ui.label(&self.message);
+                });
+                let mut clicked = None;
+                egui::ScrollArea::vertical().max_height(400.0).show(ui, |ui| {
+                    for (i, c) in self.containers.iter().enumerate() {
+                        ui.horizontal(|ui| {
+                            if ui.button("exec").clicked() {
+                                clicked = Some(i);
+                            }
+                            ui.label(format!("{}  {}  {}", c.name, c.image, c.status))
+                                .on_hover_text(&c.id);
+                        });
+                    }
+                });
+                if let Some(c) = clicked.and_then(|i| self.containers.get(i)).cloned() {
+                    let host = host_name(&self.host);
+                    match self.store_profile(cfg, &c, &host) {
+                        Ok(id) => opened = Some((id, format!("{} ({})", c.name, host))),
+                        Err(e) => self.message = format!("open failed: {:?}", e),
+                    }
+                }
+            });
+        self.open = open;
+        if opened.is_some() || self.pending.is_some() {
+            ctx.request_repaint();
+        }
+        opened
+    }
+
+    fn poll(&mut self) {
+        if let Some(rx) = &mut self.pending {
+            match rx.try_recv() {
+                Ok(Ok(containers)) => {
+                    self.message = format!("{} containers", containers.len());
+                    self.containers = containers;
+                }
+                Ok(Err(e)) => {
+                    self.message = format!("list containers failed: {:?}", e);
+                    self.containers.clear();
+                }
+                Err(oneshot::error::TryRecvError::Empty) => return,
+                Err(oneshot::error::TryRecvError::Closed) => {
+                    self.message = "list containers cancelled".to_string();
+                }
+            }
+            self.pending = None;
+        }
+    }
+
+    fn store_profile(&self, cfg: &Arc<Mutex<dyn Storage>>, c: &ContainerInfo, host: &str) -> Result<String, errors::Error> {
+        let id = Profile::stable_id(&format!(
+            "container:{}:{}:{}", self.host.as_deref().unwrap_or("local"), self.runtime.command(), c.name
+        ));
+        let profile = Profile {
+            id: id.clone(),
+            name: c.name.clone(),
+            folder: vec!["containers".to_string(), host.to_string()],
+            tags: vec![self.runtime.command().to_string()],
+            notes: c.image.clone(),
+            username: String::new(),
+            address: String::new(),
+            port: 0,
+            transport: self.host.clone(),
+            protocol: Protocol::Container(ContainerProfile {
+                runtime: self.runtime,
+                container: c.name.clone(),
+                command: self.command.clone(),
+                user: None,
+            }),
+            terminal: TerminalSettings::default(),
+            parent: None,
+            inherit: Inherit::empty(),
+            template: false,
+        };
+        cfg.blocking_lock().insert(profile)?;
+        Ok(id)
+    }
+}
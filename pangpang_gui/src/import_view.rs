@@ -0,0 +1,84 @@
+use std::sync::Arc;
+
+use eframe::egui;
+use pangpang::{import::{ImportFormat, ImportReport}, pangpang_run_sync::Mutex, storage::Storage};
+
+
+
+pub struct ImportView {
+    pub open: bool,
+    path: String,
+    format: ImportFormat,
+    report: Option<ImportReport>,
+    message: String,
+}
+
+impl ImportView {
+    pub fn new() -> Self {
+        Self {
+            open: false,
+            path: String::new(),
+            format: ImportFormat::PuTTY,
+            report: None,
+            message: String::new(),
+        }
+    }
+
+    pub fn show(&mut self, ctx: &egui::CtxRef, cfg: &Arc<Mutex<dyn Storage>>) {
+        let mut open = self.open;
+        egui::Window::new("Import sessions")
+            .open(&mut open)
+            .default_width(500.0)
+            .show(ctx, |ui| {
+                egui::ComboBox::from_label("format")
+                    .selected_text(self.format.name())
+                    .show_ui(ui, |ui| {
+                        for f in ImportFormat::all() {
+                            ui.selectable_value(&mut self.format, f, f.name());
+                        }
+                    });
+                ui.horizontal(|ui| {
+                    ui.label("file");
+                    ui.text_edit_singleline(&mut self.path);
+                    if ui.button("Load").clicked() {
+                        match self.format.import(std::path::Path::new(&self.path)) {
+                            Ok(report) => {
+                                self.message = format!("{} of {} sessions can be imported", report.imported(), report.sessions.len());
+                                self.report = Some(report);
+                            }
+                            Err(e) => {
+                                self.message = format!("load failed: {:?}", e);
+                                self.report = None;
+                            }
+                        }
+                    }
+                });
+                ui.label(&self.message);
+                if let Some(report) = &self.report {
+                    egui::ScrollArea::vertical().max_height(400.0).show(ui, |ui| {
+                        for s in &report.sessions {
+                            let mut title = s.folder.clone();
+                            title.push(s.name.clone());
+                            let title = title.join("/");
+                            if s.imported {
+                                ui.label(title);
+                            } else {
+                                ui.colored_label(egui::Color32::RED, format!("{} (skipped)", title));
+                            }
+                            for u in &s.unsupported {
+                                ui.small(format!("    unsupported: {}", u));
+                            }
+                        }
+                    });
+                    if ui.button("Import").clicked() {
+                        self.message = match report.store(&mut *cfg.blocking_lock()) {
+                            Ok(n) => format!("imported {} profiles", n),
+                            Err(e) => format!("import failed: {:?}", e),
+                        };
+                        self.report = None;
+                    }
+                }
+            });
+        self.open = open;
+    }
+}
@@ -3,6 +3,16 @@
 
 mod terminal_view;
 mod tab_view;
+mod pane_view;
+mod import_view;
+mod session_tree;
+mod container_view;
+mod profile_view;
+mod session_status;
+mod connections_view;
+mod playback_view;
+mod script_view;
+mod workspace;
 
 
 use std::sync::Arc;
@@ -14,20 +24,51 @@ struct PangPang {
     cfg: Arc<pangpang::pangpang_run_sync::Mutex<dyn pangpang::storage::Storage>>,
     pp_sender: pangpang::pangpang_run_sync::PpMsgSender,
     tab_view: tab_view::TabView,
+    import_view: import_view::ImportView,
+    session_tree: session_tree::SessionTree,
+    container_view: container_view::ContainerView,
+    profile_view: profile_view::ProfileView,
+    session_status: session_status::SessionStatus,
+    connections_view: connections_view::ConnectionsView,
+    playback_view: playback_view::PlaybackView,
+    script_view: script_view::ScriptView,
+    layouts: pane_view::LayoutStore,
+    layout_name: String,
+    workspaces: workspace::Workspaces,
 }
 
 impl PangPang {
     pub fn new() -> Self {
-        let cfg = pangpang::storage::MockStorage::new();
-        let cfg = Arc::new(pangpang::pangpang_run_sync::Mutex::new(cfg));
+        let cfg = Self::storage_from_args();
+        let pp_sender = pangpang::pangpang_run_sync::run(cfg.clone());
         Self {
             ts: 0.0,
-            cfg: cfg.clone(),
-            pp_sender: pangpang::pangpang_run_sync::run(cfg),
+            cfg,
+            session_status: session_status::SessionStatus::new(&pp_sender),
+            pp_sender,
             tab_view: tab_view::TabView::new(),
+            import_view: import_view::ImportView::new(),
+            session_tree: session_tree::SessionTree::new(),
+            container_view: container_view::ContainerView::new(),
+            profile_view: profile_view::ProfileView::new(),
+            connections_view: connections_view::ConnectionsView::new(),
+            playback_view: playback_view::PlaybackView::new(),
+            script_view: script_view::ScriptView::new(),
+            layouts: pane_view::LayoutStore::new(),
+            layout_name: String::new(),
+            workspaces: workspace::Workspaces::new(),
         }
     }
 
+    fn storage_from_args() -> Arc<pangpang::pangpang_run_sync::Mutex<dyn pangpang::storage::Storage>> {
+        use pangpang::{pangpang_run_sync::Mutex, storage::MockStorage};
+        let args: Vec<String> = std::env::args().collect();
+        pangpang::storage::open(args.get(1).map(|s| s.as_str()), args.get(2)).unwrap_or_else(|e| {
+            log::error!("load storage failed: {:?}", e);
+            Arc::new(Mutex::new(MockStorage::new()))
+        })
+    }
+
     fn fps_control(&mut self, ctx: &egui::CtxRef) {
         let fps = 60.0;
         let ts = (1.0/fps) - (ctx.input().time - self.ts);
@@ -39,16 +80,104 @@ impl PangPang {
     }
 
     fn open_terminal(&mut self, id: String, title: String, frame: epi::Frame) {
+        self.open_terminal_in(id, title, None, frame);
+    }
+
+    fn open_terminal_in(&mut self, id: String, title: String, cwd: Option<String>, frame: epi::Frame) {
+        let view = Self::new_terminal_view(&self.pp_sender, &id, cwd, frame);
+        self.tab_view.insert(id, title, view);
+    }
+
+    fn new_terminal_view(sender: &pangpang::pangpang_run_sync::PpMsgSender, id: &str, cwd: Option<String>, frame: epi::Frame) -> terminal_view::TerminalView {
         let (tx, rx) = pangpang::terminal::channel(1024);
         let view = terminal_view::TerminalView::new(tx, frame);
-        self.pp_sender.blocking_send(pangpang::pangpang_run_sync::PpMessage::NewTerminal(id, rx, view.render_state.clone())).unwrap();
-        self.tab_view.insert(title, view);
+        sender.blocking_send(pangpang::pangpang_run_sync::PpMessage::NewTerminal(id.to_string(), cwd, rx, view.render_state.clone())).unwrap();
+        view
+    }
+
+    // a new pty on the profile of the focused pane, in the same directory
+    fn split_pane(&mut self, direction: pane_view::Direction, frame: epi::Frame) {
+        if let Some((id, _, cwd)) = self.tab_view.duplicate_info() {
+            let view = Self::new_terminal_view(&self.pp_sender, &id, cwd, frame);
+            self.tab_view.split(direction, id, view);
+        }
+    }
+
+    fn open_layout(&mut self, name: &str, frame: epi::Frame) {
+        if let Some(layout) = self.layouts.get(name).cloned() {
+            self.open_panes(name.to_string(), &layout, frame);
+        }
+    }
+
+    fn open_panes(&mut self, title: String, layout: &pane_view::PaneLayout, frame: epi::Frame) {
+        let (id, panes) = self.build_panes(layout, frame);
+        self.tab_view.insert_panes(id, title, panes);
+    }
+
+    // the profile of the first pane names the tab
+    fn build_panes(&self, layout: &pane_view::PaneLayout, frame: epi::Frame) -> (String, pane_view::PaneTree) {
+        let mut first = None;
+        let panes = pane_view::PaneTree::from_layout(layout, &mut |id, cwd| {
+            first.get_or_insert_with(|| id.clone());
+            Self::new_terminal_view(&self.pp_sender, id, cwd, frame.clone())
+        });
+        (first.unwrap_or_default(), panes)
+    }
+
+    fn tab_requests(&mut self, frame: epi::Frame) {
+        for request in self.tab_view.take_requests() {
+            match request {
+                tab_view::TabRequest::Duplicate(title, layout) => self.open_panes(title, &layout, frame.clone()),
+                tab_view::TabRequest::Reconnect(key, layout) => {
+                    let (_, panes) = self.build_panes(&layout, frame.clone());
+                    self.tab_view.replace_panes(key, panes);
+                }
+                tab_view::TabRequest::Switch(name, open) => {
+                    let next = self.workspaces.switch(&name, open);
+                    self.open_workspace(next, frame.clone());
+                }
+            }
+        }
+    }
+
+    // every tab reconnects, on a new or a cached session of its profile, next to the tabs still open
+    fn open_workspace(&mut self, workspace: workspace::Workspace, frame: epi::Frame) {
+        let first = self.tab_view.tab_count();
+        for tab in &workspace.tabs {
+            self.open_panes(tab.title.clone(), &tab.layout, frame.clone());
+        }
+        self.tab_view.select(first + workspace.selected);
+    }
+
+    fn workspace_action(&mut self, action: workspace::WorkspaceAction) {
+        match action {
+            // the open tabs close first, the switch waits if the user is asked about live ones
+            workspace::WorkspaceAction::Switch(name) => {
+                let open = self.tab_view.workspace();
+                self.tab_view.close_all(tab_view::TabRequest::Switch(name, open));
+            }
+            workspace::WorkspaceAction::SaveAs(name) => {
+                self.workspaces.save_as(name, self.tab_view.workspace());
+            }
+        }
+    }
+
+    fn open_player(&mut self, path: std::path::PathBuf, frame: epi::Frame) {
+        let (tx, rx) = pangpang::terminal::channel(1024);
+        let view = terminal_view::TerminalView::player(tx, frame);
+        let title = path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+        let id = format!("recording:{}", path.display());
+        self.pp_sender.blocking_send(pangpang::pangpang_run_sync::PpMessage::Play(path, rx, view.render_state.clone())).unwrap();
+        self.tab_view.insert(id, title, view);
     }
 }
 
 impl epi::App for PangPang {
     fn update(&mut self, ctx: &egui::CtxRef, frame: &epi::Frame) {
         self.fps_control(ctx);
+        if self.session_status.poll() {
+            self.tab_view.set_status(self.session_status.colors());
+        }
         egui::TopBottomPanel::top("menu_bar").show(ctx, |ui| {
             ctx.set_pixels_per_point(1.5);
             egui::menu::bar(ui, |ui| {
@@ -56,11 +185,104 @@ impl epi::App for PangPang {
                     if ui.button("New").clicked() {
                         self.pp_sender.blocking_send(pangpang::pangpang_run_sync::PpMessage::Hello).expect("unable to say hello");
                     } else if ui.button("Open").clicked() {
-                        
+
+                    } else if ui.button("Import ssh config").clicked() {
+                        let path = pangpang::import::openssh::default_config_path();
+                        match pangpang::import::openssh::import_into(&mut *self.cfg.blocking_lock(), &path) {
+                            Ok(n) => log::info!("imported {} profiles from {}", n, path.display()),
+                            Err(e) => log::error!("import {} failed: {:?}", path.display(), e),
+                        }
+                    } else if ui.button("Import sessions").clicked() {
+                        self.import_view.open = true;
+                    } else if ui.button("Play recording").clicked() {
+                        self.playback_view.open = true;
+                    } else if ui.button("Run script").clicked() {
+                        self.script_view.open = true;
+                    } else if ui.button("Containers").clicked() {
+                        self.container_view.open = true;
                     } else if ui.button("Quit").clicked() {
                         frame.quit();
                     }
                 });
+                let mut action = None;
+                egui::menu::menu_button(ui, "Workspace", |ui| {
+                    ui.label(format!("current: {}", self.workspaces.current()));
+                    action = self.workspaces.menu(ui);
+                });
+                if let Some(action) = action {
+                    self.workspace_action(action);
+                }
+                egui::menu::menu_button(ui, "View", |ui| {
+                    if ui.button("Connections").clicked() {
+                        self.connections_view.open = true;
+                    }
+                });
+                egui::menu::menu_button(ui, "Terminal", |ui| {
+                    if ui.button("Duplicate tab").clicked() {
+                        if let Some((id, title, cwd)) = self.tab_view.duplicate_info() {
+                            self.open_terminal_in(id, title, cwd, frame.clone());
+                        }
+                    }
+                    if ui.button("Split right").clicked() {
+                        self.split_pane(pane_view::Direction::Horizontal, frame.clone());
+                    }
+                    if ui.button("Split down").clicked() {
+                        self.split_pane(pane_view::Direction::Vertical, frame.clone());
+                    }
+                    if ui.button("Close pane").clicked() {
+                        self.tab_view.close_pane();
+                    }
+                    ui.horizontal(|ui| {
+                        ui.text_edit_singleline(&mut self.layout_name);
+                        let name = self.layout_name.trim().to_string();
+                        if ui.add_enabled(!name.is_empty(), egui::Button::new("Save layout")).clicked() {
+                            if let Some(layout) = self.tab_view.layout() {
+                                self.layouts.insert(name, layout);
+                            }
+                        }
+                    });
+                    let mut open = None;
+                    egui::menu::menu_button(ui, "Open layout", |ui| {
+                        for name in self.layouts.names() {
+                            ui.horizontal(|ui| {
+                                if ui.button(&name).clicked() {
+                                    open = Some(name.clone());
+                                }
+                                if ui.small_button("\u{2716}").clicked() {
+                                    self.layouts.remove(&name);
+                                }
+                            });
+                        }
+                    });
+                    if let Some(name) = open {
+                        self.open_layout(&name, frame.clone());
+                    }
+                    ui.separator();
+                    if ui.button("Find").clicked() {
+                        if let Some(view) = self.tab_view.selected_mut() {
+                            view.open_search();
+                        }
+                    }
+                    if ui.button("Start/stop recording").clicked() {
+                        self.tab_view.toggle_recording();
+                    }
+                    ui.separator();
+                    if ui.button("Broadcast input to all tabs").clicked() {
+                        self.tab_view.broadcast_all();
+                    }
+                    if ui.button("Pause/resume broadcast").clicked() {
+                        self.tab_view.toggle_broadcast_pause();
+                    }
+                    if ui.button("Stop broadcast").clicked() {
+                        self.tab_view.stop_broadcast();
+                    }
+                    ui.separator();
+                    if ui.button("Send break").clicked() {
+                        if let Some(view) = self.tab_view.selected() {
+                            view.send_break();
+                        }
+                    }
+                });
                 egui::menu::menu_button(ui, "Help", |ui| {
                     if ui.button("About").clicked() {
                         
@@ -72,13 +294,14 @@ impl epi::App for PangPang {
         .resizable(true).show(ctx, |ui| {
             ui.vertical_centered_justified(|ui| ui.heading("sessions"));
             ui.collapsing("sessions", |ui| {
-                let cfg = self.cfg.clone();
-                for (_, profile) in cfg.blocking_lock().iter() {
-                    let btn = egui::Button::new(profile.id())
-                        .frame(false)
-                        .wrap(false);
-                    if ui.add(btn).clicked() {
-                        self.open_terminal(profile.id(), profile.address.clone(), frame.clone());
+                let opened = self.session_tree.show(ui, &*self.cfg.blocking_lock(), &self.session_status);
+                if let Some((id, title)) = opened {
+                    self.open_terminal(id, title, frame.clone());
+                }
+                if let Some(id) = self.session_tree.take_edit() {
+                    match self.cfg.blocking_lock().get(&id) {
+                        Ok(profile) => self.profile_view.edit(profile),
+                        Err(e) => log::error!("edit {} failed: {:?}", id, e),
                     }
                 }
             });
@@ -88,16 +311,46 @@ impl epi::App for PangPang {
             });
             ui.collapsing("remote file manager", |ui| ui.label("..."));
         });
+        if let Some(workspace) = self.workspaces.show_prompt(ctx) {
+            self.open_workspace(workspace, frame.clone());
+        }
+        self.import_view.show(ctx, &self.cfg);
+        self.profile_view.show(ctx, &self.cfg);
+        self.connections_view.show(ctx, &self.cfg, &self.pp_sender);
+        self.script_view.show(ctx, &self.pp_sender);
+        if let Some(path) = self.playback_view.show(ctx) {
+            self.open_player(path, frame.clone());
+        }
+        if let Some((id, title)) = self.container_view.show(ctx, &self.cfg, &self.pp_sender) {
+            self.open_terminal(id, title, frame.clone());
+        }
         egui::CentralPanel::default().show(ctx, |ui| {
             ui.add(&mut self.tab_view);
         });
+        self.tab_requests(frame.clone());
     }
 
     fn name(&self) -> &str {
         "pangpang app"
     }
 
-    fn setup(&mut self, ctx: &egui::CtxRef, _frame: &epi::Frame, _storage: Option<&dyn epi::Storage>) {
+    fn save(&mut self, storage: &mut dyn epi::Storage) {
+        self.workspaces.save(storage, self.tab_view.workspace());
+        self.layouts.save(storage);
+        self.session_tree.save(storage);
+    }
+
+    // size and position of the window, kept by eframe next to the workspaces
+    fn persist_native_window(&self) -> bool {
+        true
+    }
+
+    fn setup(&mut self, ctx: &egui::CtxRef, _frame: &epi::Frame, storage: Option<&dyn epi::Storage>) {
+        if let Some(storage) = storage {
+            self.workspaces.load(storage);
+            self.layouts.load(storage);
+            self.session_tree.load(storage);
+        }
         //for non-latin
         let name = "simfang";
         let mut fd = egui::FontDefinitions::default();
@@ -0,0 +1,479 @@
+use std::collections::BTreeMap;
+
+use eframe::{egui, epi};
+use serde_json::{json, Value};
+
+use crate::terminal_view::TerminalView;
+
+const STORAGE_KEY: &str = "pangpang layouts";
+const DIVIDER: f32 = 6.0;
+const MIN_RATIO: f32 = 0.1;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    // panes side by side
+    Horizontal,
+    // panes on top of each other
+    Vertical,
+}
+
+#[derive(Clone, Copy)]
+pub enum Move {
+    Left,
+    Right,
+    Up,
+    Down,
+}
+
+// the shape of a tab, without the terminals, so it can be saved and opened again
+#[derive(Clone)]
+pub enum PaneLayout {
+    Terminal { id: String, cwd: Option<String> },
+    Split { direction: Direction, ratio: f32, first: Box<PaneLayout>, second: Box<PaneLayout> },
+}
+
+impl PaneLayout {
+    pub fn to_json(&self) -> Value {
+        match self {
+            PaneLayout::Terminal { id, cwd } => json!({ "id": id, "cwd": cwd }),
+            PaneLayout::Split { direction, ratio, first, second } => json!({
+                "split": if *direction == Direction::Horizontal { "horizontal" } else { "vertical" },
+                "ratio": ratio,
+                "first": first.to_json(),
+                "second": second.to_json(),
+            }),
+        }
+    }
+
+    pub fn from_json(value: &Value) -> Option<Self> {
+        if let Some(id) = value.get("id").and_then(Value::as_str) {
+            let cwd = value.get("cwd").and_then(Value::as_str).map(str::to_string);
+            return Some(PaneLayout::Terminal { id: id.to_string(), cwd });
+        }
+        let direction = match value.get("split")?.as_str()? {
+            "horizontal" => Direction::Horizontal,
+            "vertical" => Direction::Vertical,
+            _ => return None,
+        };
+        let ratio = value.get("ratio").and_then(Value::as_f64).unwrap_or(0.5) as f32;
+        Some(PaneLayout::Split {
+            direction,
+            ratio: ratio.clamp(MIN_RATIO, 1.0 - MIN_RATIO),
+            first: Box::new(Self::from_json(value.get("first")?)?),
+            second: Box::new(Self::from_json(value.get("second")?)?),
+        })
+    }
+}
+
+struct Pane {
+    id: String,
+    view: TerminalView,
+}
+
+// leaves are numbered depth first, the first child before the second
+enum Node<T> {
+    Leaf(T),
+    Split { direction: Direction, ratio: f32, first: Box<Node<T>>, second: Box<Node<T>> },
+}
+
+impl<T> Node<T> {
+    fn leaves(&self) -> Vec<&T> {
+        match self {
+            Node::Leaf(pane) => vec![pane],
+            Node::Split { first, second, .. } => {
+                let mut leaves = first.leaves();
+                leaves.extend(second.leaves());
+                leaves
+            }
+        }
+    }
+
+    fn leaves_mut(&mut self) -> Vec<&mut T> {
+        match self {
+            Node::Leaf(pane) => vec![pane],
+            Node::Split { first, second, .. } => {
+                let mut leaves = first.leaves_mut();
+                leaves.extend(second.leaves_mut());
+                leaves
+            }
+        }
+    }
+
+    fn count(&self) -> usize {
+        match self {
+            Node::Leaf(_) => 1,
+            Node::Split { first, second, .. } => first.count() + second.count(),
+        }
+    }
+
+    // replaces leaf `index` by a split of it and the new pane, the new pane comes second
+    fn split(self, index: usize, direction: Direction, pane: T) -> (Node<T>, Option<T>) {
+        match self {
+            Node::Leaf(leaf) if index == 0 => {
+                let node = Node::Split { direction, ratio: 0.5, first: Box::new(Node::Leaf(leaf)), second: Box::new(Node::Leaf(pane)) };
+                (node, None)
+            }
+            Node::Leaf(leaf) => (Node::Leaf(leaf), Some(pane)),
+            Node::Split { direction: d, ratio, first, second } => {
+                let n = first.count();
+                let (first, second, rest) = if index < n {
+                    let (first, rest) = first.split(index, direction, pane);
+                    (Box::new(first), second, rest)
+                } else {
+                    let (second, rest) = second.split(index - n, direction, pane);
+                    (first, Box::new(second), rest)
+                };
+                (Node::Split { direction: d, ratio, first, second }, rest)
+            }
+        }
+    }
+
+    // the sibling takes the place of the split that held the closed leaf
+    fn close(self, index: usize) -> Option<Node<T>> {
+        match self {
+            Node::Leaf(_) if index == 0 => None,
+            Node::Leaf(leaf) => Some(Node::Leaf(leaf)),
+            Node::Split { direction, ratio, first, second } => {
+                let n = first.count();
+                if index < n {
+                    match first.close(index) {
+                        Some(first) => Some(Node::Split { direction, ratio, first: Box::new(first), second }),
+                        None => Some(*second),
+                    }
+                } else {
+                    match second.close(index - n) {
+                        Some(second) => Some(Node::Split { direction, ratio, first, second: Box::new(second) }),
+                        None => Some(*first),
+                    }
+                }
+            }
+        }
+    }
+
+}
+
+impl Node<Pane> {
+    fn layout(&self) -> PaneLayout {
+        match self {
+            Node::Leaf(pane) => PaneLayout::Terminal { id: pane.id.clone(), cwd: pane.view.cwd() },
+            Node::Split { direction, ratio, first, second } => PaneLayout::Split {
+                direction: *direction,
+                ratio: *ratio,
+                first: Box::new(first.layout()),
+                second: Box::new(second.layout()),
+            },
+        }
+    }
+
+    fn from_layout(layout: &PaneLayout, open: &mut dyn FnMut(&String, Option<String>) -> TerminalView) -> Self {
+        match layout {
+            PaneLayout::Terminal { id, cwd } => Node::Leaf(Pane { id: id.clone(), view: open(id, cwd.clone()) }),
+            PaneLayout::Split { direction, ratio, first, second } => Node::Split {
+                direction: *direction,
+                ratio: *ratio,
+                first: Box::new(Self::from_layout(first, open)),
+                second: Box::new(Self::from_layout(second, open)),
+            },
+        }
+    }
+
+    // draws the leaves in DFS order, `next` is the index of the first leaf of this node
+    fn show(&mut self, ui: &mut egui::Ui, rect: egui::Rect, next: &mut usize, out: &mut Shown) {
+        match self {
+            Node::Leaf(pane) => {
+                let index = *next;
+                *next += 1;
+                out.rects.push(rect);
+                let mut child = ui.child_ui_with_id_source(rect, egui::Layout::top_down(egui::Align::LEFT), index);
+                let response = child.add(&mut pane.view);
+                if out.focus == Some(index) {
+                    response.request_focus();
+                }
+                if response.has_focus() || response.clicked() {
+                    out.focused = Some(index);
+                }
+            }
+            Node::Split { direction, ratio, first, second } => {
+                let (a, divider, b) = split_rect(rect, *direction, *ratio);
+                let id = ui.id().with("pane divider").with(*next);
+                let response = ui.interact(divider, id, egui::Sense::drag());
+                if response.hovered() || response.dragged() {
+                    ui.output().cursor_icon = match direction {
+                        Direction::Horizontal => egui::CursorIcon::ResizeHorizontal,
+                        Direction::Vertical => egui::CursorIcon::ResizeVertical,
+                    };
+                }
+                if let (true, Some(pos)) = (response.dragged(), ui.input().pointer.interact_pos()) {
+                    let r = match direction {
+                        Direction::Horizontal => (pos.x - rect.left()) / rect.width(),
+                        Direction::Vertical => (pos.y - rect.top()) / rect.height(),
+                    };
+                    *ratio = r.clamp(MIN_RATIO, 1.0 - MIN_RATIO);
+                }
+                let stroke = if response.dragged() {
+                    ui.visuals().selection.stroke
+                } else {
+                    ui.visuals().widgets.noninteractive.bg_stroke
+                };
+                ui.painter().rect_filled(divider.shrink(2.0), 0.0, stroke.color);
+                first.show(ui, a, next, out);
+                second.show(ui, b, next, out);
+            }
+        }
+    }
+}
+
+fn split_rect(rect: egui::Rect, direction: Direction, ratio: f32) -> (egui::Rect, egui::Rect, egui::Rect) {
+    match direction {
+        Direction::Horizontal => {
+            let x = rect.left() + rect.width() * ratio;
+            (
+                egui::Rect::from_min_max(rect.min, egui::pos2(x - DIVIDER / 2.0, rect.bottom())),
+                egui::Rect::from_min_max(egui::pos2(x - DIVIDER / 2.0, rect.top()), egui::pos2(x + DIVIDER / 2.0, rect.bottom())),
+                egui::Rect::from_min_max(egui::pos2(x + DIVIDER / 2.0, rect.top()), rect.max),
+            )
+        }
+        Direction::Vertical => {
+            let y = rect.top() + rect.height() * ratio;
+            (
+                egui::Rect::from_min_max(rect.min, egui::pos2(rect.right(), y - DIVIDER / 2.0)),
+                egui::Rect::from_min_max(egui::pos2(rect.left(), y - DIVIDER / 2.0), egui::pos2(rect.right(), y + DIVIDER / 2.0)),
+                egui::Rect::from_min_max(egui::pos2(rect.left(), y + DIVIDER / 2.0), rect.max),
+            )
+        }
+    }
+}
+
+#[derive(Default)]
+struct Shown {
+    rects: Vec<egui::Rect>,
+    focus: Option<usize>,
+    focused: Option<usize>,
+}
+
+// the terminals of one tab, each pane with its own pty
+pub struct PaneTree {
+    root: Option<Node<Pane>>,
+    focused: usize,
+    // where each leaf was drawn last frame, for moving between panes
+    rects: Vec<egui::Rect>,
+    request_focus: bool,
+}
+
+impl PaneTree {
+    pub fn new(id: String, view: TerminalView) -> Self {
+        Self::with_root(Node::Leaf(Pane { id, view }))
+    }
+
+    fn with_root(root: Node<Pane>) -> Self {
+        Self { root: Some(root), focused: 0, rects: Vec::new(), request_focus: true }
+    }
+
+    pub fn from_layout(layout: &PaneLayout, open: &mut dyn FnMut(&String, Option<String>) -> TerminalView) -> Self {
+        Self::with_root(Node::from_layout(layout, open))
+    }
+
+    pub fn layout(&self) -> Option<PaneLayout> {
+        self.root.as_ref().map(Node::layout)
+    }
+
+    pub fn views(&self) -> Vec<&TerminalView> {
+        self.root.as_ref().map(|r| r.leaves().into_iter().map(|p| &p.view).collect()).unwrap_or_default()
+    }
+
+    pub fn views_mut(&mut self) -> Vec<&mut TerminalView> {
+        self.root.as_mut().map(|r| r.leaves_mut().into_iter().map(|p| &mut p.view).collect()).unwrap_or_default()
+    }
+
+    pub fn focused(&self) -> Option<&TerminalView> {
+        self.views().get(self.focused).copied()
+    }
+
+    pub fn focused_mut(&mut self) -> Option<&mut TerminalView> {
+        let focused = self.focused;
+        self.views_mut().into_iter().nth(focused)
+    }
+
+    pub fn focused_index(&self) -> usize {
+        self.focused
+    }
+
+    // profile of the focused pane
+    pub fn focused_id(&self) -> Option<String> {
+        self.root.as_ref().and_then(|r| r.leaves().get(self.focused).map(|p| p.id.clone()))
+    }
+
+    pub fn split(&mut self, direction: Direction, id: String, view: TerminalView) {
+        if let Some(root) = self.root.take() {
+            let (root, _) = root.split(self.focused, direction, Pane { id, view });
+            self.root = Some(root);
+            self.focused += 1;
+            self.request_focus = true;
+        }
+    }
+
+    // returns true when the last pane is gone and the tab should close with it
+    pub fn close_focused(&mut self) -> bool {
+        self.root = self.root.take().and_then(|r| r.close(self.focused));
+        let count = self.root.as_ref().map(Node::count).unwrap_or(0);
+        self.focused = self.focused.min(count.saturating_sub(1));
+        self.request_focus = true;
+        count == 0
+    }
+
+    // the nearest pane whose centre lies in that direction
+    pub fn move_focus(&mut self, to: Move) {
+        let from = match self.rects.get(self.focused) {
+            Some(rect) => rect.center(),
+            None => return,
+        };
+        let next = self.rects.iter().enumerate()
+            .filter(|(i, _)| *i != self.focused)
+            .map(|(i, rect)| (i, rect.center() - from))
+            .filter(|(_, d)| match to {
+                Move::Left => d.x < 0.0 && d.x.abs() >= d.y.abs(),
+                Move::Right => d.x > 0.0 && d.x.abs() >= d.y.abs(),
+                Move::Up => d.y < 0.0 && d.y.abs() >= d.x.abs(),
+                Move::Down => d.y > 0.0 && d.y.abs() >= d.x.abs(),
+            })
+            .min_by(|a, b| a.1.length().total_cmp(&b.1.length()))
+            .map(|(i, _)| i);
+        if let Some(i) = next {
+            self.focused = i;
+            self.request_focus = true;
+        }
+    }
+
+    pub fn focus(&mut self) {
+        self.request_focus = true;
+    }
+}
+
+impl egui::Widget for &mut PaneTree {
+    fn ui(self, ui: &mut egui::Ui) -> egui::Response {
+        let (rect, response) = ui.allocate_exact_size(ui.available_size(), egui::Sense::hover());
+        let mut shown = Shown {
+            focus: Some(self.focused).filter(|_| self.request_focus),
+            ..Default::default()
+        };
+        self.request_focus = false;
+        if let Some(root) = &mut self.root {
+            let mut next = 0;
+            root.show(ui, rect, &mut next, &mut shown);
+        }
+        // the pane asked for focus wins over the one still holding it this frame
+        if let Some(i) = shown.focus.or(shown.focused) {
+            self.focused = i;
+        }
+        self.rects = shown.rects;
+        response
+    }
+}
+
+// named layouts, persisted by eframe next to the workspaces
+pub struct LayoutStore {
+    layouts: BTreeMap<String, PaneLayout>,
+}
+
+impl LayoutStore {
+    pub fn new() -> Self {
+        Self { layouts: BTreeMap::new() }
+    }
+
+    pub fn load(&mut self, storage: &dyn epi::Storage) {
+        self.layouts = storage.get_string(STORAGE_KEY)
+            .and_then(|s| serde_json::from_str::<Value>(&s).ok())
+            .and_then(|v| v.as_object().cloned())
+            .map(|map| map.iter().filter_map(|(name, v)| Some((name.clone(), PaneLayout::from_json(v)?))).collect())
+            .unwrap_or_default();
+    }
+
+    pub fn save(&self, storage: &mut dyn epi::Storage) {
+        let map: serde_json::Map<String, Value> = self.layouts.iter().map(|(k, v)| (k.clone(), v.to_json())).collect();
+        storage.set_string(STORAGE_KEY, Value::Object(map).to_string());
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        self.layouts.keys().cloned().collect()
+    }
+
+    pub fn get(&self, name: &str) -> Option<&PaneLayout> {
+        self.layouts.get(name)
+    }
+
+    pub fn insert(&mut self, name: String, layout: PaneLayout) {
+        self.layouts.insert(name, layout);
+    }
+
+    pub fn remove(&mut self, name: &str) {
+        self.layouts.remove(name);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn terminal(id: &str, cwd: Option<&str>) -> PaneLayout {
+        PaneLayout::Terminal { id: id.to_string(), cwd: cwd.map(str::to_string) }
+    }
+
+    fn split(direction: Direction, ratio: f32, first: PaneLayout, second: PaneLayout) -> PaneLayout {
+        PaneLayout::Split { direction, ratio, first: Box::new(first), second: Box::new(second) }
+    }
+
+    fn leaves(node: &Option<Node<u32>>) -> Vec<u32> {
+        node.as_ref().map(|n| n.leaves().into_iter().copied().collect()).unwrap_or_default()
+    }
+
+    #[test]
+    fn layout_json() {
+        let layout = split(
+            Direction::Horizontal, 0.25,
+            terminal("a", Some("/srv/www")),
+            split(Direction::Vertical, 0.5, terminal("b", None), terminal("a", Some("/tmp"))),
+        );
+        let json = layout.to_json();
+        assert_eq!(json["split"], "horizontal");
+        assert_eq!(json["second"]["first"]["cwd"], Value::Null);
+        assert_eq!(PaneLayout::from_json(&json).unwrap().to_json(), json);
+
+        // a ratio dragged past the edge comes back usable
+        let json = json!({ "split": "vertical", "ratio": 2.0, "first": { "id": "a" }, "second": { "id": "b" } });
+        match PaneLayout::from_json(&json).unwrap() {
+            PaneLayout::Split { ratio, .. } => assert_eq!(ratio, 1.0 - MIN_RATIO),
+            PaneLayout::Terminal { .. } => panic!("not a split"),
+        }
+        assert!(PaneLayout::from_json(&json!({ "split": "diagonal", "first": { "id": "a" }, "second": { "id": "b" } })).is_none());
+        assert!(PaneLayout::from_json(&json!({ "split": "vertical", "first": { "id": "a" } })).is_none());
+    }
+
+    #[test]
+    fn split_and_close() {
+        let mut root = Some(Node::Leaf(0));
+        for (index, leaf) in [(0, 1), (0, 2), (2, 3)] {
+            let (node, rest) = root.take().unwrap().split(index, Direction::Horizontal, leaf);
+            assert!(rest.is_none());
+            root = Some(node);
+        }
+        // the new leaf lands right after the one that was split
+        assert_eq!(leaves(&root), vec![0, 2, 1, 3]);
+        let (node, rest) = root.take().unwrap().split(4, Direction::Vertical, 9);
+        assert_eq!(rest, Some(9));
+        root = Some(node);
+        assert_eq!(root.as_ref().unwrap().count(), 4);
+
+        root = root.and_then(|r| r.close(1));
+        assert_eq!(leaves(&root), vec![0, 1, 3]);
+        // out of range closes nothing
+        root = root.and_then(|r| r.close(3));
+        assert_eq!(leaves(&root), vec![0, 1, 3]);
+        root = root.and_then(|r| r.close(0));
+        assert_eq!(leaves(&root), vec![1, 3]);
+        assert!(matches!(root, Some(Node::Split { .. })));
+        root = root.and_then(|r| r.close(1));
+        assert!(matches!(root, Some(Node::Leaf(1))));
+        root = root.and_then(|r| r.close(0));
+        assert!(root.is_none());
+    }
+}
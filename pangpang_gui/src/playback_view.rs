@@ -0,0 +1,48 @@
+use std::path::PathBuf;
+
+use eframe::egui;
+
+
+
+pub struct PlaybackView {
+    pub open: bool,
+    path: String,
+    message: String,
+}
+
+impl PlaybackView {
+    pub fn new() -> Self {
+        Self {
+            open: false,
+            path: String::new(),
+            message: String::new(),
+        }
+    }
+
+    // returns the recording to play
+    pub fn show(&mut self, ctx: &egui::CtxRef) -> Option<PathBuf> {
+        let mut play = None;
+        let mut open = self.open;
+        egui::Window::new("Play recording")
+            .open(&mut open)
+            .default_width(500.0)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label("asciicast file");
+                    ui.text_edit_singleline(&mut self.path);
+                    if ui.button("Play").clicked() {
+                        let path = PathBuf::from(self.path.trim());
+                        if path.is_file() {
+                            self.message.clear();
+                            play = Some(path);
+                        } else {
+                            self.message = format!("{} is not a file", path.display());
+                        }
+                    }
+                });
+                ui.label(&self.message);
+            });
+        self.open = open && play.is_none();
+        play
+    }
+}
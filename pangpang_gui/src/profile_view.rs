@@ -0,0 +1,206 @@
+use std::{sync::Arc, time::Duration};
+
+use eframe::egui;
+use pangpang::{
+    errors,
+    pangpang_run_sync::Mutex,
+    profile::{Inherit, Profile},
+    storage::Storage,
+    terminal::{LogFormat, LogSettings, TriggerAction, TriggerRule, TriggerSource},
+};
+
+
+
+// the per-profile terminal settings, written back through the storage
+pub struct ProfileView {
+    pub open: bool,
+    profile: Option<Profile>,
+    logging: bool,
+    log: LogSettings,
+    // 0 = never rotate
+    max_size_mb: u64,
+    triggers: Vec<TriggerRule>,
+    message: String,
+}
+
+impl ProfileView {
+    pub fn new() -> Self {
+        Self {
+            open: false,
+            profile: None,
+            logging: false,
+            log: LogSettings::default(),
+            max_size_mb: 0,
+            triggers: Vec::new(),
+            message: String::new(),
+        }
+    }
+
+    pub fn edit(&mut self, profile: Profile) {
+        self.logging = profile.terminal.logging.is_some();
+        self.log = profile.terminal.logging.clone().unwrap_or_default();
+        self.max_size_mb = self.log.max_size.map(|s| s >> 20).unwrap_or(0);
+        self.triggers = profile.terminal.triggers.clone();
+        self.message = if profile.inherit.contains(Inherit::TERMINAL) {
+            "terminal settings are inherited, saving overrides them".to_string()
+        } else {
+            String::new()
+        };
+        self.profile = Some(profile);
+        self.open = true;
+    }
+
+    pub fn show(&mut self, ctx: &egui::CtxRef, cfg: &Arc<Mutex<dyn Storage>>) {
+        let title = match &self.profile {
+            Some(p) if !p.name.is_empty() => p.name.clone(),
+            Some(p) => p.target(),
+            None => return,
+        };
+        let mut open = self.open;
+        egui::Window::new(format!("Terminal settings: {}", title))
+            .id(egui::Id::new("profile_view"))
+            .open(&mut open)
+            .default_width(500.0)
+            .show(ctx, |ui| {
+                ui.checkbox(&mut self.logging, "log this session");
+                ui.add_enabled_ui(self.logging, |ui| self.logging_ui(ui));
+                ui.separator();
+                ui.label("triggers");
+                self.triggers_ui(ui);
+                ui.separator();
+                ui.horizontal(|ui| {
+                    if ui.button("Save").clicked() {
+                        self.message = match self.store(cfg) {
+                            Ok(()) => "saved, applies to newly opened terminals".to_string(),
+                            Err(e) => format!("save failed: {:?}", e),
+                        };
+                    }
+                    ui.label(&self.message);
+                });
+            });
+        self.open = open;
+    }
+
+    fn logging_ui(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.label("path");
+            ui.text_edit_singleline(&mut self.log.path)
+                .on_hover_text("{profile}, {id}, {date} and {time} are replaced when the terminal opens");
+        });
+        egui::ComboBox::from_label("format")
+            .selected_text(match self.log.format {
+                LogFormat::Text => "text",
+                LogFormat::Raw => "raw",
+            })
+            .show_ui(ui, |ui| {
+                ui.selectable_value(&mut self.log.format, LogFormat::Text, "text");
+                ui.selectable_value(&mut self.log.format, LogFormat::Raw, "raw");
+            });
+        ui.horizontal(|ui| {
+            ui.label("rotate at (MiB, 0 = never)");
+            ui.add(egui::DragValue::new(&mut self.max_size_mb).clamp_range(0..=4096));
+        });
+        ui.checkbox(&mut self.log.log_input, "log typed input");
+        ui.add_enabled(self.log.format == LogFormat::Text, egui::Checkbox::new(&mut self.log.timestamps, "timestamps"));
+    }
+
+    fn triggers_ui(&mut self, ui: &mut egui::Ui) {
+        let mut remove = None;
+        egui::ScrollArea::vertical().max_height(300.0).show(ui, |ui| {
+            for (i, rule) in self.triggers.iter_mut().enumerate() {
+                ui.horizontal(|ui| {
+                    let valid = rule.check().is_ok();
+                    let pattern = egui::TextEdit::singleline(&mut rule.pattern).hint_text("regex");
+                    let pattern = if valid { pattern } else { pattern.text_color(egui::Color32::RED) };
+                    ui.add(pattern);
+                    let mut raw = rule.source == TriggerSource::Raw;
+                    if ui.checkbox(&mut raw, "raw").on_hover_text("match the bytes as read, escape sequences included").changed() {
+                        rule.source = if raw { TriggerSource::Raw } else { TriggerSource::Line };
+                    }
+                    if ui.small_button("\u{2716}").clicked() {
+                        remove = Some(i);
+                    }
+                });
+                ui.horizontal(|ui| {
+                    egui::ComboBox::from_id_source(("trigger action", i))
+                        .selected_text(action_name(&rule.action))
+                        .show_ui(ui, |ui| {
+                            for name in ["send", "secret", "notify", "highlight"] {
+                                if ui.selectable_label(action_name(&rule.action) == name, name).clicked() {
+                                    rule.action = with_action(name, action_text(&rule.action));
+                                }
+                            }
+                        });
+                    match &mut rule.action {
+                        TriggerAction::Send(s) | TriggerAction::SendSecret(s) | TriggerAction::Notify(s) => {
+                            ui.text_edit_singleline(s);
+                        }
+                        TriggerAction::Highlight => {}
+                    }
+                    ui.checkbox(&mut rule.once, "once");
+                    let mut interval = rule.interval.as_secs_f64();
+                    ui.label("every");
+                    if ui.add(egui::DragValue::new(&mut interval).speed(0.1).clamp_range(0.0..=3600.0).suffix(" s")).changed() {
+                        rule.interval = Duration::from_secs_f64(interval);
+                    }
+                });
+                ui.add_space(4.0);
+            }
+        });
+        if let Some(i) = remove {
+            self.triggers.remove(i);
+        }
+        if ui.button("Add trigger").clicked() {
+            self.triggers.push(TriggerRule {
+                pattern: String::new(),
+                source: TriggerSource::Line,
+                action: TriggerAction::Highlight,
+                once: false,
+                interval: Duration::from_secs(1),
+            });
+        }
+    }
+
+    fn store(&mut self, cfg: &Arc<Mutex<dyn Storage>>) -> Result<(), errors::Error> {
+        let mut profile = match &self.profile {
+            Some(p) => p.clone(),
+            None => return Ok(()),
+        };
+        self.log.max_size = Some(self.max_size_mb << 20).filter(|s| *s > 0);
+        for rule in &self.triggers {
+            rule.check()?;
+        }
+        profile.terminal.logging = Some(self.log.clone()).filter(|_| self.logging);
+        profile.terminal.triggers = self.triggers.clone();
+        profile.inherit.remove(Inherit::TERMINAL);
+        cfg.blocking_lock().insert(profile.clone())?;
+        self.profile = Some(profile);
+        Ok(())
+    }
+}
+
+fn action_name(action: &TriggerAction) -> &'static str {
+    match action {
+        TriggerAction::Send(_) => "send",
+        TriggerAction::SendSecret(_) => "secret",
+        TriggerAction::Notify(_) => "notify",
+        TriggerAction::Highlight => "highlight",
+    }
+}
+
+fn action_text(action: &TriggerAction) -> String {
+    match action {
+        TriggerAction::Send(s) | TriggerAction::SendSecret(s) | TriggerAction::Notify(s) => s.clone(),
+        TriggerAction::Highlight => String::new(),
+    }
+}
+
+// switching the kind keeps what was typed
+fn with_action(name: &str, text: String) -> TriggerAction {
+    match name {
+        "send" => TriggerAction::Send(text),
+        "secret" => TriggerAction::SendSecret(text),
+        "notify" => TriggerAction::Notify(text),
+        _ => TriggerAction::Highlight,
+    }
+}
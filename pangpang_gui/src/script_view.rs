@@ -0,0 +1,85 @@
+use eframe::egui;
+use pangpang::pangpang_run_sync::{PpMessage, PpMsgSender, mpsc};
+
+
+
+const MAX_OUTPUT_LINES: usize = 1000;
+
+pub struct ScriptView {
+    pub open: bool,
+    path: String,
+    output: Vec<String>,
+    running: Option<mpsc::Receiver<String>>,
+}
+
+impl ScriptView {
+    pub fn new() -> Self {
+        Self {
+            open: false,
+            path: String::new(),
+            output: Vec::new(),
+            running: None,
+        }
+    }
+
+    pub fn show(&mut self, ctx: &egui::CtxRef, sender: &PpMsgSender) {
+        self.poll(ctx);
+        let mut open = self.open;
+        egui::Window::new("Run script")
+            .open(&mut open)
+            .default_width(600.0)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label("rhai script");
+                    ui.text_edit_singleline(&mut self.path);
+                    let run = egui::Button::new("Run");
+                    if ui.add_enabled(self.running.is_none(), run).clicked() {
+                        self.run(sender);
+                    }
+                    if ui.button("Clear").clicked() {
+                        self.output.clear();
+                    }
+                });
+                egui::ScrollArea::vertical().max_height(400.0).stick_to_bottom().show(ui, |ui| {
+                    for line in &self.output {
+                        ui.monospace(line);
+                    }
+                });
+            });
+        self.open = open;
+    }
+
+    fn run(&mut self, sender: &PpMsgSender) {
+        let source = match std::fs::read_to_string(self.path.trim()) {
+            Ok(s) => s,
+            Err(e) => {
+                self.output.push(format!("read {} failed: {}", self.path, e));
+                return;
+            }
+        };
+        let (tx, rx) = mpsc::channel(256);
+        if sender.blocking_send(PpMessage::RunScript(source, tx)).is_ok() {
+            self.output.push(format!("running {}", self.path));
+            self.running = Some(rx);
+        }
+    }
+
+    fn poll(&mut self, ctx: &egui::CtxRef) {
+        if let Some(rx) = &mut self.running {
+            loop {
+                match rx.try_recv() {
+                    Ok(line) => self.output.push(line),
+                    Err(mpsc::error::TryRecvError::Empty) => break,
+                    Err(mpsc::error::TryRecvError::Disconnected) => {
+                        self.running = None;
+                        break;
+                    }
+                }
+            }
+            ctx.request_repaint();
+        }
+        if self.output.len() > MAX_OUTPUT_LINES {
+            self.output.drain(..self.output.len() - MAX_OUTPUT_LINES);
+        }
+    }
+}
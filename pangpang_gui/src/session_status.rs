@@ -0,0 +1,100 @@
+use std::collections::HashMap;
+
+use eframe::egui;
+use pangpang::{
+    pangpang_run_sync::{PpMessage, PpMsgSender, broadcast, oneshot},
+    session::event::{EventReceiver, SessionEventKind},
+};
+
+
+
+pub enum Status {
+    Connecting,
+    Connected,
+    Failed(String),
+}
+
+impl Status {
+    pub fn color(&self) -> egui::Color32 {
+        match self {
+            Status::Connecting => egui::Color32::YELLOW,
+            Status::Connected => egui::Color32::GREEN,
+            Status::Failed(_) => egui::Color32::RED,
+        }
+    }
+}
+
+pub struct SessionStatus {
+    pending: Option<oneshot::Receiver<EventReceiver>>,
+    events: Option<EventReceiver>,
+    status: HashMap<String, Status>,
+}
+
+impl SessionStatus {
+    pub fn new(sender: &PpMsgSender) -> Self {
+        let (tx, rx) = oneshot::channel();
+        let pending = match sender.blocking_send(PpMessage::Subscribe(tx)) {
+            Ok(_) => Some(rx),
+            Err(_) => None,
+        };
+        Self {
+            pending,
+            events: None,
+            status: HashMap::new(),
+        }
+    }
+
+    // drains the event bus, returns true if any status changed
+    pub fn poll(&mut self) -> bool {
+        if let Some(rx) = &mut self.pending {
+            match rx.try_recv() {
+                Ok(events) => {
+                    self.events = Some(events);
+                    self.pending = None;
+                }
+                Err(oneshot::error::TryRecvError::Empty) => {}
+                Err(oneshot::error::TryRecvError::Closed) => self.pending = None,
+            }
+        }
+        let events = match &mut self.events {
+            Some(e) => e,
+            None => return false,
+        };
+        let mut changed = false;
+        loop {
+            let e = match events.try_recv() {
+                Ok(e) => e,
+                Err(broadcast::error::TryRecvError::Lagged(n)) => {
+                    log::warn!("session status missed {} events", n);
+                    continue;
+                }
+                Err(_) => break,
+            };
+            match e.kind {
+                SessionEventKind::Connecting => {
+                    self.status.insert(e.profile, Status::Connecting);
+                }
+                SessionEventKind::Connected | SessionEventKind::Cached | SessionEventKind::Reused(_) => {
+                    self.status.insert(e.profile, Status::Connected);
+                }
+                SessionEventKind::ConnectFailed(err) => {
+                    self.status.insert(e.profile, Status::Failed(err));
+                }
+                SessionEventKind::Evicted | SessionEventKind::Closed => {
+                    self.status.remove(&e.profile);
+                }
+                _ => continue,
+            }
+            changed = true;
+        }
+        changed
+    }
+
+    pub fn get(&self, id: &String) -> Option<&Status> {
+        self.status.get(id)
+    }
+
+    pub fn colors(&self) -> HashMap<String, egui::Color32> {
+        self.status.iter().map(|(id, s)| (id.clone(), s.color())).collect()
+    }
+}
@@ -0,0 +1,274 @@
+use std::collections::{BTreeMap, HashSet};
+
+use eframe::{egui, epi};
+use pangpang::{profile::{Profile, Protocol, Inherit}, session::serial::Parity, storage::Storage};
+use serde_json::{json, Value};
+
+use crate::session_status::{SessionStatus, Status};
+
+
+
+const STORAGE_KEY: &str = "pangpang session tree";
+
+#[derive(Default)]
+struct FolderNode {
+    folders: BTreeMap<String, FolderNode>,
+    profiles: Vec<(String, String)>,
+}
+
+pub struct SessionTree {
+    search: String,
+    // folder paths joined by "/", kept across restarts
+    expanded: HashSet<String>,
+    // profile picked from the context menu for the settings editor
+    edit: Option<String>,
+}
+
+impl SessionTree {
+    pub fn new() -> Self {
+        Self {
+            search: String::new(),
+            expanded: HashSet::new(),
+            edit: None,
+        }
+    }
+
+    pub fn load(&mut self, storage: &dyn epi::Storage) {
+        let value = storage.get_string(STORAGE_KEY).and_then(|s| serde_json::from_str::<Value>(&s).ok());
+        if let Some(expanded) = value.as_ref().and_then(|v| v.get("expanded")).and_then(Value::as_array) {
+            self.expanded = expanded.iter().filter_map(|f| Some(f.as_str()?.to_string())).collect();
+        }
+    }
+
+    pub fn save(&self, storage: &mut dyn epi::Storage) {
+        let mut expanded: Vec<&String> = self.expanded.iter().collect();
+        expanded.sort();
+        storage.set_string(STORAGE_KEY, json!({ "expanded": expanded }).to_string());
+    }
+
+    pub fn take_edit(&mut self) -> Option<String> {
+        self.edit.take()
+    }
+
+    pub fn show(&mut self, ui: &mut egui::Ui, cfg: &dyn Storage, status: &SessionStatus) -> Option<(String, String)> {
+        let mut open = None;
+        ui.add(egui::TextEdit::singleline(&mut self.search).hint_text("search"));
+        let pattern = self.search.trim().to_lowercase();
+        if pattern.is_empty() {
+            let mut root = FolderNode::default();
+            for (id, profile) in cfg.iter() {
+                let mut node = &mut root;
+                for f in &profile.folder {
+                    node = node.folders.entry(f.clone()).or_default();
+                }
+                node.profiles.push((display_name(profile), id.clone()));
+            }
+            self.show_folder(ui, &mut root, &mut Vec::new(), cfg, status, &mut open);
+        } else {
+            let mut matched: Vec<(i32, String, &String, &Profile)> = cfg.iter()
+                .filter_map(|(id, p)| {
+                    let name = display_name(p);
+                    let score = std::iter::once(&name)
+                        .chain(std::iter::once(&p.address))
+                        .chain(p.tags.iter())
+                        .filter_map(|s| fuzzy_score(&pattern, &s.to_lowercase()))
+                        .max()?;
+                    Some((score, name, id, p))
+                })
+                .collect();
+            matched.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.cmp(&b.1)));
+            for (_, name, id, p) in matched {
+                let mut label = p.folder.clone();
+                label.push(name.clone());
+                if profile_button(ui, label.join("/"), p, cfg, status, &mut self.edit) {
+                    open = Some((id.clone(), name));
+                }
+            }
+        }
+        open
+    }
+
+    fn show_folder(&mut self, ui: &mut egui::Ui, node: &mut FolderNode, path: &mut Vec<String>, cfg: &dyn Storage, status: &SessionStatus, open: &mut Option<(String, String)>) {
+        for (name, child) in node.folders.iter_mut() {
+            path.push(name.clone());
+            let key = path.join("/");
+            let expanded = self.expanded.contains(&key);
+            let arrow = if expanded { "\u{25bc}" } else { "\u{25b6}" };
+            let btn = egui::Button::new(format!("{} {}", arrow, name)).frame(false).wrap(false);
+            if ui.add(btn).clicked() {
+                if expanded {
+                    self.expanded.remove(&key);
+                } else {
+                    self.expanded.insert(key.clone());
+                }
+            }
+            if expanded {
+                ui.indent(&key, |ui| self.show_folder(ui, child, path, cfg, status, open));
+            }
+            path.pop();
+        }
+        node.profiles.sort();
+        for (name, id) in &node.profiles {
+            if let Ok(p) = cfg.get(id) {
+                if profile_button(ui, name.clone(), &p, cfg, status, &mut self.edit) {
+                    *open = Some((id.clone(), name.clone()));
+                }
+            }
+        }
+    }
+}
+
+fn display_name(p: &Profile) -> String {
+    if p.name.is_empty() {
+        p.target()
+    } else {
+        p.name.clone()
+    }
+}
+
+fn profile_button(ui: &mut egui::Ui, label: String, p: &Profile, cfg: &dyn Storage, status: &SessionStatus, edit: &mut Option<String>) -> bool {
+    let label = if p.template {
+        format!("{} (template)", label)
+    } else {
+        label
+    };
+    ui.horizontal(|ui| {
+        if let Some(s) = status.get(&p.id) {
+            let dot = ui.colored_label(s.color(), "\u{25cf}");
+            if let Status::Failed(e) = s {
+                dot.on_hover_text(e);
+            }
+        }
+        let btn = egui::Button::new(label).frame(false).wrap(false);
+        let response = ui.add(btn).on_hover_ui(|ui| profile_details(ui, p, cfg));
+        let clicked = response.clicked() && !p.template;
+        response.context_menu(|ui| {
+            if ui.button("Terminal settings").clicked() {
+                *edit = Some(p.id.clone());
+                ui.close_menu();
+            }
+        });
+        clicked
+    }).inner
+}
+
+fn profile_details(ui: &mut egui::Ui, p: &Profile, cfg: &dyn Storage) {
+    let resolved = match cfg.resolve(&p.id) {
+        Ok(r) => r,
+        Err(e) => {
+            ui.colored_label(egui::Color32::RED, format!("{:?}", e));
+            return;
+        }
+    };
+    let parent = p.parent.as_ref()
+        .and_then(|id| cfg.get(id).ok())
+        .map(|parent| display_name(&parent));
+    let field = |ui: &mut egui::Ui, name: &str, value: String, flag: Inherit| {
+        match &parent {
+            Some(parent) if p.inherit.contains(flag) => {
+                ui.colored_label(egui::Color32::LIGHT_BLUE, format!("{}: {} (inherited from {})", name, value, parent))
+            }
+            _ => ui.label(format!("{}: {}", name, value)),
+        };
+    };
+    if !matches!(resolved.protocol, Protocol::Local(_) | Protocol::Serial(_) | Protocol::Container(_)) {
+        ui.label(format!("host: {}", resolved.address));
+        field(ui, "user", resolved.username.clone(), Inherit::USERNAME);
+        field(ui, "port", resolved.port.to_string(), Inherit::PORT);
+        let transport = resolved.transport.as_ref()
+            .map(|id| cfg.get(id).map(|t| display_name(&t)).unwrap_or_else(|_| id.clone()))
+            .unwrap_or_else(|| "direct".to_string());
+        field(ui, "transport", transport, Inherit::TRANSPORT);
+    }
+    match &resolved.protocol {
+        Protocol::Ssh(ssh) => {
+            let auth = match &ssh.identity_file {
+                Some(f) => format!("key {}", f),
+                None => "password".to_string(),
+            };
+            field(ui, "auth", auth, Inherit::AUTH);
+            let forwards = format!("{} local, {} remote", ssh.local_forwards.len(), ssh.remote_forwards.len());
+            field(ui, "forwards", forwards, Inherit::FORWARDS);
+        }
+        Protocol::Local(local) => {
+            let mut command = local.command.clone().unwrap_or_else(|| "default shell".to_string());
+            for arg in &local.args {
+                command.push(' ');
+                command.push_str(arg);
+            }
+            ui.label(format!("command: {}", command));
+            if let Some(cwd) = &local.cwd {
+                ui.label(format!("cwd: {}", cwd));
+            }
+        }
+        Protocol::Telnet(_) => {
+            ui.label("protocol: telnet");
+        }
+        Protocol::Mosh(mosh) => {
+            let auth = match &mosh.ssh.identity_file {
+                Some(f) => format!("key {}", f),
+                None => "password".to_string(),
+            };
+            field(ui, "auth", auth, Inherit::AUTH);
+            ui.label(format!("mosh server: {}", mosh.server));
+            ui.label(format!("prediction: {:?}", mosh.prediction));
+        }
+        Protocol::Container(c) => {
+            ui.label(format!("container: {} ({})", c.container, c.runtime.command()));
+            ui.label(format!("command: {}", c.command));
+            let host = resolved.transport.as_ref()
+                .map(|id| cfg.get(id).map(|t| display_name(&t)).unwrap_or_else(|_| id.clone()))
+                .unwrap_or_else(|| "local".to_string());
+            field(ui, "host", host, Inherit::TRANSPORT);
+        }
+        Protocol::RawTcp(tcp) => {
+            ui.label("protocol: raw tcp");
+            ui.label(format!("local echo: {}, crlf: {}", tcp.local_echo, tcp.crlf));
+        }
+        Protocol::Serial(serial) => {
+            ui.label(format!("device: {}", serial.device));
+            let parity = match serial.parity {
+                Parity::None => "N",
+                Parity::Odd => "O",
+                Parity::Even => "E",
+            };
+            ui.label(format!("line: {} {}{}{}", serial.baud_rate, serial.data_bits, parity, serial.stop_bits));
+            ui.label(format!("flow control: {:?}", serial.flow_control));
+        }
+    }
+    let terminal = format!("{}, {} lines", resolved.terminal.term, resolved.terminal.scrollback);
+    field(ui, "terminal", terminal, Inherit::TERMINAL);
+    if let Some(logging) = &resolved.terminal.logging {
+        field(ui, "log", logging.path.clone(), Inherit::TERMINAL);
+    }
+    if !resolved.terminal.triggers.is_empty() {
+        field(ui, "triggers", resolved.terminal.triggers.len().to_string(), Inherit::TERMINAL);
+    }
+    if !p.tags.is_empty() {
+        ui.label(format!("tags: {}", p.tags.join(", ")));
+    }
+    if !p.notes.is_empty() {
+        ui.separator();
+        ui.label(&p.notes);
+    }
+}
+
+fn fuzzy_score(pattern: &str, text: &str) -> Option<i32> {
+    let text: Vec<char> = text.chars().collect();
+    let mut score = 0;
+    let mut pos = 0;
+    let mut last: Option<usize> = None;
+    for pc in pattern.chars().filter(|c| !c.is_whitespace()) {
+        let idx = (pos..text.len()).find(|&i| text[i] == pc)?;
+        score += 1;
+        if last.map(|l| l + 1 == idx).unwrap_or(false) {
+            score += 5;
+        }
+        if idx == 0 || !text[idx - 1].is_alphanumeric() {
+            score += 3;
+        }
+        last = Some(idx);
+        pos = idx + 1;
+    }
+    Some(score * 100 / (text.len() as i32 + 1) + score * 10)
+}
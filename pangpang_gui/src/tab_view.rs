@@ -1,16 +1,104 @@
-use std::ops::Add;
+use std::{collections::{HashMap, HashSet}, ops::Add};
 
 use eframe::egui;
 
-use crate::terminal_view::TerminalView;
+use crate::{pane_view::{Direction, Move, PaneLayout, PaneTree}, terminal_view::TerminalView, workspace::{TabLayout, Workspace}};
 
 type TabViewType = TerminalView;
-type TabViewData = Vec<(String, TabViewType)>;
+type TabViewData = Vec<Tab>;
 const ITEM_WIDTH: f32 = 150.0;
+const TOAST_SECONDS: f64 = 6.0;
+const GROUP_COLORS: [egui::Color32; 6] = [
+    egui::Color32::from_rgb(0x4e, 0x9a, 0xf1),
+    egui::Color32::from_rgb(0xf1, 0x9a, 0x4e),
+    egui::Color32::from_rgb(0x9a, 0xf1, 0x4e),
+    egui::Color32::from_rgb(0xc8, 0x6e, 0xf1),
+    egui::Color32::from_rgb(0xf1, 0x4e, 0x8a),
+    egui::Color32::from_rgb(0x4e, 0xf1, 0xd2),
+];
+
+struct Toast {
+    title: String,
+    message: String,
+    line: String,
+    until: f64,
+}
+
+struct Tab {
+    // stable across reordering, indexes are not
+    key: u64,
+    id: String,
+    title: String,
+    panes: PaneTree,
+    // output and bells while another tab was selected
+    activity: bool,
+    bell: bool,
+}
+
+enum TabState {
+    Connecting,
+    Connected,
+    Disconnected(Option<String>),
+}
+
+impl TabState {
+    fn color(&self) -> egui::Color32 {
+        match self {
+            TabState::Connecting => egui::Color32::YELLOW,
+            TabState::Connected => egui::Color32::GREEN,
+            TabState::Disconnected(_) => egui::Color32::RED,
+        }
+    }
+
+    fn describe(&self) -> String {
+        match self {
+            TabState::Connecting => "connecting".to_string(),
+            TabState::Connected => "connected".to_string(),
+            TabState::Disconnected(None) => "disconnected".to_string(),
+            TabState::Disconnected(Some(e)) => format!("disconnected: {}", e),
+        }
+    }
+}
+
+// asks the app for new terminals, the tab view can not open them itself
+pub enum TabRequest {
+    Duplicate(String, PaneLayout),
+    Reconnect(u64, PaneLayout),
+    // every tab was closed to switch to that workspace, the closed tabs are saved in the old one
+    Switch(String, Workspace),
+}
+
+// what waits for the user to agree to drop live sessions
+enum Closing {
+    // tabs, and what the app does once they are gone
+    Tabs(Vec<u64>, Option<TabRequest>),
+    // a tab and the index of its focused pane, other panes stay open
+    Pane(u64, usize),
+}
+
+struct Rename {
+    key: u64,
+    text: String,
+    focus: bool,
+}
+
 pub struct TabView {
     items: TabViewData,
     selected: usize,
     offset: f32,
+    status: HashMap<String, egui::Color32>,
+    toasts: Vec<Toast>,
+    next_key: u64,
+    broadcast: HashSet<u64>,
+    broadcast_paused: bool,
+    // members whose terminal dropped broadcast input, until the user looks at them
+    broadcast_missed: HashSet<u64>,
+    // tabs that already got their session, regrouping leaves the others where the user put them
+    grouped: HashSet<u64>,
+    dragging: Option<u64>,
+    renaming: Option<Rename>,
+    confirm_close: Option<Closing>,
+    requests: Vec<TabRequest>,
 }
 
 impl TabView {
@@ -19,15 +107,454 @@ impl TabView {
             items: TabViewData::new(),
             selected: 0,
             offset: 0.0,
+            status: HashMap::new(),
+            toasts: Vec::new(),
+            next_key: 0,
+            broadcast: HashSet::new(),
+            broadcast_paused: false,
+            broadcast_missed: HashSet::new(),
+            grouped: HashSet::new(),
+            dragging: None,
+            renaming: None,
+            confirm_close: None,
+            requests: Vec::new(),
         }
     }
 
-    pub fn insert(&mut self, title: String, view: TabViewType) {
-        self.items.push((title, view));
+    // trigger notifications of every tab, shown in the corner for a few seconds
+    fn show_toasts(&mut self, ctx: &egui::CtxRef) {
+        let now = ctx.input().time;
+        for tab in &self.items {
+            for view in tab.panes.views() {
+                for (message, line) in view.take_notifications() {
+                    self.toasts.push(Toast { title: tab.title.clone(), message, line, until: now + TOAST_SECONDS });
+                }
+            }
+        }
+        self.toasts.retain(|t| t.until > now);
+        if self.toasts.is_empty() {
+            return;
+        }
+        egui::Area::new("trigger notifications")
+            .anchor(egui::Align2::RIGHT_BOTTOM, egui::vec2(-10.0, -10.0))
+            .show(ctx, |ui| {
+                for t in &self.toasts {
+                    egui::Frame::popup(ui.style()).show(ui, |ui| {
+                        ui.strong(format!("{}: {}", t.title, t.message));
+                        ui.monospace(&t.line);
+                    });
+                }
+            });
+        ctx.request_repaint();
+    }
+
+    pub fn insert(&mut self, id: String, title: String, view: TabViewType) {
+        self.insert_panes(id.clone(), title, PaneTree::new(id, view));
+    }
+
+    pub fn insert_panes(&mut self, id: String, title: String, panes: PaneTree) {
+        self.next_key += 1;
+        self.items.push(Tab { key: self.next_key, id, title, panes, activity: false, bell: false });
         self.selected = self.items.len() - 1;
         self.offset = f32::INFINITY;
     }
 
+    // the focused pane of the selected tab
+    pub fn selected(&self) -> Option<&TabViewType> {
+        self.items.get(self.selected).and_then(|tab| tab.panes.focused())
+    }
+
+    pub fn selected_mut(&mut self) -> Option<&mut TabViewType> {
+        self.items.get_mut(self.selected).and_then(|tab| tab.panes.focused_mut())
+    }
+
+    // profile, title and shell directory of the focused pane, to open another pty next to it
+    pub fn duplicate_info(&self) -> Option<(String, String, Option<String>)> {
+        let tab = self.items.get(self.selected)?;
+        Some((tab.panes.focused_id()?, tab.title.clone(), tab.panes.focused()?.cwd()))
+    }
+
+    pub fn toggle_recording(&self) {
+        if let Some(tab) = self.items.get(self.selected) {
+            if let Some(view) = tab.panes.focused() {
+                view.toggle_recording(&tab.title);
+            }
+        }
+    }
+
+    pub fn split(&mut self, direction: Direction, id: String, view: TabViewType) {
+        if let Some(tab) = self.items.get_mut(self.selected) {
+            tab.panes.split(direction, id, view);
+        }
+    }
+
+    // the last pane takes its tab with it
+    pub fn close_pane(&mut self) {
+        let tab = match self.items.get(self.selected) {
+            Some(tab) => tab,
+            None => return,
+        };
+        let key = tab.key;
+        let index = tab.panes.focused_index();
+        if tab.panes.views().len() <= 1 {
+            self.request_close(vec![key]);
+        } else if tab.panes.focused().map(TerminalView::live).unwrap_or(false) {
+            self.confirm_close = Some(Closing::Pane(key, index));
+        } else {
+            self.remove_pane(key, index);
+        }
+    }
+
+    fn remove_pane(&mut self, key: u64, index: usize) {
+        let tab = match self.items.iter_mut().find(|tab| tab.key == key) {
+            Some(tab) => tab,
+            None => return,
+        };
+        // focus moved while the user was asked, close nothing rather than the wrong pane
+        if tab.panes.focused_index() != index {
+            return;
+        }
+        if tab.panes.close_focused() {
+            self.remove(key);
+        }
+    }
+
+    pub fn take_requests(&mut self) -> Vec<TabRequest> {
+        std::mem::take(&mut self.requests)
+    }
+
+    // fresh terminals for a tab, in the same layout
+    pub fn replace_panes(&mut self, key: u64, panes: PaneTree) {
+        if let Some(tab) = self.items.iter_mut().find(|tab| tab.key == key) {
+            tab.panes = panes;
+            tab.activity = false;
+            tab.bell = false;
+            self.grouped.remove(&key);
+        }
+    }
+
+    pub fn layout(&self) -> Option<PaneLayout> {
+        self.items.get(self.selected).and_then(|tab| tab.panes.layout())
+    }
+
+    // recordings being played are left out, there is nothing to reconnect
+    pub fn workspace(&self) -> Workspace {
+        let mut selected = 0;
+        let mut tabs = Vec::new();
+        for (i, tab) in self.items.iter().enumerate() {
+            if tab.panes.views().iter().any(|view| view.read_only()) {
+                continue;
+            }
+            if let Some(layout) = tab.panes.layout() {
+                if i == self.selected {
+                    selected = tabs.len();
+                }
+                tabs.push(TabLayout { title: tab.title.clone(), layout });
+            }
+        }
+        Workspace { tabs, selected }
+    }
+
+    // every tab goes, asking first when one is live; `then` comes back through take_requests once they have
+    pub fn close_all(&mut self, then: TabRequest) {
+        let keys = self.items.iter().map(|tab| tab.key).collect();
+        self.close_tabs(keys, Some(then));
+    }
+
+    pub fn tab_count(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn select(&mut self, index: usize) {
+        if index < self.items.len() {
+            self.selected = index;
+        }
+    }
+
+    pub fn broadcast_all(&mut self) {
+        self.broadcast = self.items.iter().map(|tab| tab.key).collect();
+        self.broadcast_paused = false;
+    }
+
+    pub fn stop_broadcast(&mut self) {
+        self.broadcast.clear();
+        self.broadcast_missed.clear();
+    }
+
+    pub fn toggle_broadcast_pause(&mut self) {
+        self.broadcast_paused = !self.broadcast_paused;
+    }
+
+    fn toggle_broadcast(&mut self, key: u64) {
+        if !self.broadcast.remove(&key) {
+            self.broadcast.insert(key);
+        }
+        self.broadcast_missed.remove(&key);
+    }
+
+    // input typed into a member of the group is copied to every pane of the other members
+    fn wire_broadcast(&mut self) {
+        let key = match self.items.get(self.selected) {
+            Some(tab) => tab.key,
+            None => return,
+        };
+        let member = self.broadcast.contains(&key);
+        // the user is looking at it now
+        self.broadcast_missed.remove(&key);
+        let targets: Vec<_> = if member && !self.broadcast_paused {
+            self.items.iter()
+                .filter(|tab| tab.key != key && self.broadcast.contains(&tab.key))
+                .flat_map(|tab| tab.panes.views().into_iter().map(move |view| (tab.key, view.sender())))
+                .collect()
+        } else {
+            Vec::new()
+        };
+        let missed = self.broadcast_missed.len();
+        let banner = Some((self.broadcast.len(), self.broadcast_paused, missed)).filter(|_| member);
+        let focused = self.items[self.selected].panes.focused_index();
+        for (i, view) in self.items[self.selected].panes.views_mut().into_iter().enumerate() {
+            if i == focused {
+                view.set_broadcast(targets.clone(), banner);
+            } else {
+                view.set_broadcast(Vec::new(), banner);
+            }
+        }
+    }
+
+    fn remove(&mut self, key: u64) {
+        if let Some(i) = self.items.iter().position(|tab| tab.key == key) {
+            self.items.remove(i);
+            self.broadcast.remove(&key);
+            self.broadcast_missed.remove(&key);
+            self.grouped.remove(&key);
+            // the tab that moved into the closed one's place, or the new last one
+            if i < self.selected || self.selected >= self.items.len() {
+                self.selected = self.selected.saturating_sub(1);
+            }
+            if let Some(tab) = self.items.get_mut(self.selected) {
+                tab.panes.focus();
+            }
+        }
+    }
+
+    fn request_close(&mut self, keys: Vec<u64>) {
+        self.close_tabs(keys, None);
+    }
+
+    // closes right away unless a live session would go with the tabs
+    fn close_tabs(&mut self, keys: Vec<u64>, then: Option<TabRequest>) {
+        let live = self.items.iter()
+            .filter(|tab| keys.contains(&tab.key))
+            .any(|tab| tab.panes.views().iter().any(|view| view.live()));
+        if live {
+            self.confirm_close = Some(Closing::Tabs(keys, then));
+        } else {
+            self.remove_tabs(keys, then);
+        }
+    }
+
+    fn remove_tabs(&mut self, keys: Vec<u64>, then: Option<TabRequest>) {
+        for key in keys {
+            self.remove(key);
+        }
+        self.requests.extend(then);
+    }
+
+    fn show_confirm_close(&mut self, ctx: &egui::CtxRef) {
+        let (title, question) = match &self.confirm_close {
+            Some(Closing::Tabs(keys, _)) => {
+                let live = self.items.iter()
+                    .filter(|tab| keys.contains(&tab.key) && tab.panes.views().iter().any(|view| view.live()))
+                    .count();
+                ("Close tabs", format!("{} of the tabs still have a live session, close anyway?", live))
+            }
+            Some(Closing::Pane(..)) => ("Close pane", "the pane still has a live session, close anyway?".to_string()),
+            None => return,
+        };
+        let mut answer = None;
+        egui::Window::new(title)
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, egui::vec2(0.0, 0.0))
+            .show(ctx, |ui| {
+                ui.label(question);
+                ui.horizontal(|ui| {
+                    if ui.button("Close").clicked() {
+                        answer = Some(true);
+                    }
+                    if ui.button("Cancel").clicked() {
+                        answer = Some(false);
+                    }
+                });
+            });
+        match answer {
+            Some(true) => match self.confirm_close.take() {
+                Some(Closing::Tabs(keys, then)) => self.remove_tabs(keys, then),
+                Some(Closing::Pane(key, index)) => self.remove_pane(key, index),
+                None => {}
+            },
+            Some(false) => self.confirm_close = None,
+            None => {}
+        }
+    }
+
+    // the selected tab never collects alerts, the user is looking at it
+    fn update_alerts(&mut self) {
+        for (i, tab) in self.items.iter_mut().enumerate() {
+            for view in tab.panes.views() {
+                let (activity, bell) = view.take_alerts();
+                tab.activity |= activity;
+                tab.bell |= bell;
+            }
+            if i == self.selected {
+                tab.activity = false;
+                tab.bell = false;
+            }
+        }
+    }
+
+    fn state(&self, tab: &Tab) -> Option<TabState> {
+        let views = tab.panes.views();
+        if views.iter().all(|view| view.read_only()) {
+            return None;
+        }
+        if let Some(error) = views.iter().find_map(|view| view.closed()) {
+            Some(TabState::Disconnected(error))
+        } else if views.iter().all(|view| view.session().is_some()) {
+            Some(TabState::Connected)
+        } else {
+            Some(TabState::Connecting)
+        }
+    }
+
+    fn menu_action(&mut self, key: u64, action: TabMenu) {
+        let tab = match self.items.iter().find(|tab| tab.key == key) {
+            Some(tab) => tab,
+            None => return,
+        };
+        // a recording has no profile to open again
+        let reopen = self.state(tab).is_some();
+        match action {
+            TabMenu::Rename => {
+                self.renaming = Some(Rename { key, text: tab.title.clone(), focus: true });
+            }
+            TabMenu::Duplicate if reopen => {
+                if let Some(layout) = tab.panes.layout() {
+                    self.requests.push(TabRequest::Duplicate(tab.title.clone(), layout));
+                }
+            }
+            TabMenu::Reconnect if reopen => {
+                if let Some(layout) = tab.panes.layout() {
+                    self.requests.push(TabRequest::Reconnect(key, layout));
+                }
+            }
+            TabMenu::Broadcast => self.toggle_broadcast(key),
+            TabMenu::Close => self.request_close(vec![key]),
+            TabMenu::CloseOthers => {
+                let others = self.items.iter().map(|tab| tab.key).filter(|k| *k != key).collect();
+                self.request_close(others);
+            }
+            TabMenu::Duplicate | TabMenu::Reconnect => {}
+        }
+    }
+
+    fn finish_rename(&mut self, commit: bool) {
+        if let Some(rename) = self.renaming.take() {
+            let title = rename.text.trim();
+            if commit && !title.is_empty() {
+                if let Some(tab) = self.items.iter_mut().find(|tab| tab.key == rename.key) {
+                    tab.title = title.to_string();
+                }
+            }
+        }
+    }
+
+    // the dragged tab takes the place of the one under the pointer
+    fn drag_to(&mut self, key: u64, x: f32, rects: &[egui::Rect]) {
+        let from = match self.items.iter().position(|tab| tab.key == key) {
+            Some(i) => i,
+            None => return,
+        };
+        let to = match rects.iter().position(|rect| x >= rect.left() && x < rect.right()) {
+            Some(i) => i,
+            None => return,
+        };
+        if from == to {
+            return;
+        }
+        let selected = self.items[self.selected].key;
+        let tab = self.items.remove(from);
+        self.items.insert(to, tab);
+        self.selected = self.items.iter().position(|tab| tab.key == selected).unwrap_or(0);
+    }
+
+    // ctrl+alt+arrows move between the panes of the selected tab
+    fn navigate(&mut self, ui: &egui::Ui) {
+        let input = ui.input();
+        if !(input.modifiers.command && input.modifiers.alt) {
+            return;
+        }
+        let to = if input.key_pressed(egui::Key::ArrowLeft) {
+            Move::Left
+        } else if input.key_pressed(egui::Key::ArrowRight) {
+            Move::Right
+        } else if input.key_pressed(egui::Key::ArrowUp) {
+            Move::Up
+        } else if input.key_pressed(egui::Key::ArrowDown) {
+            Move::Down
+        } else {
+            return;
+        };
+        if let Some(tab) = self.items.get_mut(self.selected) {
+            tab.panes.move_focus(to);
+        }
+    }
+
+    pub fn set_status(&mut self, status: HashMap<String, egui::Color32>) {
+        self.status = status;
+    }
+
+    // puts a tab next to the others on its session once it connects, in order of the first one opened
+    fn regroup(&mut self) {
+        let sessions: Vec<Option<u64>> = self.items.iter().map(|tab| tab.session()).collect();
+        let connected: Vec<u64> = self.items.iter().zip(&sessions)
+            .filter(|(_, s)| s.is_some())
+            .map(|(tab, _)| tab.key)
+            .collect();
+        if connected.iter().all(|key| self.grouped.contains(key)) {
+            return;
+        }
+        self.grouped.extend(connected);
+        let mut order: Vec<(usize, usize)> = sessions.iter()
+            .enumerate()
+            .map(|(i, s)| (s.and_then(|s| sessions.iter().position(|x| *x == Some(s))).unwrap_or(i), i))
+            .collect();
+        order.sort();
+        if order.iter().enumerate().all(|(pos, (_, i))| pos == *i) {
+            return;
+        }
+        let mut items: Vec<Option<Tab>> = self.items.drain(..).map(Some).collect();
+        for (pos, (_, i)) in order.iter().enumerate() {
+            if *i == self.selected {
+                self.selected = pos;
+                break;
+            }
+        }
+        self.items = order.iter().map(|(_, i)| items[*i].take().unwrap()).collect();
+    }
+
+    // a colour for each session that has more than one tab
+    fn groups(&self) -> HashMap<u64, egui::Color32> {
+        let mut counts: HashMap<u64, usize> = HashMap::new();
+        for s in self.items.iter().filter_map(|tab| tab.session()) {
+            *counts.entry(s).or_default() += 1;
+        }
+        counts.into_iter()
+            .filter(|(_, n)| *n > 1)
+            .map(|(s, _)| (s, GROUP_COLORS[s as usize % GROUP_COLORS.len()]))
+            .collect()
+    }
+
     fn paint_tab_bar(&mut self, ui: &mut egui::Ui) -> bool {
         ui.allocate_ui_with_layout(
             egui::vec2(
@@ -56,33 +583,61 @@ impl TabView {
                     scroll.horizontal_scroll_offset(self.offset)
                 };
                 let mut changed = false;
-                scroll.show(ui, |ui| {
-                    let mut remove: Option<usize> = None;
+                self.regroup();
+                let groups = self.groups();
+                let states: Vec<Option<TabState>> = self.items.iter().map(|tab| self.state(tab)).collect();
+                let (rects, events) = scroll.show(ui, |ui| {
+                    let mut rects = Vec::new();
+                    let mut events = Vec::new();
                     for (i, item) in self.items.iter().enumerate() {
-                        let mut close = false;
-                        let mut click = false;
-                        ui.add(TabItem::new(
-                            &item.0,
+                        let mut e = TabEvents::default();
+                        let member = self.broadcast.contains(&item.key);
+                        let state = states[i].as_ref();
+                        let rename = self.renaming.as_mut().filter(|r| r.key == item.key);
+                        let response = ui.add(TabItem::new(
+                            &item.title,
+                            state.map(|s| match s {
+                                TabState::Connecting => self.status.get(&item.id).copied().unwrap_or_else(|| s.color()),
+                                _ => s.color(),
+                            }),
+                            (item.activity, item.bell),
+                            item.session().and_then(|s| groups.get(&s).copied()),
+                            Some((self.broadcast_paused, self.broadcast_missed.contains(&item.key))).filter(|_| member),
                             i == self.selected,
-                            &mut close,
-                            &mut click,
+                            rename,
+                            &mut e,
                         ));
-                        if close {
-                            remove = Some(i);
-                            changed = true;
-                        }
-                        if click {
-                            self.selected = i;
-                            changed = true;
-                        }
-                    }
-                    if let Some(i) = remove {
-                        self.items.remove(i);
-                        if self.selected > 0 {
-                            self.selected -= 1;
+                        if let Some(state) = state {
+                            response.on_hover_text(state.describe());
                         }
+                        rects.push(e.rect.unwrap_or(egui::Rect::NOTHING));
+                        events.push((item.key, e));
                     }
+                    (rects, events)
                 });
+                // by key, a menu action may have closed tabs in front of it
+                for (key, e) in events {
+                    if let (true, Some(i)) = (e.click, self.items.iter().position(|tab| tab.key == key)) {
+                        self.selected = i;
+                        changed = true;
+                    }
+                    if e.drag {
+                        self.dragging = Some(key);
+                    }
+                    if let Some(commit) = e.rename {
+                        self.finish_rename(commit);
+                    }
+                    if let Some(action) = e.menu {
+                        self.menu_action(key, action);
+                    }
+                }
+                let pointer = ui.input().pointer.clone();
+                if let Some(key) = self.dragging {
+                    match pointer.interact_pos() {
+                        Some(pos) if pointer.any_down() => self.drag_to(key, pos.x, &rects),
+                        _ => self.dragging = None,
+                    }
+                }
                 changed
             },
         ).inner
@@ -95,11 +650,23 @@ impl egui::Widget for &mut TabView {
             ui.available_size(),
             egui::Layout::top_down(egui::Align::LEFT),
             |ui| {
+                self.show_toasts(ui.ctx());
+                self.show_confirm_close(ui.ctx());
+                self.update_alerts();
                 let changed = self.paint_tab_bar(ui);
-                if let Some((_, view)) = self.items.get_mut(self.selected) {
-                    let response = ui.add(view);
+                let input = ui.input();
+                if input.modifiers.command && input.modifiers.shift && input.key_pressed(egui::Key::B) {
+                    self.toggle_broadcast_pause();
+                }
+                self.navigate(ui);
+                self.wire_broadcast();
+                if let Some(tab) = self.items.get_mut(self.selected) {
                     if changed {
-                        response.request_focus();
+                        tab.panes.focus();
+                    }
+                    ui.add(&mut tab.panes);
+                    for view in tab.panes.views_mut() {
+                        self.broadcast_missed.extend(view.take_broadcast_missed());
                     }
                 }
             },
@@ -108,32 +675,77 @@ impl egui::Widget for &mut TabView {
     }
 }
 
+impl Tab {
+    // tabs are grouped by the session of their first pane
+    fn session(&self) -> Option<u64> {
+        self.panes.views().first().and_then(|view| view.session())
+    }
+}
+
+#[derive(Clone, Copy)]
+enum TabMenu {
+    Rename,
+    Duplicate,
+    Reconnect,
+    Broadcast,
+    Close,
+    CloseOthers,
+}
+
+// what happened to a tab this frame
+#[derive(Default)]
+struct TabEvents {
+    rect: Option<egui::Rect>,
+    click: bool,
+    drag: bool,
+    // Some(commit) when the rename field is left
+    rename: Option<bool>,
+    menu: Option<TabMenu>,
+}
+
 struct TabItem<'a> {
     title: &'a String,
+    status: Option<egui::Color32>,
+    // activity, bell
+    alerts: (bool, bool),
+    group: Option<egui::Color32>,
+    // Some((paused, missed input)) for members of the broadcast group
+    broadcast: Option<(bool, bool)>,
     selected: bool,
-    close: &'a mut bool,
-    click: &'a mut bool,
+    rename: Option<&'a mut Rename>,
+    events: &'a mut TabEvents,
 }
 
 impl<'a> TabItem<'a> {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         title: &'a String,
+        status: Option<egui::Color32>,
+        alerts: (bool, bool),
+        group: Option<egui::Color32>,
+        broadcast: Option<(bool, bool)>,
         selected: bool,
-        close: &'a mut bool,
-        click: &'a mut bool,
+        rename: Option<&'a mut Rename>,
+        events: &'a mut TabEvents,
     ) -> Self {
         Self {
             title,
+            status,
+            alerts,
+            group,
+            broadcast,
             selected,
-            close,
-            click,
+            rename,
+            events,
         }
     }
 }
 
 impl<'a> egui::Widget for TabItem<'a> {
     fn ui(self, ui: &mut egui::Ui) -> egui::Response {
-        ui.allocate_ui_with_layout(
+        let events = self.events;
+        let rename = self.rename;
+        let response = ui.allocate_ui_with_layout(
             egui::vec2(ITEM_WIDTH, ui.available_height()),
             egui::Layout::right_to_left(),
             |ui| {
@@ -150,30 +762,101 @@ impl<'a> egui::Widget for TabItem<'a> {
                         ui.style().visuals.window_stroke(),
                     );
                 }
+                if let Some((paused, missed)) = self.broadcast {
+                    let color = if missed {
+                        egui::Color32::RED
+                    } else if paused {
+                        egui::Color32::GRAY
+                    } else {
+                        egui::Color32::from_rgb(255, 140, 0)
+                    };
+                    ui.painter().rect_stroke(ui.available_rect_before_wrap().shrink(1.0), 5.0, egui::Stroke::new(2.0, color));
+                }
+                if let Some(color) = self.group {
+                    let rect = ui.available_rect_before_wrap();
+                    ui.painter().line_segment(
+                        [rect.left_bottom() + egui::vec2(5.0, -1.0), rect.right_bottom() + egui::vec2(-5.0, -1.0)],
+                        egui::Stroke::new(2.0, color),
+                    );
+                }
                 let btn = egui::Button::new(
                     egui::RichText::new(char::from_u32(0x1f5d9).unwrap())
                         .text_style(egui::TextStyle::Small),
                 )
                 .frame(false);
                 if ui.add(btn).clicked() {
-                    *self.close = true;
+                    events.menu = Some(TabMenu::Close);
                 }
                 ui.with_layout(egui::Layout::left_to_right(), |ui| {
                     let rect = ui.available_rect_before_wrap();
+                    let mut text_pos = rect.left_center();
+                    if let Some(color) = self.status {
+                        ui.painter().circle_filled(text_pos + egui::vec2(6.0, 0.0), 4.0, color);
+                        text_pos.x += 14.0;
+                    }
+                    let (activity, bell) = self.alerts;
+                    let badge = if bell {
+                        Some(("\u{1f514}", egui::Color32::YELLOW))
+                    } else if activity {
+                        Some(("\u{2022}", egui::Color32::LIGHT_BLUE))
+                    } else {
+                        None
+                    };
+                    if let Some((text, color)) = badge {
+                        ui.painter().text(text_pos, egui::Align2::LEFT_CENTER, text, egui::TextStyle::Small, color);
+                        text_pos.x += 14.0;
+                    }
+                    if let Some(rename) = rename {
+                        let edit_rect = egui::Rect::from_min_max(egui::pos2(text_pos.x, rect.top()), rect.max);
+                        let edit = ui.put(edit_rect, egui::TextEdit::singleline(&mut rename.text));
+                        if rename.focus {
+                            edit.request_focus();
+                            rename.focus = false;
+                        } else if ui.input().key_pressed(egui::Key::Escape) {
+                            events.rename = Some(false);
+                        } else if edit.lost_focus() {
+                            events.rename = Some(true);
+                        }
+                        return;
+                    }
                     ui.painter().text(
-                        rect.left_center(),
+                        text_pos,
                         egui::Align2::LEFT_CENTER,
                         self.title,
                         egui::TextStyle::Button,
                         ui.style().visuals.text_color(),
                     );
-                    let response = ui.allocate_rect(rect, egui::Sense::click());
-                    if response.clicked() {
-                        *self.click = true;
+                    let response = ui.allocate_rect(rect, egui::Sense::click_and_drag());
+                    if response.double_clicked() {
+                        events.menu = Some(TabMenu::Rename);
+                    } else if response.clicked() {
+                        events.click = true;
+                    }
+                    if response.drag_started() {
+                        events.drag = true;
                     }
+                    let member = self.broadcast.is_some();
+                    response.context_menu(|ui| {
+                        let items = [
+                            ("Rename", TabMenu::Rename),
+                            ("Duplicate", TabMenu::Duplicate),
+                            ("Reconnect", TabMenu::Reconnect),
+                            (if member { "Leave broadcast group" } else { "Join broadcast group" }, TabMenu::Broadcast),
+                            ("Close", TabMenu::Close),
+                            ("Close others", TabMenu::CloseOthers),
+                        ];
+                        for (label, action) in items {
+                            if ui.button(label).clicked() {
+                                events.menu = Some(action);
+                                ui.close_menu();
+                            }
+                        }
+                    });
                 });
             },
         )
-        .response
+        .response;
+        events.rect = Some(response.rect);
+        response
     }
 }
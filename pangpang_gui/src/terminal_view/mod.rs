@@ -1,4 +1,4 @@
-use std::sync::Arc;
+use std::{collections::HashSet, sync::Arc};
 
 use eframe::{egui, epi};
 use pangpang::{
@@ -36,6 +36,24 @@ pub struct TerminalView {
     sender: pangpang::terminal::msg::PpTerminalMessageSender,
     window_size: egui::Vec2,
     mouse_primary_key_down: bool,
+    search: SearchBar,
+    // playback of a recording, keys are not sent anywhere
+    read_only: bool,
+    // the other members' terminals, by tab key
+    broadcast: Vec<(u64, pangpang::terminal::msg::PpTerminalMessageSender)>,
+    // tabs whose terminal could not take the input, picked up by the tab view
+    broadcast_missed: HashSet<u64>,
+    // group size, paused and members that missed input, while this terminal is in the broadcast group
+    broadcast_banner: Option<(usize, bool, usize)>,
+}
+
+#[derive(Default)]
+struct SearchBar {
+    open: bool,
+    focus: bool,
+    text: String,
+    regex: bool,
+    sent: (String, bool),
 }
 
 impl TerminalView {
@@ -48,11 +66,176 @@ impl TerminalView {
             sender,
             window_size: egui::vec2(0.0, 0.0),
             mouse_primary_key_down: false,
+            search: SearchBar::default(),
+            read_only: false,
+            broadcast: Vec::new(),
+            broadcast_missed: HashSet::new(),
+            broadcast_banner: None,
+        }
+    }
+
+    pub fn sender(&self) -> pangpang::terminal::msg::PpTerminalMessageSender {
+        self.sender.clone()
+    }
+
+    pub fn set_broadcast(&mut self, targets: Vec<(u64, pangpang::terminal::msg::PpTerminalMessageSender)>, banner: Option<(usize, bool, usize)>) {
+        self.broadcast = targets;
+        self.broadcast_banner = banner;
+    }
+
+    pub fn take_broadcast_missed(&mut self) -> HashSet<u64> {
+        std::mem::take(&mut self.broadcast_missed)
+    }
+
+    pub fn player(
+        sender: pangpang::terminal::msg::PpTerminalMessageSender,
+        frame: epi::Frame,
+    ) -> Self {
+        Self { read_only: true, ..Self::new(sender, frame) }
+    }
+
+    pub fn read_only(&self) -> bool {
+        self.read_only
+    }
+
+    // nothing is lost by closing a recording or a terminal whose pty already ended
+    pub fn live(&self) -> bool {
+        !self.read_only && self.render_state.blocking_lock().closed().is_none()
+    }
+
+    pub fn closed(&self) -> Option<Option<String>> {
+        self.render_state.blocking_lock().closed().cloned()
+    }
+
+    pub fn take_alerts(&self) -> (bool, bool) {
+        self.render_state.blocking_lock().take_alerts()
+    }
+
+    pub fn take_notifications(&self) -> Vec<(String, String)> {
+        self.render_state.blocking_lock().take_notifications()
+    }
+
+    pub fn toggle_recording(&self, title: &str) {
+        let path = match self.render_state.blocking_lock().recording() {
+            Some(_) => None,
+            None => Some(pangpang::terminal::default_recording_path(title)),
+        };
+        self.write_pty(pangpang::terminal::msg::PpTerminalMessage::Record(path));
+    }
+
+    fn playback_bar(&mut self, ui: &mut egui::Ui) {
+        use pangpang::terminal::msg::{PpTerminalMessage, PlaybackControl};
+        let state = match self.render_state.blocking_lock().playback() {
+            Some(state) => state.clone(),
+            None => return,
+        };
+        let mut controls = Vec::new();
+        ui.horizontal(|ui| {
+            if state.paused {
+                if ui.button("\u{25b6}").clicked() {
+                    controls.push(PlaybackControl::Resume);
+                }
+            } else if ui.button("\u{23f8}").clicked() {
+                controls.push(PlaybackControl::Pause);
+            }
+            let mut position = state.position.as_secs_f64();
+            let duration = state.duration.as_secs_f64();
+            let slider = egui::Slider::new(&mut position, 0.0..=duration)
+                .show_value(false);
+            if ui.add(slider).changed() {
+                controls.push(PlaybackControl::Seek(std::time::Duration::from_secs_f64(position)));
+            }
+            ui.label(format!("{:.0}s / {:.0}s", position, duration));
+            let mut speed = state.speed;
+            egui::ComboBox::from_id_source("playback speed")
+                .selected_text(format!("{}x", speed))
+                .show_ui(ui, |ui| {
+                    for s in [0.5, 1.0, 2.0, 4.0, 8.0] {
+                        ui.selectable_value(&mut speed, s, format!("{}x", s));
+                    }
+                });
+            if speed != state.speed {
+                controls.push(PlaybackControl::Speed(speed));
+            }
+        });
+        for control in controls {
+            self.write_pty(PpTerminalMessage::Playback(control));
         }
+        if !state.paused {
+            ui.ctx().request_repaint();
+        }
+    }
+
+    pub fn session(&self) -> Option<u64> {
+        self.render_state.blocking_lock().session()
+    }
+
+    pub fn cwd(&self) -> Option<String> {
+        self.render_state.blocking_lock().cwd()
+    }
+
+    pub fn open_search(&mut self) {
+        self.search.open = true;
+        self.search.focus = true;
+    }
+
+    fn close_search(&mut self) {
+        self.search.open = false;
+        self.search.sent = (String::new(), false);
+        self.write_pty(pangpang::terminal::msg::PpTerminalMessage::Search(String::new(), false));
+    }
+
+    fn search_bar(&mut self, ui: &mut egui::Ui) {
+        use pangpang::terminal::msg::PpTerminalMessage;
+        let state = self.render_state.blocking_lock().search().clone();
+        let bar = &mut self.search;
+        let (mut previous, mut next, mut close) = (false, false, false);
+        ui.horizontal(|ui| {
+            ui.label("find");
+            let edit = ui.text_edit_singleline(&mut bar.text);
+            if bar.focus {
+                edit.request_focus();
+                bar.focus = false;
+            }
+            ui.checkbox(&mut bar.regex, "regex");
+            // enter walks up the scrollback, from the newest match
+            let enter = edit.lost_focus() && ui.input().key_pressed(egui::Key::Enter);
+            if enter {
+                edit.request_focus();
+            }
+            previous = ui.button("\u{25b2}").clicked() || enter;
+            next = ui.button("\u{25bc}").clicked();
+            match (&state.error, state.focused) {
+                (Some(e), _) => ui.colored_label(egui::Color32::RED, e),
+                (None, Some(i)) => ui.label(format!("{}/{}", i + 1, state.matches.len())),
+                (None, None) if bar.text.is_empty() => ui.label(""),
+                (None, None) => ui.label("no matches"),
+            };
+            close = ui.button("\u{2716}").clicked() || ui.input().key_pressed(egui::Key::Escape) && edit.has_focus();
+        });
+        if previous {
+            self.write_pty(PpTerminalMessage::SearchPrevious);
+        }
+        if next {
+            self.write_pty(PpTerminalMessage::SearchNext);
+        }
+        if close {
+            self.close_search();
+            return;
+        }
+        let query = (self.search.text.clone(), self.search.regex);
+        if query != self.search.sent {
+            self.search.sent = query.clone();
+            self.write_pty(PpTerminalMessage::Search(query.0, query.1));
+        }
+    }
+
+    pub fn send_break(&self) {
+        self.write_pty(pangpang::terminal::msg::PpTerminalMessage::Break);
     }
 
     fn write_pty(&self, msg: pangpang::terminal::msg::PpTerminalMessage) {
-        if let Err(_) = self.sender.blocking_send(msg) {
+        if self.sender.blocking_send(msg).is_err() {
             println!("connection lost!");
         }
     }
@@ -78,6 +261,9 @@ impl TerminalView {
                 } if *pressed => {
                     modifiers_state = *modifiers;
                     match *key {
+                        // moving between panes, handled by the tab view
+                        egui::Key::ArrowUp | egui::Key::ArrowDown | egui::Key::ArrowLeft | egui::Key::ArrowRight
+                            if modifiers.command && modifiers.alt => {}
                         egui::Key::ArrowUp => {
                             input_sequence.push(C0::ESC);
                             if modifiers.any() {
@@ -154,25 +340,14 @@ impl TerminalView {
                             }
                             input_sequence.push(b'~');
                         }
-                        egui::Key::C => {
-                            if modifiers.command {
-                                input_sequence.push(b'C' - b'@');
-                            }
-                        }
-                        egui::Key::Z => {
-                            if modifiers.command {
-                                input_sequence.push(b'Z' - b'@');
-                            }
-                        }
+                        egui::Key::C if modifiers.command => input_sequence.push(b'C' - b'@'),
+                        egui::Key::Z if modifiers.command => input_sequence.push(b'Z' - b'@'),
+                        egui::Key::F if modifiers.command && modifiers.shift => self.open_search(),
                         _ => {}
                     };
                 }
-                egui::Event::Text(text) => {
-                    if modifiers_state.is_none() || modifiers_state.shift_only() {
-                        for byte in text.bytes() {
-                            input_sequence.push(byte);
-                        }
-                    }
+                egui::Event::Text(text) if modifiers_state.is_none() || modifiers_state.shift_only() => {
+                    input_sequence.extend_from_slice(text.as_bytes());
                 }
                 egui::Event::PointerButton {
                     pos,
@@ -208,54 +383,56 @@ impl TerminalView {
                         ));
                     }
                 }
-                egui::Event::PointerMoved(pos) => {
-                    if self.mouse_primary_key_down {
-                        let cursor = galley
-                            .cursor_from_pos(pos.to_vec2() - ui.min_rect().min.to_vec2())
-                            .pcursor;
-                        let paragraph: i32 = cursor.paragraph.try_into().unwrap();
-                        let scroll: i32 = state.display_offset().try_into().unwrap();
-                        let line = paragraph - scroll;
-                        self.write_pty(
-                            pangpang::terminal::msg::PpTerminalMessage::SelectionUpdate(
-                                line,
-                                cursor.offset,
-                            ),
-                        );
-                    }
+                egui::Event::PointerMoved(pos) if self.mouse_primary_key_down => {
+                    let cursor = galley
+                        .cursor_from_pos(pos.to_vec2() - ui.min_rect().min.to_vec2())
+                        .pcursor;
+                    let paragraph: i32 = cursor.paragraph.try_into().unwrap();
+                    let scroll: i32 = state.display_offset().try_into().unwrap();
+                    let line = paragraph - scroll;
+                    self.write_pty(
+                        pangpang::terminal::msg::PpTerminalMessage::SelectionUpdate(
+                            line,
+                            cursor.offset,
+                        ),
+                    );
                 }
                 _ => {}
             };
         }
-        if !input_sequence.is_empty() {
+        if !input_sequence.is_empty() && !self.read_only {
+            // a full or closed member must not stall the focused terminal, it is marked instead
+            for (key, target) in &self.broadcast {
+                if target.try_send(pangpang::terminal::msg::PpTerminalMessage::Input(input_sequence.clone())).is_err() {
+                    self.broadcast_missed.insert(*key);
+                }
+            }
             self.write_pty(pangpang::terminal::msg::PpTerminalMessage::Input(
                 input_sequence,
             ));
         }
         let scroll_delta = ui.input().scroll_delta.y;
-        if scroll_delta != 0.0 {
-            if ui.rect_contains_pointer(ui.max_rect()) {
-                if let Some(pos) = ui.input().pointer.hover_pos() {
-                    if self.mouse_primary_key_down {
-                        let cursor = galley
-                            .cursor_from_pos(pos.to_vec2() - ui.min_rect().min.to_vec2())
-                            .pcursor;
-                        let paragraph: i32 = cursor.paragraph.try_into().unwrap();
-                        let scroll: i32 = state.display_offset().try_into().unwrap();
-                        let line = paragraph - scroll;
-                        self.write_pty(
-                            pangpang::terminal::msg::PpTerminalMessage::SelectionUpdate(
-                                line,
-                                cursor.offset,
-                            ),
-                        );
-                    }
+        if scroll_delta != 0.0 && ui.rect_contains_pointer(ui.max_rect()) {
+            if let Some(pos) = ui.input().pointer.hover_pos() {
+                if self.mouse_primary_key_down {
+                    let cursor = galley
+                        .cursor_from_pos(pos.to_vec2() - ui.min_rect().min.to_vec2())
+                        .pcursor;
+                    let paragraph: i32 = cursor.paragraph.try_into().unwrap();
+                    let scroll: i32 = state.display_offset().try_into().unwrap();
+                    let line = paragraph - scroll;
+                    self.write_pty(
+                        pangpang::terminal::msg::PpTerminalMessage::SelectionUpdate(
+                            line,
+                            cursor.offset,
+                        ),
+                    );
                 }
-
-                self.write_pty(pangpang::terminal::msg::PpTerminalMessage::Scroll(
-                    (scroll_delta / ui.fonts().row_height(egui::TextStyle::Monospace)) as i32,
-                ));
             }
+
+            self.write_pty(pangpang::terminal::msg::PpTerminalMessage::Scroll(
+                (scroll_delta / ui.fonts().row_height(egui::TextStyle::Monospace)) as i32,
+            ));
         }
     }
 
@@ -276,7 +453,7 @@ impl TerminalView {
             .left_top();
         ui.output().text_cursor_pos = Some(cursor_pos);
         ui.painter().galley(terminal_pos, galley.clone());
-        let visuals = ui.style().interact(&response);
+        let visuals = ui.style().interact(response);
         let shape = if response.has_focus() {
             self.input_state(ui, state, galley);
             egui::epaint::RectShape {
@@ -308,6 +485,27 @@ impl TerminalView {
 
 impl egui::Widget for &mut TerminalView {
     fn ui(self, ui: &mut egui::Ui) -> egui::Response {
+        if self.read_only {
+            self.playback_bar(ui);
+        }
+        match self.broadcast_banner {
+            Some((n, false, _)) => {
+                ui.colored_label(egui::Color32::from_rgb(255, 140, 0), format!("broadcasting input to {} terminals", n));
+            }
+            Some((n, true, _)) => {
+                ui.colored_label(egui::Color32::GRAY, format!("broadcast to {} terminals paused (ctrl+shift+b)", n));
+            }
+            None => {}
+        }
+        if let Some((_, _, missed)) = self.broadcast_banner.filter(|b| b.2 > 0) {
+            ui.colored_label(egui::Color32::RED, format!("{} terminals missed broadcast input, their tabs are marked red", missed));
+        }
+        if let Some(path) = self.render_state.blocking_lock().recording() {
+            ui.colored_label(egui::Color32::RED, format!("\u{25cf} recording to {}", path));
+        }
+        if self.search.open {
+            self.search_bar(ui);
+        }
         let (id, rect) = ui.allocate_space(ui.available_size());
         let mut child_ui = ui.child_ui(rect, *ui.layout());
         let response = child_ui.interact(rect, id, egui::Sense::click_and_drag());
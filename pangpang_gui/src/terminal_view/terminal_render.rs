@@ -1,7 +1,7 @@
 
 
 use eframe::{egui::{text::LayoutJob, TextStyle, Color32, TextFormat, Stroke}, epi};
-use pangpang::alacritty_terminal::{term::{TermMode, self}, ansi::{NamedColor, Color}};
+use pangpang::{alacritty_terminal::{term::{TermMode, self}, ansi::{NamedColor, Color}}, terminal::{SearchState, PlaybackState}};
 
 
 
@@ -14,6 +14,17 @@ pub struct TerminalRender{
     cursor_pos: (usize, usize),
     display_offset: usize,
     frame: epi::Frame,
+    session: Option<u64>,
+    cwd: Option<String>,
+    search: SearchState,
+    recording: Option<String>,
+    playback: Option<PlaybackState>,
+    notifications: Vec<(String, String)>,
+    highlights: Vec<i32>,
+    // Some once the pty is gone, with the error if there was one
+    closed: Option<Option<String>>,
+    activity: bool,
+    bell: bool,
 }
 
 
@@ -26,6 +37,16 @@ impl TerminalRender {
             cursor_pos: (0, 0),
             display_offset: 0,
             frame,
+            session: None,
+            cwd: None,
+            search: SearchState::default(),
+            recording: None,
+            playback: None,
+            notifications: Vec::new(),
+            highlights: Vec::new(),
+            closed: None,
+            activity: false,
+            bell: false,
         }
     }
 
@@ -44,21 +65,101 @@ impl TerminalRender {
     pub fn display_offset(&self) -> usize {
         self.display_offset
     }
+
+    pub fn session(&self) -> Option<u64> {
+        self.session
+    }
+
+    pub fn cwd(&self) -> Option<String> {
+        self.cwd.clone()
+    }
+
+    pub fn search(&self) -> &SearchState {
+        &self.search
+    }
+
+    pub fn recording(&self) -> Option<&String> {
+        self.recording.as_ref()
+    }
+
+    pub fn playback(&self) -> Option<&PlaybackState> {
+        self.playback.as_ref()
+    }
+
+    pub fn take_notifications(&mut self) -> Vec<(String, String)> {
+        std::mem::take(&mut self.notifications)
+    }
+
+    pub fn closed(&self) -> Option<&Option<String>> {
+        self.closed.as_ref()
+    }
+
+    // screen updates and bells since the last call
+    pub fn take_alerts(&mut self) -> (bool, bool) {
+        (std::mem::take(&mut self.activity), std::mem::take(&mut self.bell))
+    }
 }
 
 impl pangpang::terminal::Render for TerminalRender {
+    fn session(&mut self, serial: u64) {
+        self.session = Some(serial);
+        self.frame.request_repaint();
+    }
+
+    fn working_directory(&mut self, cwd: String) {
+        self.cwd = Some(cwd);
+    }
+
+    fn search(&mut self, state: &SearchState) {
+        self.search = state.clone();
+    }
+
+    fn recording(&mut self, path: Option<String>) {
+        self.recording = path;
+        self.frame.request_repaint();
+    }
+
+    fn playback(&mut self, state: &PlaybackState) {
+        self.playback = Some(state.clone());
+    }
+
+    fn notify(&mut self, message: String, line: String) {
+        self.notifications.push((message, line));
+        self.frame.request_repaint();
+    }
+
+    fn highlights(&mut self, lines: &[i32]) {
+        self.highlights = lines.to_vec();
+    }
+
+    fn bell(&mut self) {
+        self.bell = true;
+    }
+
+    fn closed(&mut self, error: Option<String>) {
+        self.closed = Some(error);
+        self.frame.request_repaint();
+    }
+
     fn draw(&mut self, render: pangpang::terminal::TerminalRender) {
         if !self.is_visible {
             return
         }
+        self.activity = true;
         self.mode = render.mode;
         self.cursor_pos = (render.cursor.point.column.0, render.cursor.point.line.0.try_into().unwrap());
         self.display_offset = render.display_offset;
         self.layout = LayoutJob::default();
+        // only matches that can reach the viewport
+        let top = -(render.display_offset as i32);
+        let focused = self.search.focused.and_then(|i| self.search.matches.get(i)).cloned();
+        let matches: Vec<_> = self.search.matches.iter().filter(|m| m.end().line.0 >= top).collect();
         let mut first_char = true;
         for cell in render.display_iter {
-            let mut fmt = TextFormat::default();
-            fmt.style = TextStyle::Monospace;
+            let mut fmt = TextFormat {
+                style: TextStyle::Monospace,
+                ..Default::default()
+            };
             if cell.point.column == 0 && !first_char {
                 self.layout.append("\n", 0.0, fmt);
             }
@@ -77,6 +178,11 @@ impl pangpang::terminal::Render for TerminalRender {
                     fmt.underline = Stroke::new(2.0, fmt.color);
                 }
 
+                if self.highlights.contains(&cell.point.line.0) {
+                    fmt.color = Color32::BLACK;
+                    fmt.background = Color32::YELLOW;
+                }
+
                 if render.cursor.point == cell.point {
                     fmt.background = color_to_color32(Color::Named(NamedColor::Cursor));
                 }
@@ -87,6 +193,14 @@ impl pangpang::terminal::Render for TerminalRender {
                     }
                 }
 
+                if focused.as_ref().map(|m| m.contains(&cell.point)).unwrap_or(false) {
+                    fmt.color = Color32::BLACK;
+                    fmt.background = Color32::from_rgb(255, 165, 0);
+                } else if matches.iter().any(|m| m.contains(&cell.point)) {
+                    fmt.color = Color32::BLACK;
+                    fmt.background = Color32::from_rgb(180, 180, 0);
+                }
+
                 self.layout.append(cell.c.to_string().as_str(), 0.0, fmt);
             }
         }
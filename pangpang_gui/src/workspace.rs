@@ -0,0 +1,188 @@
+use std::collections::BTreeMap;
+
+use eframe::{egui, epi};
+use serde_json::{json, Value};
+
+use crate::pane_view::PaneLayout;
+
+const STORAGE_KEY: &str = "pangpang workspaces";
+pub const DEFAULT_WORKSPACE: &str = "default";
+
+#[derive(Clone)]
+pub struct TabLayout {
+    pub title: String,
+    pub layout: PaneLayout,
+}
+
+// what was open, window geometry is persisted by eframe itself
+#[derive(Clone, Default)]
+pub struct Workspace {
+    pub tabs: Vec<TabLayout>,
+    pub selected: usize,
+}
+
+impl Workspace {
+    fn to_json(&self) -> Value {
+        let tabs: Vec<Value> = self.tabs.iter()
+            .map(|t| json!({ "title": t.title, "layout": t.layout.to_json() }))
+            .collect();
+        json!({ "tabs": tabs, "selected": self.selected })
+    }
+
+    fn from_json(value: &Value) -> Self {
+        let tabs = value.get("tabs").and_then(Value::as_array).map(|tabs| {
+            tabs.iter().filter_map(|t| Some(TabLayout {
+                title: t.get("title")?.as_str()?.to_string(),
+                layout: PaneLayout::from_json(t.get("layout")?)?,
+            })).collect()
+        }).unwrap_or_default();
+        let selected = value.get("selected").and_then(Value::as_u64).unwrap_or(0) as usize;
+        Self { tabs, selected }
+    }
+}
+
+pub struct Workspaces {
+    current: String,
+    saved: BTreeMap<String, Workspace>,
+    // offered once after startup, nothing reconnects without asking
+    prompt: Option<String>,
+    // the current workspace was removed, its name stays until another one is picked
+    removed: bool,
+    new_name: String,
+}
+
+impl Workspaces {
+    pub fn new() -> Self {
+        Self {
+            current: DEFAULT_WORKSPACE.to_string(),
+            saved: BTreeMap::new(),
+            prompt: None,
+            removed: false,
+            new_name: String::new(),
+        }
+    }
+
+    pub fn load(&mut self, storage: &dyn epi::Storage) {
+        let value = match storage.get_string(STORAGE_KEY).and_then(|s| serde_json::from_str::<Value>(&s).ok()) {
+            Some(value) => value,
+            None => return,
+        };
+        if let Some(current) = value.get("current").and_then(Value::as_str) {
+            self.current = current.to_string();
+        }
+        if let Some(saved) = value.get("workspaces").and_then(Value::as_object) {
+            self.saved = saved.iter().map(|(name, w)| (name.clone(), Workspace::from_json(w))).collect();
+        }
+        if self.saved.get(&self.current).map(|w| !w.tabs.is_empty()).unwrap_or(false) {
+            self.prompt = Some(self.current.clone());
+        }
+    }
+
+    // the open tabs are kept under the current name, unless the restore prompt is still waiting
+    // or the current workspace was removed
+    pub fn save(&mut self, storage: &mut dyn epi::Storage, open: Workspace) {
+        if self.prompt.is_none() && !self.removed {
+            self.saved.insert(self.current.clone(), open);
+        }
+        let saved: serde_json::Map<String, Value> = self.saved.iter().map(|(k, w)| (k.clone(), w.to_json())).collect();
+        let current = if self.removed { DEFAULT_WORKSPACE } else { self.current.as_str() };
+        let value = json!({ "current": current, "workspaces": saved });
+        storage.set_string(STORAGE_KEY, value.to_string());
+    }
+
+    pub fn current(&self) -> &String {
+        &self.current
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        self.saved.keys().cloned().collect()
+    }
+
+    // stores the open tabs and returns the workspace to open in their place
+    pub fn switch(&mut self, name: &str, open: Workspace) -> Workspace {
+        if self.prompt.take().is_none() && !self.removed {
+            self.saved.insert(self.current.clone(), open);
+        }
+        self.removed = false;
+        self.current = name.to_string();
+        self.saved.get(name).cloned().unwrap_or_default()
+    }
+
+    pub fn save_as(&mut self, name: String, open: Workspace) {
+        self.prompt = None;
+        self.removed = false;
+        self.saved.insert(name.clone(), open);
+        self.current = name;
+    }
+
+    pub fn remove(&mut self, name: &str) {
+        self.saved.remove(name);
+        if self.current == name {
+            self.removed = true;
+        }
+    }
+
+    // the workspace to reconnect, once the user agreed
+    pub fn show_prompt(&mut self, ctx: &egui::CtxRef) -> Option<Workspace> {
+        let name = self.prompt.clone()?;
+        let tabs = self.saved.get(&name).map(|w| w.tabs.len()).unwrap_or(0);
+        let mut restore = None;
+        egui::Window::new("Restore workspace")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, egui::vec2(0.0, 0.0))
+            .show(ctx, |ui| {
+                ui.label(format!("workspace \"{}\" had {} tabs open, reconnect them?", name, tabs));
+                ui.horizontal(|ui| {
+                    if ui.button("Reconnect").clicked() {
+                        restore = Some(true);
+                    }
+                    if ui.button("Start empty").clicked() {
+                        restore = Some(false);
+                    }
+                });
+            });
+        match restore {
+            Some(true) => {
+                self.prompt = None;
+                self.saved.get(&name).cloned()
+            }
+            Some(false) => {
+                self.prompt = None;
+                None
+            }
+            None => None,
+        }
+    }
+
+    // the menu; returns the workspace to switch to, or a name to save the open tabs under
+    pub fn menu(&mut self, ui: &mut egui::Ui) -> Option<WorkspaceAction> {
+        let mut action = None;
+        for name in self.names() {
+            ui.horizontal(|ui| {
+                let label = if name == self.current { format!("\u{2714} {}", name) } else { name.clone() };
+                if ui.button(label).clicked() && name != self.current {
+                    action = Some(WorkspaceAction::Switch(name.clone()));
+                }
+                if ui.small_button("\u{2716}").clicked() {
+                    self.remove(&name);
+                }
+            });
+        }
+        ui.separator();
+        ui.horizontal(|ui| {
+            ui.text_edit_singleline(&mut self.new_name);
+            let name = self.new_name.trim().to_string();
+            if ui.add_enabled(!name.is_empty(), egui::Button::new("Save as")).clicked() {
+                action = Some(WorkspaceAction::SaveAs(name));
+                self.new_name.clear();
+            }
+        });
+        action
+    }
+}
+
+pub enum WorkspaceAction {
+    Switch(String),
+    SaveAs(String),
+}